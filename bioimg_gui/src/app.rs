@@ -8,6 +8,7 @@ use indoc::indoc;
 
 use bioimg_runtime as rt;
 use bioimg_runtime::zoo_model::{ModelPackingError, ZooModel};
+use bioimg_runtime::zip_writer_ext::{CompressionPolicy, PackingProgress};
 use bioimg_spec::rdf;
 use bioimg_spec::rdf::ResourceId;
 use bioimg_spec::rdf::bounded_string::BoundedString;
@@ -34,6 +35,7 @@ use crate::widgets::staging_string::{InputLines, StagingString};
 use crate::widgets::staging_vec::StagingVec;
 use crate::widgets::util::{widget_vec_from_values, TaskChannel, VecItemRender, VecWidget};
 use crate::widgets::version_widget::VersionWidget;
+use crate::widgets::training_data_widget::TrainingDataWidget;
 use crate::widgets::weights_widget::WeightsWidget;
 use crate::widgets::zoo_widget::{upload_model, ZooLoginWidget};
 use crate::widgets::ValueWidget;
@@ -50,6 +52,7 @@ enum PackingStatus {
     Done,
     Packing {
         path: PathBuf,
+        progress: PackingProgress,
         task: poll_promise::Promise<Result<(), ModelPackingError>>,
     },
 }
@@ -94,6 +97,7 @@ pub struct AppState1 {
     pub model_interface_widget: ModelInterfaceWidget,
     ////
     pub weights_widget: WeightsWidget,
+    pub training_data_widget: StagingOpt<TrainingDataWidget>,
 
 
 
@@ -171,6 +175,7 @@ impl ValueWidget for AppState1{
         self.model_interface_widget.set_value(zoo_model.interface);
 
         self.weights_widget.set_value(zoo_model.weights);
+        self.training_data_widget.set_value(zoo_model.training_data);
 
         self.model_packing_status = PackingStatus::default();
     }
@@ -200,6 +205,7 @@ impl Default for AppState1 {
 
             model_packing_status: PackingStatus::default(),
             weights_widget: Default::default(),
+            training_data_widget: Default::default(),
             notifications_widget: NotificationsWidget::new(),
             notifications_channel: Default::default(),
             zoo_login_widget: Default::default(),
@@ -293,6 +299,9 @@ impl AppState1{
         let weights = self.weights_widget.get_value()
             .map_err(|e| GuiError::new_with_rect("Check model weights for errors", e.failed_widget_rect))?
             .as_ref().clone();
+        let training_data = self.training_data_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check training data for errors", e.failed_widget_rect))?;
 
         Ok(ZooModel {
             name,
@@ -311,6 +320,7 @@ impl AppState1{
             documentation,
             license,
             id: model_id,
+            training_data,
             weights,
             interface: model_interface,
         })
@@ -655,6 +665,13 @@ impl eframe::App for AppState1 {
                     ui.strong("License: ").on_hover_text("A standard software licence, specifying how this model can be used and for what purposes.");
                     self.staging_license.draw_and_parse(ui, egui::Id::from("License"));
                 });
+
+                ui.horizontal_top(|ui| {
+                    ui.strong("Training Data: ").on_hover_text(
+                        "The dataset used to train this model, either linked by its model zoo id or described here"
+                    );
+                    self.training_data_widget.draw_and_parse(ui, egui::Id::from("Training Data"));
+                });
                 ui.add_space(20.0);
 
 
@@ -720,25 +737,43 @@ impl eframe::App for AppState1 {
                             path.set_extension("zip");
                             let notification_message = format!("Packing into {}...", path.to_string_lossy());
                             self.notifications_widget.push_message(Ok(notification_message));
+                            let progress = PackingProgress::default();
                             PackingStatus::Packing {
                                 path: path.clone(),
+                                progress: progress.clone(),
                                 task: poll_promise::Promise::spawn_thread("dumping_to_zip", move || {
-                                    let file = std::fs::File::create(path)?;
-                                    zoo_model.pack_into(file)
+                                    zoo_model.pack_into_path(&path, CompressionPolicy::default(), progress)
                                 }),
                             }
                         }
-                        PackingStatus::Packing { path, task } => match task.try_take() {
+                        PackingStatus::Packing { path, progress, task } => match task.try_take() {
                             Ok(value) => {
                                 self.notifications_widget.push_message(match &value{
                                     Ok(_) => Ok(format!("Model saved to {}", path.to_string_lossy())),
+                                    Err(ModelPackingError::Cancelled) => Ok("Saving model cancelled".to_owned()),
                                     Err(err) => Err(format!("Error saving model: {err}")),
                                 });
                                 PackingStatus::Done
                             },
                             Err(task) => {
+                                let megabytes_written = progress.total_bytes_written() as f64 / 1_000_000.0;
+                                let mut text = match progress.current_file(){
+                                    Some((file_name, _)) => format!("{megabytes_written:.1} MB written ({file_name})"),
+                                    None => format!("{megabytes_written:.1} MB written"),
+                                };
+                                let progress_bar = match progress.fraction(){
+                                    Some(fraction) => {
+                                        text = format!("{:.0}% - {text}", fraction * 100.0);
+                                        egui::ProgressBar::new(fraction)
+                                    },
+                                    None => egui::ProgressBar::new(0.0).animate(true),
+                                };
+                                ui.add(progress_bar.text(text).desired_width(300.0));
+                                if !progress.is_cancelled() && ui.button("Cancel").clicked(){
+                                    progress.cancel();
+                                }
                                 ui.ctx().request_repaint();
-                                PackingStatus::Packing { path, task }
+                                PackingStatus::Packing { path, progress, task }
                             }
                         },
                     }
@@ -194,6 +194,8 @@ pub struct InputAxisWidgetRawData {
 pub struct WeightsDescrBaseWidgetRawData{
     pub source_widget: FileSourceWidgetRawData,
     pub authors_widget: Option<Vec<CollapsibleWidgetRawData<AuthorWidget>>>,
+    #[serde(default)] // added after AppState1RawData
+    pub comment_widget: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -255,6 +257,40 @@ pub struct WeightsWidgetRawData{
     pub onnx_weights_widget: Option<CollapsibleWidgetRawData<OnnxWeightsWidget>>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum TrainingDataModeRawData{
+    Linked,
+    Inline,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct InlineDatasetWidgetRawData{
+    pub name_widget: String,
+    pub description_widget: String,
+    pub authors_widget: Option<Vec<CollapsibleWidgetRawData<AuthorWidget>>>,
+    pub license_widget: Option<::bioimg_spec::rdf::LicenseId>,
+    pub source_widget: Option<String>,
+    pub tags_widget: Vec<String>,
+    pub documentation_widget: Option<CodeEditorWidgetRawData>,
+    pub id_widget: Option<String>,
+    pub covers_widget: Vec<SpecialImageWidgetRawData>,
+    pub attachments_widget: Vec<FileSourceWidgetRawData>,
+    pub cite_widget: Vec<CiteEntryWidgetRawData>,
+    pub maintainers_widget: Vec<MaintainerWidgetRawData>,
+    pub config_widget: Option<JsonObjectEditorWidgetRawData>,
+    pub git_repo_widget: Option<String>,
+    pub icon_widget: Option<IconWidgetRawData>,
+    pub links_widget: Vec<String>,
+    pub version_widget: Option<VersionWidgetRawData>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrainingDataWidgetRawData{
+    pub mode_widget: TrainingDataModeRawData,
+    pub linked_id_widget: String,
+    pub inline_widget: InlineDatasetWidgetRawData,
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct InputTensorWidgetRawData {
     pub id_widget: String,
@@ -263,6 +299,8 @@ pub struct InputTensorWidgetRawData {
     pub axis_widgets: Vec<InputAxisWidgetRawData>,
     pub test_tensor_widget: TestTensorWidgetRawData,
     pub preprocessing_widget: Vec<PreprocessingWidgetRawData>,
+    #[serde(default)] // added after AppState1RawData
+    pub loaded_data: Option<::bioimg_spec::rdf::model::TensorDataDescrs>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -467,6 +505,8 @@ pub struct OutputTensorWidgetRawData {
     pub axis_widgets: Vec<OutputAxisWidgetRawData>,
     pub test_tensor_widget: TestTensorWidgetRawData,
     pub postprocessing_widgets: Vec<CollapsibleWidgetRawData<PostprocessingWidget>>,
+    #[serde(default)] // added after AppState1RawData
+    pub loaded_data: Option<::bioimg_spec::rdf::model::TensorDataDescrs>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -546,4 +586,6 @@ pub struct AppState1RawData{
     pub model_interface_widget: ModelInterfaceWidgetRawData,
     ////
     pub weights_widget: WeightsWidgetRawData,
+    #[serde(default)] // added after AppState1RawData
+    pub training_data_widget: Option<TrainingDataWidgetRawData>,
 }
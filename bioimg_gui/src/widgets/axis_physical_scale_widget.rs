@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use bioimg_runtime::resampling::{PhysicalUnit, PixelSpacing};
 use bioimg_spec::rdf::model as modelrdf;
 
 use crate::project_data::PhysicalScaleWidgetRawData;
@@ -59,6 +60,28 @@ where
     }
 }
 
+impl PhysicalScaleWidget<modelrdf::SpaceUnit>{
+    /// Fills in the scale and unit from a pixel size found in an image's metadata
+    pub fn prefil_from_spacing(&mut self, spacing: PixelSpacing){
+        self.raw_scale = spacing.size.to_string();
+        self.unit_widget.set_value(match spacing.unit{
+            Some(PhysicalUnit::Space(unit)) => Some(unit),
+            _ => None,
+        });
+    }
+}
+
+impl PhysicalScaleWidget<modelrdf::TimeUnit>{
+    /// Fills in the scale and unit from a frame interval found in an image's metadata
+    pub fn prefil_from_spacing(&mut self, spacing: PixelSpacing){
+        self.raw_scale = spacing.size.to_string();
+        self.unit_widget.set_value(match spacing.unit{
+            Some(PhysicalUnit::Time(unit)) => Some(unit),
+            _ => None,
+        });
+    }
+}
+
 impl<U> PhysicalScaleWidget<U>{
     fn parse_scale(&self) -> Result<modelrdf::AxisScale>{
         if self.raw_scale.is_empty(){
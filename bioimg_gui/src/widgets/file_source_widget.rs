@@ -316,7 +316,7 @@ impl ValueWidget for FileSourceWidget{
                     })
                 };
             },
-            rt::FileSource::HttpUrl(url) => {
+            rt::FileSource::HttpUrl(url) | rt::FileSource::CachedUrl{url, ..} => {
                 self.mode = FileSourceWidgetMode::Url;
                 self.http_url_widget.set_value(url);
             },
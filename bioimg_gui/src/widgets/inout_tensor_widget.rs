@@ -4,8 +4,8 @@ use std::sync::Arc;
 
 use indoc::indoc;
 
-use bioimg_runtime::model_interface::{InputSlot, OutputSlot};
-use bioimg_runtime::npy_array::ArcNpyArray;
+use bioimg_runtime::model_interface::{validate_tensor_data, InputSlot, OutputSlot};
+use bioimg_runtime::npy_array::{ArcNpyArray, NpyArray};
 
 use crate::result::{GuiError, Result};
 use bioimg_spec::rdf::model as modelrdf;
@@ -23,6 +23,18 @@ use super::util::{VecItemRender, VecWidget};
 use super::{Restore, StatefulWidget, ValueWidget};
 use crate::widgets::staging_vec::ItemWidgetConf;
 
+/// The data description the tensor was loaded with, as long as it still fits the test tensor
+fn tensor_data(
+    loaded: Option<&modelrdf::TensorDataDescrs>,
+    tensor_id: &modelrdf::TensorId,
+    channel_dim: Option<usize>,
+    test_tensor: &NpyArray,
+) -> Option<modelrdf::TensorDataDescrs>{
+    loaded
+        .filter(|data| validate_tensor_data(tensor_id, data, channel_dim, test_tensor).is_ok())
+        .cloned()
+}
+
 #[derive(Restore, Default)]
 pub struct InputTensorWidget {
     #[restore_default]
@@ -34,6 +46,7 @@ pub struct InputTensorWidget {
     pub axis_widgets: Vec<InputAxisWidget>,
     pub test_tensor_widget: TestTensorWidget,
     pub preprocessing_widget: Vec<PreprocessingWidget>,
+    loaded_data: Option<modelrdf::TensorDataDescrs>,
 }
 
 
@@ -54,6 +67,7 @@ impl ValueWidget for InputTensorWidget{
                 w
             })
             .collect(); //FIXME: use current alloc?
+        self.loaded_data = value.tensor_meta.declared_data().cloned();
         self.id_widget.set_value(value.tensor_meta.id);
         self.description_widget.set_value(value.tensor_meta.description);
         self.test_tensor_widget.set_value(value.test_tensor);
@@ -87,7 +101,7 @@ impl InputTensorWidget{
     fn autofill_from_test_tensor(&mut self){
         let state_guard = self.test_tensor_widget.state();
         let state: &TestTensorWidgetState = state_guard.deref();
-        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr } = state else {
+        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr, image_axes } = state else {
             self.adjust_num_axes_on_file_selected = true;
             return;
         };
@@ -104,16 +118,19 @@ impl InputTensorWidget{
             }
         }
         let sample_shape = gui_npy_arr.shape();
-        let mut extents = sample_shape.iter().skip(self.axis_widgets.len());
-
-        while let Some(extent) = extents.next() {
+        for (dim, extent) in sample_shape.iter().enumerate().skip(self.axis_widgets.len()) {
             let mut axis_widget = InputAxisWidget::default();
-            axis_widget.axis_type_widget.value = if *extent == 1{
-                modelrdf::AxisType::Channel
-            } else {
-                modelrdf::AxisType::Space
-            };
-            axis_widget.space_axis_widget.prefil_parameterized_size(*extent);
+            match image_axes.as_ref().and_then(|axes| axes.axis(dim)){
+                Some((letter, spacing)) => axis_widget.prefil_from_image_axis(letter, *extent, spacing),
+                None => {
+                    axis_widget.axis_type_widget.value = if *extent == 1{
+                        modelrdf::AxisType::Channel
+                    } else {
+                        modelrdf::AxisType::Space
+                    };
+                    axis_widget.space_axis_widget.prefil_parameterized_size(*extent);
+                },
+            }
             self.axis_widgets.push(axis_widget)
         }
     }
@@ -135,11 +152,16 @@ impl InputTensorWidget{
                 "Example tensor has {} dimensions but there are {} axes defined", sample_shape.len(), axes.len()
             )))
         }
+        let id = self.id_widget.state()
+            .map_err(|err| GuiError::new(format!("Bad input id: {err}")))?
+            .clone();
+        let channel_dim = axes.iter().position(|axis| matches!(axis, modelrdf::InputAxis::Channel(_)));
+        // the first preprocessing step converts to this data type, so it has to match the test tensor
+        let data = tensor_data(self.loaded_data.as_ref(), &id, channel_dim, gui_npy_array)
+            .unwrap_or_else(|| gui_npy_array.dtype().into());
         let input_axis_group = modelrdf::InputAxisGroup::try_from(axes)?;
         let meta_msg = rdfinput::InputTensorMetadataMsg{
-            id: self.id_widget.state()
-                .map_err(|err| GuiError::new(format!("Bad input id: {err}")))?
-                .clone(),
+            id,
             optional: self.is_optional,
             preprocessing: self.preprocessing_widget.iter()
                 .map(|w| w.state())
@@ -147,6 +169,7 @@ impl InputTensorWidget{
                 .map_err(|err| GuiError::new(format!("Preprocessing error: {err}")))?,
             description: self.description_widget.state()?.clone(),
             axes: input_axis_group,
+            data: Some(data),
         };
         return Ok(
             InputSlot{ tensor_meta: meta_msg.try_into()?, test_tensor: Arc::clone(gui_npy_array) }
@@ -266,6 +289,7 @@ pub struct OutputTensorWidget {
     pub axis_widgets: Vec<OutputAxisWidget>,
     pub test_tensor_widget: TestTensorWidget,
     pub postprocessing_widgets: Vec<CollapsibleWidget<PostprocessingWidget>>,
+    loaded_data: Option<modelrdf::TensorDataDescrs>,
 }
 
 impl Default for OutputTensorWidget{
@@ -277,6 +301,7 @@ impl Default for OutputTensorWidget{
             axis_widgets: Default::default(),
             test_tensor_widget: Default::default(),
             postprocessing_widgets: Default::default(),
+            loaded_data: None,
         }
     }
 }
@@ -298,6 +323,7 @@ impl ValueWidget for OutputTensorWidget{
                 w
             })
             .collect();
+        self.loaded_data = value.tensor_meta.declared_data().cloned();
         self.id_widget.set_value(value.tensor_meta.id);
         self.description_widget.set_value(value.tensor_meta.description);
         self.test_tensor_widget.set_value(value.test_tensor);
@@ -330,7 +356,7 @@ impl OutputTensorWidget{
     fn autofill_from_test_tensor(&mut self){
         let state_guard = self.test_tensor_widget.state();
         let state: &TestTensorWidgetState = state_guard.deref();
-        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr } = state else {
+        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr, image_axes } = state else {
             self.adjust_num_axes_on_file_selected = true;
             return;
         };
@@ -347,16 +373,19 @@ impl OutputTensorWidget{
             }
         }
         let sample_shape = gui_npy_arr.shape();
-        let mut extents = sample_shape.iter().skip(self.axis_widgets.len());
-
-        while let Some(extent) = extents.next() {
+        for (dim, extent) in sample_shape.iter().enumerate().skip(self.axis_widgets.len()) {
             let mut axis_widget = OutputAxisWidget::default();
-            axis_widget.axis_type_widget.value = if *extent == 1{
-                modelrdf::AxisType::Channel
-            } else {
-                modelrdf::AxisType::Space
-            };
-            axis_widget.space_axis_widget.prefil_parameterized_size(*extent);
+            match image_axes.as_ref().and_then(|axes| axes.axis(dim)){
+                Some((letter, spacing)) => axis_widget.prefil_from_image_axis(letter, *extent, spacing),
+                None => {
+                    axis_widget.axis_type_widget.value = if *extent == 1{
+                        modelrdf::AxisType::Channel
+                    } else {
+                        modelrdf::AxisType::Space
+                    };
+                    axis_widget.space_axis_widget.prefil_parameterized_size(*extent);
+                },
+            }
             self.axis_widgets.push(axis_widget)
         }
     }
@@ -374,14 +403,19 @@ impl OutputTensorWidget{
                 "Example tensor has {} dimensions but there are {} axes defined", sample_shape.len(), axes.len()
             )))
         }
+        let id = self.id_widget.state()?.clone();
+        let channel_dim = axes.iter().position(|axis| matches!(axis, modelrdf::OutputAxis::Channel(_)));
+        // without a loaded description, the data type is whatever postprocessing produces
+        let data = tensor_data(self.loaded_data.as_ref(), &id, channel_dim, gui_npy_array);
         let axis_group = modelrdf::OutputAxisGroup::try_from(axes)?; //FIXME: parse in draw_and_parse?
         let meta_msg = modelrdf::output_tensor::OutputTensorMetadataMsg{
-            id: self.id_widget.state()?.clone(),
+            id,
             postprocessing: self.postprocessing_widgets.iter()
                 .map(|w| w.inner.state())
                 .collect::<Result<_>>()?,
             description: self.description_widget.state()?.clone(),
             axes: axis_group,
+            data,
         };
         Ok(
             OutputSlot{ tensor_meta: meta_msg.try_into()?, test_tensor: Arc::clone(gui_npy_array) }
@@ -1,5 +1,6 @@
 use indoc::indoc;
 
+use bioimg_runtime::resampling::PixelSpacing;
 use bioimg_spec::rdf::bounded_string::BoundedString;
 use bioimg_spec::rdf::model::axes::AxisType;
 use bioimg_spec::rdf::model as modelrdf;
@@ -139,6 +140,28 @@ pub struct InputAxisWidget {
 }
 
 impl InputAxisWidget{
+    /// Prefills this widget from a dimension of a test tensor loaded from an image, whose axis is `letter` out of "tczyx"
+    pub fn prefil_from_image_axis(&mut self, letter: char, extent: usize, spacing: Option<PixelSpacing>){
+        match letter{
+            'c' => self.axis_type_widget.value = AxisType::Channel,
+            't' => {
+                self.axis_type_widget.value = AxisType::Time;
+                self.time_axis_widget.id_widget.raw = letter.to_string();
+                self.time_axis_widget.size_widget.prefil_parameterized(extent);
+                if let Some(spacing) = spacing{
+                    self.time_axis_widget.physical_scale_widget.prefil_from_spacing(spacing);
+                }
+            },
+            _ => {
+                self.axis_type_widget.value = AxisType::Space;
+                self.space_axis_widget.id_widget.raw = letter.to_string();
+                self.space_axis_widget.prefil_parameterized_size(extent);
+                if let Some(spacing) = spacing{
+                    self.space_axis_widget.physical_scale_widget.prefil_from_spacing(spacing);
+                }
+            },
+        }
+    }
     pub fn new(value: Option<modelrdf::InputAxis>) -> Self{
         let mut out = Self::default();
         if let Some(val) = value{
@@ -57,6 +57,7 @@ pub mod labels;
 pub mod axis_physical_scale_widget;
 pub mod button_ext;
 pub mod iconify;
+pub mod training_data_widget;
 
 pub trait StatefulWidget {
     type Value<'p>
@@ -137,3 +138,4 @@ impl_Restore_for!(rdf::model::axes::AxisType);
 impl_Restore_for!(rdf::model::SpaceUnit);
 impl_Restore_for!(rdf::model::TimeUnit);
 impl_Restore_for!(rdf::model::DataType);
+impl_Restore_for!(rdf::model::TensorDataDescrs);
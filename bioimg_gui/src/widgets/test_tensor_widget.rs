@@ -4,7 +4,7 @@ use std::time::Instant;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use bioimg_runtime::{npy_array::ArcNpyArray, NpyArray};
+use bioimg_runtime::{npy_array::ArcNpyArray, resampling::PixelSpacing, tiff_io::TiffImage, NpyArray};
 use parking_lot as pl;
 
 use crate::{project_data::TestTensorWidgetRawData, result::GuiError};
@@ -13,11 +13,25 @@ use super::util::GenCell;
 use super::{error_display::show_error, Restore, StatefulWidget, ValueWidget};
 
 
+/// Axis letters (out of "tczyx") and pixel sizes of a test tensor that was loaded from a TIFF image
+#[derive(Clone)]
+pub struct ImageAxes{
+    pub letters: Vec<char>,
+    pub pixel_spacing: Vec<Option<PixelSpacing>>,
+}
+
+impl ImageAxes{
+    /// The letter and pixel size of dimension `dim` of the image, if it has that many dimensions
+    pub fn axis(&self, dim: usize) -> Option<(char, Option<PixelSpacing>)>{
+        Some((*self.letters.get(dim)?, self.pixel_spacing.get(dim).copied().flatten()))
+    }
+}
+
 #[derive(Default)]
 pub enum TestTensorWidgetState{
     #[default]
     Empty,
-    Loaded{path: Option<PathBuf>, data: ArcNpyArray},
+    Loaded{path: Option<PathBuf>, data: ArcNpyArray, image_axes: Option<ImageAxes>},
     Error{message: String}
 }
 
@@ -39,7 +53,7 @@ impl ValueWidget for TestTensorWidget{
 
     fn set_value<'v>(&mut self, data: Self::Value<'v>) {
         self.state = Arc::new(pl::Mutex::new(GenCell::new(
-            TestTensorWidgetState::Loaded { path: None, data}
+            TestTensorWidgetState::Loaded { path: None, data, image_axes: None}
         )));
     }
 
@@ -53,7 +67,7 @@ impl Restore for TestTensorWidget{
         let state: &TestTensorWidgetState = state_guard.deref();
         match state{
             TestTensorWidgetState::Empty  | &TestTensorWidgetState::Error { .. }=> TestTensorWidgetRawData::Empty,
-            TestTensorWidgetState::Loaded { path, data } => TestTensorWidgetRawData::Loaded {
+            TestTensorWidgetState::Loaded { path, data, .. } => TestTensorWidgetRawData::Loaded {
                 path: path.clone(),
                 data: {
                     let mut v = vec![];
@@ -69,7 +83,7 @@ impl Restore for TestTensorWidget{
             TestTensorWidgetRawData::Empty => TestTensorWidgetState::Empty,
             TestTensorWidgetRawData::Loaded { path, data } => {
                 let state = match NpyArray::try_load(Cursor::new(data)){
-                    Ok(data) => TestTensorWidgetState::Loaded { path, data: Arc::new(data) },
+                    Ok(data) => TestTensorWidgetState::Loaded { path, data: Arc::new(data), image_axes: None },
                     Err(_e) => TestTensorWidgetState::Error { message: "Could not deserialize npy data".to_owned() }
                 };
                 state
@@ -79,11 +93,18 @@ impl Restore for TestTensorWidget{
 }
 
 impl TestTensorWidget{
-    pub fn try_load(mut path: impl Read) -> Result<ArcNpyArray, GuiError>{
+    /// Loads a .npy array or a TIFF image, in which case the image's axes are returned too
+    pub fn try_load(mut path: impl Read) -> Result<(ArcNpyArray, Option<ImageAxes>), GuiError>{
         let mut data = vec![];
         path.read_to_end(&mut data)?;
+        // tiff files start with either "II" or "MM", depending on their byte order
+        if data.starts_with(b"II") || data.starts_with(b"MM"){
+            let image = TiffImage::try_load(Cursor::new(data))?;
+            let image_axes = ImageAxes{letters: image.axes().chars().collect(), pixel_spacing: image.pixel_spacing().to_vec()};
+            return Ok((Arc::new(image.data().clone()), Some(image_axes)))
+        }
         let data = NpyArray::try_load(&mut data.as_slice())?;
-        Ok(Arc::new(data))
+        Ok((Arc::new(data), None))
     }
     pub fn state(&self) -> pl::MutexGuard<'_, GenCell<TestTensorWidgetState>>{
         self.state.lock()
@@ -101,7 +122,7 @@ impl StatefulWidget for TestTensorWidget{
                 let current_state = Arc::clone(&self.state);
                 #[cfg(not(target_arch="wasm32"))]
                 std::thread::spawn(move ||{
-                    let Some(path) = rfd::FileDialog::new().add_filter("numpy array", &["npy"],).pick_file() else {
+                    let Some(path) = rfd::FileDialog::new().add_filter("numpy array or tiff image", &["npy", "tif", "tiff"],).pick_file() else {
                         current_state.lock().maybe_set(timestamp, TestTensorWidgetState::Empty);
                         return
                     };
@@ -114,21 +135,21 @@ impl StatefulWidget for TestTensorWidget{
                     };
                     let reader = std::io::BufReader::new(file);
                     let new_state = match Self::try_load(reader){
-                        Ok(data) => TestTensorWidgetState::Loaded { path: Some(path.to_owned()), data },
+                        Ok((data, image_axes)) => TestTensorWidgetState::Loaded { path: Some(path.to_owned()), data, image_axes },
                         Err(e) => TestTensorWidgetState::Error { message: e.to_string() }
                     };
                     current_state.lock().maybe_set(timestamp, new_state);
                 });
                 #[cfg(target_arch="wasm32")]
                 wasm_bindgen_futures::spawn_local(async move {
-                    let Some(file) = rfd::AsyncFileDialog::new().add_filter("numpy array", &["npy"],).pick_file().await else {
+                    let Some(file) = rfd::AsyncFileDialog::new().add_filter("numpy array or tiff image", &["npy", "tif", "tiff"],).pick_file().await else {
                         sender.send(TestTensorWidgetState::Empty).unwrap();
                         return
                     };
                     let contents = file.read().await;
                     let reader: Box<dyn SeekReadSend + 'static> = Box::new(std::io::Cursor::new(contents));
                     let task = match Self::try_load(reader){
-                        Ok(data) => TestTensorWidgetState::Loaded{path: None, data},
+                        Ok((data, image_axes)) => TestTensorWidgetState::Loaded{path: None, data, image_axes},
                         Err(e) => TestTensorWidgetState::Error{message: e.to_string()},
                     };
                     sender.send(task).unwrap();
@@ -137,7 +158,7 @@ impl StatefulWidget for TestTensorWidget{
             
             match (&*self.state()).deref(){
                 TestTensorWidgetState::Empty => (),
-                TestTensorWidgetState::Loaded { path, data } => {
+                TestTensorWidgetState::Loaded { path, data, image_axes } => {
                     let shape = data.shape();
                     let last_item_idx = shape.len() - 1;
                     let shape_str = shape
@@ -152,6 +173,9 @@ impl StatefulWidget for TestTensorWidget{
                             acc
                         });
                     ui.weak(format!("C-order shape: [{shape_str}] "));
+                    if let Some(image_axes) = image_axes{
+                        ui.weak(format!("axes: {} ", image_axes.letters.iter().collect::<String>()));
+                    }
                     if let Some(p) = path{
                         ui.weak("from");
                         ui.weak(p.to_string_lossy());
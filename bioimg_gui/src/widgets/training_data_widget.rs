@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use bioimg_runtime as rt;
+use bioimg_runtime::zoo_dataset::{TrainingData, ZooDataset};
+use bioimg_spec::rdf;
+use bioimg_spec::rdf::model::dataset_descr::DatasetName;
+
+use crate::project_data::TrainingDataModeRawData;
+use crate::result::{GuiError, Result, VecResultExt};
+use super::{Restore, StatefulWidget, ValueWidget};
+use super::attachments_widget::AttachmentsWidget;
+use super::author_widget::AuthorWidget;
+use super::cite_widget::CiteEntryWidget;
+use super::code_editor_widget::{CodeEditorWidget, MarkdwownLang};
+use super::collapsible_widget::{CollapsibleWidget, SummarizableWidget};
+use super::cover_image_widget::CoverImageItemConf;
+use super::icon_widget::{IconWidget, IconWidgetValue};
+use super::image_widget_2::SpecialImageWidget;
+use super::json_editor_widget::JsonObjectEditorWidget;
+use super::maintainer_widget::MaintainerWidget;
+use super::model_links_widget::ModelLinksWidget;
+use super::search_and_pick_widget::SearchAndPickWidget;
+use super::staging_opt::StagingOpt;
+use super::staging_string::{InputLines, StagingString};
+use super::staging_vec::StagingVec;
+use super::url_widget::StagingUrl;
+use super::util::{group_frame, widget_vec_from_values, VecItemRender, VecWidget};
+use super::version_widget::VersionWidget;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, strum::VariantArray, strum::AsRefStr, strum::Display)]
+pub enum TrainingDataMode{
+    #[default]
+    #[strum(to_string="Zoo Dataset")]
+    Linked,
+    #[strum(to_string="Inline Description")]
+    Inline,
+}
+
+impl Restore for TrainingDataMode{
+    type RawData = TrainingDataModeRawData;
+    fn dump(&self) -> Self::RawData {
+        match self{
+            Self::Linked => Self::RawData::Linked,
+            Self::Inline => Self::RawData::Inline,
+        }
+    }
+    fn restore(&mut self, raw: Self::RawData) {
+        *self = match raw{
+            Self::RawData::Linked => Self::Linked,
+            Self::RawData::Inline => Self::Inline,
+        }
+    }
+}
+
+#[derive(Restore)]
+pub struct InlineDatasetWidget{
+    pub name_widget: StagingString<DatasetName>,
+    pub description_widget: StagingString<rdf::ResourceTextDescription>,
+    pub authors_widget: StagingOpt<StagingVec<CollapsibleWidget<AuthorWidget>>>,
+    pub license_widget: StagingOpt<SearchAndPickWidget<rdf::LicenseId>, false>,
+    pub source_widget: StagingOpt<StagingUrl, false>,
+    pub tags_widget: StagingVec<StagingString<rdf::Tag>>,
+    pub documentation_widget: StagingOpt<CodeEditorWidget<MarkdwownLang>>,
+    pub id_widget: StagingOpt<StagingString<rdf::ResourceId>, false>,
+    pub covers_widget: StagingVec<SpecialImageWidget<rt::CoverImage>, CoverImageItemConf>,
+    pub attachments_widget: Vec<AttachmentsWidget>,
+    pub cite_widget: Vec<CiteEntryWidget>,
+    pub maintainers_widget: Vec<MaintainerWidget>,
+    pub config_widget: StagingOpt<JsonObjectEditorWidget, false>,
+    pub git_repo_widget: StagingOpt<StagingUrl, false>,
+    pub icon_widget: StagingOpt<IconWidget>,
+    pub links_widget: ModelLinksWidget,
+    pub version_widget: StagingOpt<VersionWidget, false>,
+}
+
+impl Default for InlineDatasetWidget{
+    fn default() -> Self {
+        Self{
+            name_widget: StagingString::new(InputLines::SingleLine),
+            description_widget: StagingString::new(InputLines::Multiline),
+            authors_widget: Default::default(),
+            license_widget: Default::default(),
+            source_widget: Default::default(),
+            tags_widget: Default::default(),
+            documentation_widget: Default::default(),
+            id_widget: Default::default(),
+            covers_widget: Default::default(),
+            attachments_widget: Default::default(),
+            cite_widget: Default::default(),
+            maintainers_widget: Default::default(),
+            config_widget: Default::default(),
+            git_repo_widget: Default::default(),
+            icon_widget: Default::default(),
+            links_widget: Default::default(),
+            version_widget: Default::default(),
+        }
+    }
+}
+
+impl ValueWidget for InlineDatasetWidget{
+    type Value<'v> = ZooDataset;
+
+    fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        self.name_widget.set_value(value.name.clone());
+        self.description_widget.set_value(value.description.clone());
+        self.authors_widget.set_value(Some(value.authors.clone()).filter(|authors| !authors.is_empty()));
+        self.license_widget.set_value(value.license);
+        self.source_widget.set_value(value.source.clone().map(Arc::new));
+        self.tags_widget.set_value(value.tags.clone());
+        self.documentation_widget.set_value(value.documentation.as_deref());
+        self.id_widget.set_value(value.id);
+        self.covers_widget.set_value(value.covers.into_iter().map(|cover| (None, Some(cover))).collect());
+        self.attachments_widget = widget_vec_from_values(value.attachments);
+        self.cite_widget = widget_vec_from_values(value.cite);
+        self.maintainers_widget = widget_vec_from_values(value.maintainers);
+        self.config_widget.set_value(Some(value.config).filter(|config| !config.is_empty()));
+        self.git_repo_widget.set_value(value.git_repo.map(Arc::new));
+        self.icon_widget.set_value(value.icon.map(IconWidgetValue::from));
+        self.links_widget.set_value(value.links);
+        self.version_widget.set_value(value.version);
+    }
+}
+
+impl StatefulWidget for InlineDatasetWidget{
+    type Value<'p> = Result<ZooDataset>;
+
+    fn draw_and_parse(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.vertical(|ui|{
+            ui.horizontal(|ui|{
+                ui.strong("Name: ").on_hover_text("A human-friendly name of the dataset");
+                self.name_widget.draw_and_parse(ui, id.with("name".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Description: ").on_hover_text("A brief description of the dataset");
+                self.description_widget.draw_and_parse(ui, id.with("description".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Authors: ").on_hover_text("The creators of the dataset description");
+                self.authors_widget.draw_and_parse(ui, id.with("authors".as_ptr()));
+            });
+            ui.horizontal(|ui|{
+                ui.strong("License: ").on_hover_text("The license under which the dataset is distributed");
+                self.license_widget.draw_and_parse(ui, id.with("license".as_ptr()));
+            });
+            ui.horizontal(|ui|{
+                ui.strong("Source: ").on_hover_text("A URL from where the dataset can be downloaded");
+                self.source_widget.draw_and_parse(ui, id.with("source".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Tags: ").on_hover_text("Tags to help search and classify the dataset");
+                self.tags_widget.draw_and_parse(ui, id.with("tags".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Documentation: ").on_hover_text("Markdown documentation of the dataset");
+                self.documentation_widget.draw_and_parse(ui, id.with("documentation".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Dataset Id: ").on_hover_text("The bioimage.io id of this dataset, if it has one");
+                self.id_widget.draw_and_parse(ui, id.with("id".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Cover Images: ").on_hover_text("Images showing what the dataset looks like");
+                self.covers_widget.draw_and_parse(ui, id.with("covers".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                let attachments_base_id = id.with("attachments".as_ptr());
+                ui.strong("Attachments: ").on_hover_text("Any other files that are relevant to the dataset");
+                ui.add(VecWidget{
+                    items: &mut self.attachments_widget,
+                    min_items: 0,
+                    item_label: "Attachment",
+                    show_reorder_buttons: true,
+                    new_item: Some(AttachmentsWidget::default),
+                    item_renderer: VecItemRender::HeaderAndBody{
+                        render_header: |widg: &mut AttachmentsWidget, idx, ui|{
+                            widg.summarize(ui, attachments_base_id.with(("header".as_ptr(), idx)));
+                        },
+                        render_body: |widg: &mut AttachmentsWidget, idx, ui|{
+                            widg.draw_and_parse(ui, attachments_base_id.with(("body".as_ptr(), idx)));
+                        },
+                        collapsible_id_source: Some(attachments_base_id),
+                        marker: Default::default(),
+                    }
+                });
+            });
+            ui.horizontal_top(|ui|{
+                let cite_base_id = id.with("cite".as_ptr());
+                ui.strong("Cite: ").on_hover_text("How this dataset should be cited in other publications");
+                ui.add(VecWidget{
+                    items: &mut self.cite_widget,
+                    min_items: 0,
+                    item_label: "Citation Entry",
+                    show_reorder_buttons: true,
+                    new_item: Some(CiteEntryWidget::default),
+                    item_renderer: VecItemRender::HeaderAndBody{
+                        render_header: |widg: &mut CiteEntryWidget, idx, ui|{
+                            widg.summarize(ui, cite_base_id.with(("header".as_ptr(), idx)));
+                        },
+                        render_body: |widg: &mut CiteEntryWidget, idx, ui|{
+                            widg.draw_and_parse(ui, cite_base_id.with(("body".as_ptr(), idx)));
+                        },
+                        collapsible_id_source: Some(cite_base_id),
+                        marker: Default::default(),
+                    }
+                });
+            });
+            ui.horizontal_top(|ui|{
+                let maintainers_base_id = id.with("maintainers".as_ptr());
+                ui.strong("Maintainers: ").on_hover_text("Maintainers of the dataset description");
+                ui.add(VecWidget{
+                    items: &mut self.maintainers_widget,
+                    min_items: 0,
+                    item_label: "Maintainer",
+                    show_reorder_buttons: true,
+                    new_item: Some(MaintainerWidget::default),
+                    item_renderer: VecItemRender::HeaderAndBody{
+                        render_header: |widg: &mut MaintainerWidget, idx, ui|{
+                            widg.summarize(ui, maintainers_base_id.with(("header".as_ptr(), idx)));
+                        },
+                        render_body: |widg: &mut MaintainerWidget, idx, ui|{
+                            widg.draw_and_parse(ui, maintainers_base_id.with(("body".as_ptr(), idx)));
+                        },
+                        collapsible_id_source: Some(maintainers_base_id),
+                        marker: Default::default(),
+                    }
+                });
+            });
+            ui.horizontal_top(|ui|{
+                ui.weak("Custom configs: ").on_hover_text("A JSON object with any extra information about the dataset");
+                self.config_widget.draw_and_parse(ui, id.with("config".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Git Repo: ").on_hover_text("A URL to a git repository related to the dataset");
+                self.git_repo_widget.draw_and_parse(ui, id.with("git_repo".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Icon: ").on_hover_text("An emoji or a small square image to identify the dataset");
+                self.icon_widget.draw_and_parse(ui, id.with("icon".as_ptr()));
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Links: ").on_hover_text("IDs of other bioimage.io resources");
+                group_frame(ui, |ui|{
+                    self.links_widget.draw_and_parse(ui, id.with("links".as_ptr()));
+                });
+            });
+            ui.horizontal_top(|ui|{
+                ui.strong("Version: ").on_hover_text("The version of this dataset description");
+                self.version_widget.draw_and_parse(ui, id.with("version".as_ptr()));
+            });
+        });
+    }
+
+    fn state<'p>(&'p self) -> Self::Value<'p> {
+        let name = self.name_widget.state()
+            .cloned()
+            .map_err(|e| GuiError::new_with_rect("Check dataset name for errors", e.failed_widget_rect))?;
+        let description = self.description_widget.state()
+            .cloned()
+            .map_err(|e| GuiError::new_with_rect("Check dataset description for errors", e.failed_widget_rect))?;
+        let authors = self.authors_widget.state()
+            .map(|authors| authors.collect_result())
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset authors for errors", e.failed_widget_rect))?
+            .unwrap_or_default();
+        let source = self.source_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset source for errors", e.failed_widget_rect))?
+            .map(|url| url.as_ref().clone());
+        let tags = self.tags_widget.state()
+            .into_iter()
+            .map(|res_ref| res_ref.cloned())
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| GuiError::new_with_rect("Check dataset tags for errors", e.failed_widget_rect))?;
+
+        let id = self.id_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset id for errors", e.failed_widget_rect))?
+            .cloned();
+        let covers = self.covers_widget.state()
+            .collect_result()
+            .map_err(|e| GuiError::new_with_rect("Check dataset cover images for errors", e.failed_widget_rect))?;
+        let attachments = self.attachments_widget.iter().enumerate()
+            .map(|(idx, widget)| {
+                widget.state().map_err(|_| GuiError::new(format!("Check dataset attachment #{} for errors", idx + 1)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let cite = self.cite_widget.iter().enumerate()
+            .map(|(idx, widget)| {
+                widget.state().map_err(|_| GuiError::new(format!("Check dataset citation #{} for errors", idx + 1)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let maintainers = self.maintainers_widget.iter().enumerate()
+            .map(|(idx, widget)| {
+                widget.state().map_err(|_| GuiError::new(format!("Check dataset maintainer #{} for errors", idx + 1)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let config = self.config_widget.state().cloned()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset custom configs for errors", e.failed_widget_rect))?
+            .unwrap_or_default();
+        let git_repo = self.git_repo_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset git repo for errors", e.failed_widget_rect))?
+            .map(|url| url.as_ref().clone());
+        let icon = self.icon_widget.state()
+            .transpose()
+            .map_err(|_| GuiError::new("Check dataset icon for errors"))?;
+        let links = self.links_widget.state()
+            .collect_result()
+            .map_err(|e| GuiError::new_with_rect("Check dataset links for errors", e.failed_widget_rect))?
+            .into_iter()
+            .cloned()
+            .collect();
+        let version = self.version_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check dataset version for errors", e.failed_widget_rect))?
+            .cloned();
+
+        Ok(ZooDataset{
+            id,
+            covers,
+            attachments,
+            cite,
+            license: self.license_widget.state(),
+            config,
+            git_repo,
+            icon,
+            links,
+            maintainers,
+            tags,
+            version,
+            documentation: self.documentation_widget.state().map(|doc| doc.to_owned()),
+            source,
+            authors,
+            ..ZooDataset::new(name, description)
+        })
+    }
+}
+
+#[derive(Default, Restore)]
+pub struct TrainingDataWidget{
+    pub mode_widget: SearchAndPickWidget<TrainingDataMode, false>,
+    pub linked_id_widget: StagingString<rdf::ResourceId>,
+    pub inline_widget: InlineDatasetWidget,
+}
+
+impl ValueWidget for TrainingDataWidget{
+    type Value<'v> = TrainingData;
+
+    fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        match value{
+            TrainingData::Linked(id) => {
+                self.mode_widget.value = TrainingDataMode::Linked;
+                self.linked_id_widget.set_value(id);
+            },
+            TrainingData::Inline(dataset) => {
+                self.mode_widget.value = TrainingDataMode::Inline;
+                self.inline_widget.set_value(*dataset);
+            },
+        }
+    }
+}
+
+impl StatefulWidget for TrainingDataWidget{
+    type Value<'p> = Result<TrainingData>;
+
+    fn draw_and_parse(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.vertical(|ui|{
+            ui.horizontal(|ui|{
+                ui.strong("Kind: ").on_hover_text(
+                    "Either the id of a dataset already in the model zoo or a description of the dataset itself"
+                );
+                self.mode_widget.draw_and_parse(ui, id.with("mode".as_ptr()));
+            });
+            match self.mode_widget.value{
+                TrainingDataMode::Linked => {
+                    ui.horizontal(|ui|{
+                        ui.strong("Dataset Id: ").on_hover_text("The id of a dataset in the bioimage.io collection");
+                        self.linked_id_widget.draw_and_parse(ui, id.with("linked".as_ptr()));
+                    });
+                },
+                TrainingDataMode::Inline => {
+                    group_frame(ui, |ui|{
+                        self.inline_widget.draw_and_parse(ui, id.with("inline".as_ptr()));
+                    });
+                },
+            }
+        });
+    }
+
+    fn state<'p>(&'p self) -> Self::Value<'p> {
+        Ok(match self.mode_widget.value{
+            TrainingDataMode::Linked => TrainingData::Linked(
+                self.linked_id_widget.state()
+                    .cloned()
+                    .map_err(|e| GuiError::new_with_rect("Check training dataset id for errors", e.failed_widget_rect))?
+            ),
+            TrainingDataMode::Inline => TrainingData::Inline(Box::new(self.inline_widget.state()?)),
+        })
+    }
+}
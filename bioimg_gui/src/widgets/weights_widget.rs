@@ -9,6 +9,7 @@ use super::version_widget::VersionWidget;
 use super::util::group_frame;
 use super::staging_vec::StagingVec;
 use super::staging_opt::StagingOpt;
+use super::staging_string::StagingString;
 use super::pytorch_statedict_weights_widget::PytorchStateDictWidget;
 use super::onnx_weights_widget::OnnxWeightsWidget;
 use super::file_source_widget::FileSourceWidget;
@@ -75,6 +76,7 @@ impl  WeightsWidget{
 pub struct WeightsDescrBaseWidget{
     pub source_widget: FileSourceWidget,
     pub authors_widget: StagingOpt<StagingVec<CollapsibleWidget<AuthorWidget>>>,
+    pub comment_widget: StagingOpt<StagingString<String>>,
     // pub parent_widget: Option<WeightsFormat>,
 }
 
@@ -97,6 +99,7 @@ impl ValueWidget for WeightsDescrBaseWidget{
     fn set_value<'v>(&mut self, value: Self::Value<'v>) {
         self.source_widget.set_value(value.source);
         self.authors_widget.set_value(value.authors);
+        self.comment_widget.set_value(value.comment);
     }
 }
 
@@ -115,6 +118,10 @@ impl StatefulWidget for WeightsDescrBaseWidget{
                 ui.strong("Authors: ").on_hover_text("The people who trained these weights and biases");
                 self.authors_widget.draw_and_parse(ui, id.with("authors"));
             });
+            ui.horizontal(|ui|{
+                ui.strong("Comment: ").on_hover_text("A comment about these weights, e.g. how they were created");
+                self.comment_widget.draw_and_parse(ui, id.with("comment"));
+            });
         });
     }
 
@@ -123,7 +130,10 @@ impl StatefulWidget for WeightsDescrBaseWidget{
             authors.collect_result()
         }).transpose()?;
         let source = self.source_widget.state().map_err(|e| GuiError::new(format!("Model source error: {e}")))?;
-        Ok(rt::WeightsBase{authors, source})
+        let comment = self.comment_widget.state().transpose()
+            .map_err(|_| GuiError::new("Invalid weights comment"))?
+            .cloned();
+        Ok(rt::WeightsBase{authors, source, comment})
     }
 }
 
@@ -1,7 +1,7 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use bioimg_spec::rdf::model::{
-    axis_size::{QualifiedAxisId, ResolvedAxisSize}, AnyAxisSize, AxisSizeReference, ParameterizedAxisSize
+    axis_size::{QualifiedAxisId, ResolvedAxisSize}, AnyAxisSize, AxisSizeReference, ParameterizedAxisSize, TensorId
 };
 
 pub trait ResolvedAxisSizeExt{
@@ -105,3 +105,143 @@ impl SlotResolver {
         }
     }
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum ShapeInferenceError {
+    #[error("No shape given for input '{0}'")]
+    MissingInputShape(TensorId),
+    #[error("Tensor '{tensor_id}' has {expected} axes but shape has {found} dimensions")]
+    MismatchedNumDimensions{tensor_id: TensorId, expected: usize, found: usize},
+    #[error("Extent {extent} is invalid for axis '{qualified_axis_id}' ({reason})")]
+    IncompatibleExtent{qualified_axis_id: QualifiedAxisId, extent: usize, reason: String},
+    #[error("Batch axis '{qualified_axis_id}' has extent {extent}, but other inputs have batch size {batch_size}")]
+    InconsistentBatchSize{qualified_axis_id: QualifiedAxisId, extent: usize, batch_size: usize},
+    #[error("Size of axis '{0}' can't be determined from the input shapes")]
+    Unresolvable(QualifiedAxisId),
+    #[error("Loop detected when trying to resolve reference to {0}")]
+    Loop(QualifiedAxisId),
+    #[error("Axis '{qualified_axis_id}' would have a non-integer extent of {size}")]
+    NonIntegralSize{qualified_axis_id: QualifiedAxisId, size: f64},
+}
+
+/// What an axis needs in order to have its concrete extent computed
+#[derive(Clone, Debug)]
+pub struct AxisExtentDescr {
+    /// `None` for batch axes
+    pub size: Option<AnyAxisSize>,
+    pub scale: f64,
+}
+
+/// Computes concrete axis extents from concrete input shapes.
+///
+/// Unlike [SlotResolver], this follows references all the way to actual extents, applying
+/// `extent = reference_extent * reference_scale / scale + offset`.
+pub struct ExtentResolver {
+    axes: HashMap<QualifiedAxisId, AxisExtentDescr>,
+    extents: HashMap<QualifiedAxisId, usize>,
+    batch_size: Option<usize>,
+}
+
+impl ExtentResolver {
+    pub fn new(axes: HashMap<QualifiedAxisId, AxisExtentDescr>) -> Self {
+        Self { axes, extents: HashMap::new(), batch_size: None }
+    }
+
+    pub fn batch_size(&self) -> Option<usize> {
+        self.batch_size
+    }
+
+    /// Records the actual `extent` of an input axis. Consistency with its size description
+    /// is only checked in [Self::validate], once all input extents are known
+    pub fn set_extent(&mut self, qualified_axis_id: QualifiedAxisId, extent: usize) -> Result<(), ShapeInferenceError> {
+        let is_batch = self.axes.get(&qualified_axis_id).is_some_and(|descr| descr.size.is_none());
+        if is_batch {
+            match self.batch_size {
+                Some(batch_size) if batch_size != extent => {
+                    return Err(ShapeInferenceError::InconsistentBatchSize { qualified_axis_id, extent, batch_size })
+                },
+                _ => self.batch_size = Some(extent),
+            }
+        }
+        self.extents.insert(qualified_axis_id, extent);
+        Ok(())
+    }
+
+    /// Checks the extent given for `qualified_axis_id` against its size description
+    pub fn validate(&mut self, qualified_axis_id: &QualifiedAxisId) -> Result<(), ShapeInferenceError> {
+        let Some(&extent) = self.extents.get(qualified_axis_id) else {
+            return Err(ShapeInferenceError::Unresolvable(qualified_axis_id.clone()))
+        };
+        let Some(descr) = self.axes.get(qualified_axis_id) else {
+            return Ok(())
+        };
+        let incompatible = |reason: String| ShapeInferenceError::IncompatibleExtent {
+            qualified_axis_id: qualified_axis_id.clone(), extent, reason
+        };
+        match descr.size.clone() {
+            None => Ok(()),
+            Some(AnyAxisSize::Fixed(fixed)) if usize::from(fixed) != extent => {
+                Err(incompatible(format!("expected {fixed}")))
+            },
+            Some(AnyAxisSize::Fixed(_)) => Ok(()),
+            Some(AnyAxisSize::Parameterized(ParameterizedAxisSize { min, step })) => {
+                let (min, step) = (usize::from(min), usize::from(step));
+                if extent < min || (extent - min) % step != 0 {
+                    return Err(incompatible(format!("expected {min} + n * {step}")))
+                }
+                Ok(())
+            },
+            Some(AnyAxisSize::Reference(reference)) => {
+                let expected = self.compute_reference(qualified_axis_id, descr.scale, &reference, HashSet::new())?;
+                if expected != extent {
+                    return Err(incompatible(format!("expected {expected} from reference to {reference}")))
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn compute_reference(
+        &mut self,
+        current: &QualifiedAxisId,
+        scale: f64,
+        reference: &AxisSizeReference,
+        visited: HashSet<QualifiedAxisId>,
+    ) -> Result<usize, ShapeInferenceError> {
+        let referenced = &reference.qualified_axis_id;
+        let reference_extent = self.resolve_visiting(referenced, visited)?;
+        let reference_scale = self.axes.get(referenced).map(|descr| descr.scale).unwrap_or(1.0);
+        let size = reference_extent as f64 * reference_scale / scale + reference.offset as f64;
+        if (size - size.round()).abs() > 1e-6 {
+            return Err(ShapeInferenceError::NonIntegralSize { qualified_axis_id: current.clone(), size })
+        }
+        Ok(size.round() as usize)
+    }
+
+    fn resolve_visiting(
+        &mut self, current: &QualifiedAxisId, mut visited: HashSet<QualifiedAxisId>
+    ) -> Result<usize, ShapeInferenceError> {
+        if let Some(extent) = self.extents.get(current) {
+            return Ok(*extent);
+        }
+        if !visited.insert(current.clone()) {
+            return Err(ShapeInferenceError::Loop(current.clone()));
+        }
+        let Some(descr) = self.axes.get(current).cloned() else {
+            return Err(ShapeInferenceError::Unresolvable(current.clone()));
+        };
+        let extent = match descr.size {
+            None => self.batch_size.unwrap_or(1),
+            Some(AnyAxisSize::Fixed(fixed)) => fixed.into(),
+            Some(AnyAxisSize::Parameterized(_)) => return Err(ShapeInferenceError::Unresolvable(current.clone())),
+            Some(AnyAxisSize::Reference(reference)) => self.compute_reference(current, descr.scale, &reference, visited)?,
+        };
+        self.extents.insert(current.clone(), extent);
+        Ok(extent)
+    }
+
+    /// Concrete extent of `qualified_axis_id`, following references as needed
+    pub fn resolve(&mut self, qualified_axis_id: &QualifiedAxisId) -> Result<usize, ShapeInferenceError> {
+        self.resolve_visiting(qualified_axis_id, HashSet::new())
+    }
+}
@@ -23,8 +23,6 @@ pub enum CondaEnvLoadingError{
     ParsingError(#[from] CondaEnvParsingError),
     #[error(transparent)]
     RdfFileReferenceReadError(#[from] RdfFileReferenceReadError),
-    #[error("Url file reference not supported yet")]
-    UrlFileReferenceNotSupportedYet,
 }
 
 #[derive(Clone)]
@@ -58,7 +56,11 @@ impl CondaEnv{
     ) -> Result<Self, CondaEnvLoadingError>{
         let file_ref: &rdf::FileReference = &descr.source;
         let inner_path: String = match file_ref{
-            rdf::FileReference::Url(_) => return Err(CondaEnvLoadingError::UrlFileReferenceNotSupportedYet),
+            rdf::FileReference::Url(url) => {
+                let path = zip_archive.fetch_url(url, descr.sha256.as_ref())?;
+                let file = std::fs::File::open(path).map_err(RdfFileReferenceReadError::from)?;
+                return Ok(CondaEnv::try_load(file)?)
+            },
             rdf::FileReference::Path(path) => path.into(),
         };
         let conda_env = zip_archive.with_entry(&inner_path, |entry|{
@@ -73,14 +75,13 @@ impl CondaEnv{
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<rdf::EnvironmentFileDescr, ModelPackingError> {
-        let zip_path = rdf::FsPath::unique_suffixed("_environment.yml");
-        zip_file.write_file(&zip_path, |writer| {
+        let (_, zip_path, sha256) = zip_file.write_new_file("environment.yml", |writer| {
             serde_yaml::to_writer(writer, &self.raw)
         })?;
         let file_ref = rdf::FileReference::Path(zip_path);
         Ok(rdf::FileDescription{
             source: file_ref.try_into().unwrap(),
-            sha256: None //FIXME
+            sha256: Some(sha256),
         })
     }
 }
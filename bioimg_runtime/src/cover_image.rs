@@ -1,4 +1,4 @@
-use std::{borrow::Borrow, io::{Cursor, Read, Seek, Write}, ops::Deref, sync::Arc};
+use std::{borrow::Borrow, io::{Cursor, Seek, Write}, ops::Deref, sync::Arc};
 
 use bioimg_spec::rdf;
 use image::codecs::png::PngEncoder;
@@ -24,8 +24,7 @@ impl CoverImage {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result< rdf::CoverImageSource, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed("_cover_image.png");
-        zip_file.write_file(&test_tensor_zip_path, |writer| -> Result<(), ModelPackingError> {
+        let (_, test_tensor_zip_path, _) = zip_file.write_new_file("cover_image.png", |writer| -> Result<(), ModelPackingError> {
             let encoder = PngEncoder::new(writer);
             Ok(self.0.write_with_encoder(encoder)?)
         })?;
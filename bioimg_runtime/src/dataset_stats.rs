@@ -0,0 +1,426 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use bioimg_spec::rdf::model::axes::NonBatchAxisId;
+use bioimg_spec::rdf::model::preprocessing::{
+    FixedZmuv, FixedZmuvAlongAxis, PreprocessingEpsilon, ScaleLinearAlongAxisDescr, ScaleLinearDescr, ScaleRangeDescr,
+    SimpleFixedZmuv, SimpleScaleLinearDescr, Zmuv,
+};
+use bioimg_spec::rdf::model::preprocessing::zero_mean_unit_variance::ZmuvStdDeviation;
+use bioimg_spec::rdf::model::{AxisId, PreprocessingDescr, TensorId};
+use bioimg_spec::rdf::non_empty_list::NonEmptyList;
+
+use crate::npy_array::NpyArray;
+use crate::processing::{reduce_groups, ProcessingError};
+use crate::ModelInterface;
+
+#[derive(thiserror::Error, Debug)]
+pub enum DatasetStatsError{
+    #[error(transparent)]
+    ProcessingError(#[from] ProcessingError),
+    #[error("Tensor '{tensor_id}' has {found} dimensions but {expected} axes are described")]
+    MismatchedNumDimensions{tensor_id: TensorId, expected: usize, found: usize},
+    #[error("Samples of '{tensor_id}' must keep the same extents on non-reduced axes: expected {expected:?}, found {found:?}")]
+    MismatchedGroupShape{tensor_id: TensorId, expected: Vec<usize>, found: Vec<usize>},
+    #[error("No statistics are being tracked for tensor '{0}'")]
+    UntrackedTensor(TensorId),
+    #[error("No samples of tensor '{0}' have been collected")]
+    NoSamples(TensorId),
+    #[error("Statistics of '{tensor_id}' vary along more than one axis ({axes:?}), which can't be described by a fixed preprocessing step")]
+    TooManyKeptAxes{tensor_id: TensorId, axes: Vec<AxisId>},
+    #[error("Standard deviation of '{tensor_id}' is too small to be used for normalization: {std}")]
+    BadStandardDeviation{tensor_id: TensorId, std: f32},
+}
+
+/// Mean and variance of a stream of values, merged chunk by chunk (Chan et al.)
+#[derive(Clone, Debug, Default)]
+pub struct RunningMoments{
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningMoments{
+    pub fn push_values(&mut self, values: &[f32]){
+        if values.is_empty(){
+            return
+        }
+        let chunk_count = values.len() as f64;
+        let chunk_mean = values.iter().map(|v| *v as f64).sum::<f64>() / chunk_count;
+        let chunk_m2 = values.iter().map(|v| (*v as f64 - chunk_mean).powi(2)).sum::<f64>();
+
+        let count = self.count as f64;
+        let total = count + chunk_count;
+        let delta = chunk_mean - self.mean;
+        self.mean += delta * chunk_count / total;
+        self.m2 += chunk_m2 + delta * delta * count * chunk_count / total;
+        self.count += values.len() as u64;
+    }
+    pub fn count(&self) -> u64{
+        self.count
+    }
+    pub fn mean(&self) -> f64{
+        self.mean
+    }
+    /// Population standard deviation
+    pub fn std(&self) -> f64{
+        if self.count == 0{
+            return f64::NAN
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Centroid{
+    mean: f64,
+    weight: f64,
+}
+
+/// Approximate quantiles of a stream of values using a merging t-digest (Dunning, 2019)
+#[derive(Clone, Debug)]
+pub struct TDigest{
+    compression: f64,
+    centroids: Vec<Centroid>,
+    total_weight: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest{
+    fn default() -> Self {
+        Self::new(200.0)
+    }
+}
+
+impl TDigest{
+    /// Higher `compression` keeps more centroids around, yielding more precise quantiles
+    pub fn new(compression: f64) -> Self{
+        Self{compression, centroids: vec![], total_weight: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY}
+    }
+
+    fn k(&self, quantile: f64) -> f64{
+        self.compression / (2.0 * std::f64::consts::PI) * (2.0 * quantile - 1.0).asin()
+    }
+
+    fn k_inverse(&self, k: f64) -> f64{
+        ((k * 2.0 * std::f64::consts::PI / self.compression).sin() + 1.0) / 2.0
+    }
+
+    pub fn push_values(&mut self, values: &[f32]){
+        let mut incoming: Vec<Centroid> = values.iter()
+            .filter(|v| !v.is_nan())
+            .map(|v| Centroid{mean: *v as f64, weight: 1.0})
+            .collect();
+        if incoming.is_empty(){
+            return
+        }
+        for centroid in incoming.iter(){
+            self.min = self.min.min(centroid.mean);
+            self.max = self.max.max(centroid.mean);
+        }
+        incoming.append(&mut self.centroids);
+        incoming.sort_unstable_by(|a, b| a.mean.total_cmp(&b.mean));
+        self.total_weight = incoming.iter().map(|c| c.weight).sum();
+
+        let mut incoming = incoming.into_iter();
+        let mut current = incoming.next().unwrap();
+        let mut weight_so_far = 0.0;
+        let mut quantile_limit = self.k_inverse(self.k(0.0) + 1.0);
+        for next in incoming{
+            let proposed_quantile = (weight_so_far + current.weight + next.weight) / self.total_weight;
+            if proposed_quantile <= quantile_limit{
+                let weight = current.weight + next.weight;
+                current.mean += (next.mean - current.mean) * next.weight / weight;
+                current.weight = weight;
+            } else {
+                weight_so_far += current.weight;
+                quantile_limit = self.k_inverse(self.k(weight_so_far / self.total_weight) + 1.0);
+                self.centroids.push(std::mem::replace(&mut current, next));
+            }
+        }
+        self.centroids.push(current);
+    }
+
+    /// Approximate value at `quantile` (in the range [0, 1]), interpolating like numpy's default `percentile`
+    pub fn quantile(&self, quantile: f64) -> f64{
+        let (Some(first), Some(last)) = (self.centroids.first(), self.centroids.last()) else {
+            return f64::NAN
+        };
+        let target = quantile.clamp(0.0, 1.0) * (self.total_weight - 1.0) + 0.5;
+        if target < first.weight / 2.0{
+            return self.min + (first.mean - self.min) * target / (first.weight / 2.0)
+        }
+        let mut cumulative = first.weight / 2.0;
+        for pair in self.centroids.windows(2){
+            let step = (pair[0].weight + pair[1].weight) / 2.0;
+            if target <= cumulative + step{
+                return pair[0].mean + (pair[1].mean - pair[0].mean) * (target - cumulative) / step
+            }
+            cumulative += step;
+        }
+        let remaining = ((target - cumulative) / (last.weight / 2.0)).min(1.0);
+        last.mean + (self.max - last.mean) * remaining
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GroupStats{
+    pub moments: RunningMoments,
+    pub digest: TDigest,
+}
+
+/// Statistics of one tensor over a whole dataset, reduced jointly over a subset of its axes.
+///
+/// The batch axis is always reduced, since samples are pooled together; statistics are kept
+/// separately for each position on the remaining axes (e.g. per channel).
+#[derive(Clone, Debug)]
+pub struct TensorStats{
+    tensor_id: TensorId,
+    axis_ids: Vec<AxisId>,
+    axes: Option<Vec<AxisId>>,
+    reduced_axes: Vec<usize>,
+    group_shape: Option<Vec<usize>>,
+    groups: Vec<GroupStats>,
+}
+
+impl TensorStats{
+    pub fn new(tensor_id: TensorId, axis_ids: Vec<AxisId>, axes: Option<&[AxisId]>) -> Result<Self, DatasetStatsError>{
+        let mut reduced_axes: Vec<usize> = match axes{
+            None => (0..axis_ids.len()).collect(),
+            Some(axes) => axes.iter()
+                .map(|axis_id| axis_ids.iter().position(|ax| ax == axis_id).ok_or_else(|| {
+                    ProcessingError::AxisNotFound{tensor_id: tensor_id.clone(), axis_id: axis_id.clone()}
+                }))
+                .collect::<Result<_, _>>()?,
+        };
+        if let Some(batch_index) = axis_ids.iter().position(|ax| &**ax == "batch"){
+            if !reduced_axes.contains(&batch_index){
+                reduced_axes.push(batch_index);
+            }
+        }
+        reduced_axes.sort();
+        let axes = axes.map(|axes| axes.to_vec());
+        Ok(Self{tensor_id, axis_ids, axes, reduced_axes, group_shape: None, groups: vec![]})
+    }
+
+    /// Ids of the axes along which statistics are kept separately
+    pub fn kept_axes(&self) -> Vec<AxisId>{
+        self.axis_ids.iter().enumerate()
+            .filter(|(idx, _)| !self.reduced_axes.contains(idx))
+            .map(|(_, axis_id)| axis_id.clone())
+            .collect()
+    }
+
+    pub fn groups(&self) -> &[GroupStats]{
+        &self.groups
+    }
+
+    pub fn push(&mut self, data: &NpyArray) -> Result<(), DatasetStatsError>{
+        if data.shape().len() != self.axis_ids.len(){
+            return Err(DatasetStatsError::MismatchedNumDimensions {
+                tensor_id: self.tensor_id.clone(), expected: self.axis_ids.len(), found: data.shape().len()
+            })
+        }
+        let group_shape: Vec<usize> = data.shape().iter().enumerate()
+            .filter(|(idx, _)| !self.reduced_axes.contains(idx))
+            .map(|(_, extent)| *extent)
+            .collect();
+        match &self.group_shape{
+            Some(expected) if *expected != group_shape => return Err(DatasetStatsError::MismatchedGroupShape {
+                tensor_id: self.tensor_id.clone(), expected: expected.clone(), found: group_shape,
+            }),
+            Some(_) => (),
+            None => {
+                self.groups = vec![GroupStats::default(); group_shape.iter().product()];
+                self.group_shape = Some(group_shape);
+            }
+        }
+        let mut groups = self.groups.iter_mut();
+        reduce_groups(&data.to_f32(), &self.reduced_axes, |values|{
+            let group = groups.next().unwrap();
+            group.moments.push_values(values);
+            group.digest.push_values(values);
+        });
+        Ok(())
+    }
+
+    fn ensure_samples(&self) -> Result<(), DatasetStatsError>{
+        if self.groups.is_empty() || self.groups.iter().all(|group| group.moments.count() == 0){
+            return Err(DatasetStatsError::NoSamples(self.tensor_id.clone()))
+        }
+        Ok(())
+    }
+
+    /// The single non-reduced axis, if any, along which a fixed preprocessing step would have to vary
+    fn along_axis(&self) -> Result<Option<NonBatchAxisId>, DatasetStatsError>{
+        let kept_axes = self.kept_axes();
+        match kept_axes.as_slice(){
+            [] => Ok(None),
+            [axis_id] => Ok(Some(NonBatchAxisId::try_from(axis_id.clone()).unwrap())),
+            _ => Err(DatasetStatsError::TooManyKeptAxes { tensor_id: self.tensor_id.clone(), axes: kept_axes }),
+        }
+    }
+
+    /// Freezes these statistics into the equivalent of a `zero_mean_unit_variance` step over the whole dataset
+    pub fn to_fixed_zmuv(&self) -> Result<FixedZmuv, DatasetStatsError>{
+        self.ensure_samples()?;
+        let means_and_stds: Vec<SimpleFixedZmuv> = self.groups.iter()
+            .map(|group|{
+                let std = group.moments.std() as f32;
+                Ok(SimpleFixedZmuv{
+                    mean: group.moments.mean() as f32,
+                    std: ZmuvStdDeviation::try_from(std).map_err(|_| DatasetStatsError::BadStandardDeviation{
+                        tensor_id: self.tensor_id.clone(), std
+                    })?,
+                })
+            })
+            .collect::<Result<_, DatasetStatsError>>()?;
+        Ok(match self.along_axis()?{
+            None => FixedZmuv::Simple(means_and_stds.into_iter().next().unwrap()),
+            Some(axis) => FixedZmuv::AlongAxis(FixedZmuvAlongAxis{
+                mean_and_std: NonEmptyList::try_from(means_and_stds).unwrap(),
+                axis,
+            }),
+        })
+    }
+
+    /// Freezes these statistics into the equivalent of a `scale_range` step over the whole dataset
+    pub fn to_scale_linear(&self, min_percentile: f32, max_percentile: f32, eps: PreprocessingEpsilon) -> Result<ScaleLinearDescr, DatasetStatsError>{
+        self.ensure_samples()?;
+        let eps = f32::from(eps) as f64;
+        let gain_offsets: Vec<(f32, f32)> = self.groups.iter()
+            .map(|group|{
+                let lower = group.digest.quantile(min_percentile as f64 / 100.0);
+                let upper = group.digest.quantile(max_percentile as f64 / 100.0);
+                let gain = 1.0 / (upper - lower + eps);
+                (gain as f32, (-lower * gain) as f32)
+            })
+            .collect();
+        Ok(match self.along_axis()?{
+            None => {
+                let (gain, offset) = gain_offsets[0];
+                ScaleLinearDescr::Simple(SimpleScaleLinearDescr{gain, offset})
+            },
+            Some(axis) => ScaleLinearDescr::AlongAxis(ScaleLinearAlongAxisDescr{
+                axis,
+                gain_offsets: NonEmptyList::try_from(gain_offsets).unwrap(),
+            }),
+        })
+    }
+}
+
+/// Collects the statistics required to turn the dataset-dependent preprocessing of a model
+/// (`zero_mean_unit_variance`, `scale_range`) into fixed preprocessing steps.
+#[derive(Default)]
+pub struct DatasetStats{
+    tensors: HashMap<TensorId, Vec<TensorStats>>,
+}
+
+impl DatasetStats{
+    /// Starts tracking statistics of `tensor_id`, reduced jointly over `axes` (all axes if `None`)
+    pub fn track(&mut self, tensor_id: TensorId, axis_ids: Vec<AxisId>, axes: Option<Vec<AxisId>>) -> Result<(), DatasetStatsError>{
+        let trackers = self.tensors.entry(tensor_id.clone()).or_default();
+        if trackers.iter().any(|stats| stats.axes == axes){
+            return Ok(())
+        }
+        trackers.push(TensorStats::new(tensor_id, axis_ids, axes.as_deref())?);
+        Ok(())
+    }
+
+    /// Tracks every statistic needed by the preprocessing steps of `interface`
+    pub fn for_interface<DATA: Borrow<NpyArray>>(interface: &ModelInterface<DATA>) -> Result<Self, DatasetStatsError>{
+        let mut out = Self::default();
+        for slot in interface.inputs().iter(){
+            for step in slot.tensor_meta.preprocessing(){
+                let (tensor_id, axes) = match step{
+                    PreprocessingDescr::ZeroMeanUnitVariance(Zmuv{axes, ..}) => {
+                        (&slot.tensor_meta.id, axes.as_ref().map(|axes| axes.to_vec()))
+                    },
+                    PreprocessingDescr::ScaleRange(ScaleRangeDescr{axes, reference_tensor, ..}) => {
+                        (reference_tensor.as_ref().unwrap_or(&slot.tensor_meta.id), axes.clone())
+                    },
+                    _ => continue,
+                };
+                let Some(reference_slot) = interface.inputs().iter().find(|slot| slot.tensor_meta.id == *tensor_id) else {
+                    return Err(DatasetStatsError::UntrackedTensor(tensor_id.clone()))
+                };
+                let axis_ids = reference_slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+                out.track(tensor_id.clone(), axis_ids, axes)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Feeds one sample of `tensor_id` into all statistics tracked for it
+    pub fn push(&mut self, tensor_id: &TensorId, data: &NpyArray) -> Result<(), DatasetStatsError>{
+        let Some(trackers) = self.tensors.get_mut(tensor_id) else {
+            return Err(DatasetStatsError::UntrackedTensor(tensor_id.clone()))
+        };
+        for stats in trackers.iter_mut(){
+            stats.push(data)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, tensor_id: &TensorId, axes: Option<&[AxisId]>) -> Option<&TensorStats>{
+        self.tensors.get(tensor_id)?.iter()
+            .find(|stats| stats.axes.as_deref() == axes)
+    }
+
+    fn get_or_err(&self, tensor_id: &TensorId, axes: Option<&[AxisId]>) -> Result<&TensorStats, DatasetStatsError>{
+        self.get(tensor_id, axes).ok_or_else(|| DatasetStatsError::UntrackedTensor(tensor_id.clone()))
+    }
+
+    /// Replaces the dataset-dependent steps in `preprocessing` of tensor `tensor_id` with
+    /// fixed ones computed from the collected statistics
+    pub fn freeze_preprocessing(
+        &self, tensor_id: &TensorId, preprocessing: &[PreprocessingDescr]
+    ) -> Result<Vec<PreprocessingDescr>, DatasetStatsError>{
+        preprocessing.iter()
+            .map(|step| Ok(match step{
+                PreprocessingDescr::ZeroMeanUnitVariance(Zmuv{axes, ..}) => {
+                    let stats = self.get_or_err(tensor_id, axes.as_deref())?;
+                    PreprocessingDescr::FixedZeroMeanUnitVariance(stats.to_fixed_zmuv()?)
+                },
+                PreprocessingDescr::ScaleRange(descr) => {
+                    let reference = descr.reference_tensor.as_ref().unwrap_or(tensor_id);
+                    let stats = self.get_or_err(reference, descr.axes.as_deref())?;
+                    PreprocessingDescr::ScaleLinear(
+                        stats.to_scale_linear(descr.percentiles.min(), descr.percentiles.max(), descr.eps)?
+                    )
+                },
+                step => step.clone(),
+            }))
+            .collect()
+    }
+}
+
+#[test]
+fn test_dataset_stats_match_exact_values(){
+    let tensor_id = TensorId::try_from("raw").unwrap();
+    let axis_ids: Vec<AxisId> = ["batch", "channel", "x"].into_iter().map(|ax| AxisId::try_from(ax).unwrap()).collect();
+    let mut stats = TensorStats::new(tensor_id, axis_ids.clone(), Some(&axis_ids[2..])).unwrap();
+
+    let mut all_values: Vec<f32> = vec![];
+    for sample_idx in 0..10{
+        let sample = ndarray::Array::from_shape_fn((1, 2, 1000), |(_, c, x)| {
+            ((sample_idx * 1000 + x) as f32).sqrt() + (c * 100) as f32
+        });
+        all_values.extend(sample.index_axis(ndarray::Axis(1), 0).iter());
+        stats.push(&NpyArray::from(sample.into_dyn())).unwrap();
+    }
+    assert_eq!(stats.kept_axes(), vec![axis_ids[1].clone()]);
+
+    let (mean, std) = crate::processing::mean_and_std(&mut all_values.clone());
+    let channel_0 = &stats.groups()[0];
+    assert!((channel_0.moments.mean() as f32 - mean).abs() < 1e-3);
+    assert!((channel_0.moments.std() as f32 - std).abs() < 1e-3);
+
+    let [p1, p99] = crate::processing::percentiles(&mut all_values, [1.0, 99.0]);
+    assert!((channel_0.digest.quantile(0.01) as f32 - p1).abs() / p1 < 0.01);
+    assert!((channel_0.digest.quantile(0.99) as f32 - p99).abs() / p99 < 0.01);
+
+    let channel_1 = &stats.groups()[1];
+    assert!((channel_1.moments.mean() as f32 - mean - 100.0).abs() < 1e-3);
+}
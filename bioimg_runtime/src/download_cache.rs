@@ -0,0 +1,222 @@
+use std::io::Write;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bioimg_spec::rdf::{HttpUrl, Sha256};
+use sha2::Digest;
+
+use crate::zip_writer_ext::HashingWriter;
+
+#[derive(thiserror::Error, Debug)]
+pub enum DownloadError{
+    #[error("Could not write to download cache: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not fetch {url}: {reason}")]
+    FetchError{url: HttpUrl, reason: String},
+    #[error("Contents of {url} don't match the declared sha256 (expected {expected}, found {found})")]
+    HashMismatch{url: HttpUrl, expected: Sha256, found: Sha256},
+}
+
+/// Something that can retrieve the contents of a url
+pub trait UrlFetcher: Send + Sync{
+    fn fetch(&self, url: &HttpUrl, sink: &mut dyn Write) -> Result<(), DownloadError>;
+}
+
+/// Fetches urls over the network
+#[cfg(not(target_arch = "wasm32"))]
+pub struct HttpFetcher;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl UrlFetcher for HttpFetcher{
+    fn fetch(&self, url: &HttpUrl, sink: &mut dyn Write) -> Result<(), DownloadError> {
+        let response = ureq::get(url.as_str()).call()
+            .map_err(|e| DownloadError::FetchError{url: url.clone(), reason: e.to_string()})?;
+        if response.status() / 100 != 2{
+            return Err(DownloadError::FetchError{
+                url: url.clone(), reason: format!("unexpected status {}", response.status())
+            })
+        }
+        std::io::copy(&mut response.into_reader(), sink)?;
+        Ok(())
+    }
+}
+
+/// Serves urls from a local directory by mapping the url path onto it
+pub struct LocalDirFetcher{
+    root: PathBuf,
+}
+
+impl LocalDirFetcher{
+    pub fn new(root: impl Into<PathBuf>) -> Self{
+        Self{root: root.into()}
+    }
+}
+
+impl UrlFetcher for LocalDirFetcher{
+    fn fetch(&self, url: &HttpUrl, sink: &mut dyn Write) -> Result<(), DownloadError> {
+        let path = self.root.join(url.path().trim_start_matches('/'));
+        let mut file = std::fs::File::open(&path)
+            .map_err(|e| DownloadError::FetchError{url: url.clone(), reason: format!("{}: {e}", path.to_string_lossy())})?;
+        std::io::copy(&mut file, sink)?;
+        Ok(())
+    }
+}
+
+/// A directory of downloaded files, addressed by their sha256.
+///
+/// Files with a known sha256 are verified before being stored and again every time they are served
+/// from the cache, so that corrupted or tampered entries are fetched again. Files without
+/// one are keyed by the sha256 of their url; since nothing tells whether the remote file changed, those
+/// are fetched again the first time each cache (and its clones) resolves them.
+#[derive(Clone)]
+pub struct DownloadCache{
+    dir: PathBuf,
+    fetcher: Arc<dyn UrlFetcher>,
+    refreshed_url_entries: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for DownloadCache{
+    fn default() -> Self {
+        // a per-user directory, so that other users can't plant entries in it
+        let base_dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        Self::new(base_dir.join("bioimg_download_cache"), Arc::new(HttpFetcher))
+    }
+}
+
+fn file_sha256(path: &Path) -> std::io::Result<Sha256>{
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(Sha256::from_digest(hasher.finalize().into()))
+}
+
+fn hex_sha256(data: &[u8]) -> String{
+    Sha256::from_digest(sha2::Sha256::digest(data).into()).as_str().to_owned()
+}
+
+impl std::fmt::Debug for DownloadCache{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DownloadCache{{ dir: {:?} }}", self.dir)
+    }
+}
+
+impl DownloadCache{
+    pub fn new(dir: impl Into<PathBuf>, fetcher: Arc<dyn UrlFetcher>) -> Self{
+        Self{dir: dir.into(), fetcher, refreshed_url_entries: Default::default()}
+    }
+
+    pub fn dir(&self) -> &Path{
+        &self.dir
+    }
+
+    fn cache_path(&self, url: &HttpUrl, sha256: Option<&Sha256>) -> PathBuf{
+        let key = match sha256{
+            Some(sha256) => self.dir.join("sha256").join(sha256.as_str()),
+            None => self.dir.join("url").join(hex_sha256(url.as_str().as_bytes())),
+        };
+        // keep the extension so that the cached file can still be recognized for what it is
+        match url.path().rsplit('/').next().and_then(|name| name.rsplit_once('.')){
+            Some((_, ext)) if !ext.is_empty() => key.with_extension(ext),
+            _ => key,
+        }
+    }
+
+    /// Returns the path to a local copy of the contents of `url`, downloading it if necessary
+    pub fn resolve(&self, url: &HttpUrl, sha256: Option<&Sha256>) -> Result<PathBuf, DownloadError>{
+        let path = self.cache_path(url, sha256);
+        let is_fresh = match sha256{
+            Some(expected) => path.exists() && file_sha256(&path)? == *expected,
+            None => self.refreshed_url_entries.lock().unwrap().contains(&path),
+        };
+        if is_fresh{
+            return Ok(path)
+        }
+        let parent_dir = path.parent().expect("cache paths are always inside the cache dir");
+        std::fs::create_dir_all(parent_dir)?;
+
+        let mut tmp_file = tempfile::NamedTempFile::new_in(parent_dir)?;
+        let found = {
+            let mut sink = HashingWriter::new(tmp_file.as_file_mut(), None);
+            self.fetcher.fetch(url, &mut sink)?;
+            sink.flush()?;
+            sink.finalize()
+        };
+        if let Some(expected) = sha256{
+            if *expected != found{
+                return Err(DownloadError::HashMismatch{url: url.clone(), expected: expected.clone(), found})
+            }
+        }
+        tmp_file.persist(&path).map_err(|e| e.error)?;
+        if sha256.is_none(){
+            self.refreshed_url_entries.lock().unwrap().insert(path.clone());
+        }
+        Ok(path)
+    }
+}
+
+#[test]
+fn test_download_cache_resolves_and_verifies(){
+    let served_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(served_dir.path().join("weights")).unwrap();
+    std::fs::write(served_dir.path().join("weights/model.onnx"), b"weights").unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = DownloadCache::new(cache_dir.path(), Arc::new(LocalDirFetcher::new(served_dir.path())));
+
+    let url = HttpUrl::try_from("https://example.com/weights/model.onnx".to_owned()).unwrap();
+    let sha256 = Sha256::from_digest(sha2::Sha256::digest(b"weights").into());
+    let cached = cache.resolve(&url, Some(&sha256)).unwrap();
+    assert_eq!(std::fs::read(&cached).unwrap(), b"weights");
+    assert_eq!(cached.extension().unwrap(), "onnx");
+
+    // already cached, so the original is no longer needed
+    std::fs::remove_file(served_dir.path().join("weights/model.onnx")).unwrap();
+    assert_eq!(cache.resolve(&url, Some(&sha256)).unwrap(), cached);
+
+    std::fs::write(served_dir.path().join("weights/model.onnx"), b"tampered").unwrap();
+    // the content-addressed entry is unaffected by changes to the remote file
+    assert_eq!(std::fs::read(cache.resolve(&url, Some(&sha256)).unwrap()).unwrap(), b"weights");
+    let by_url = cache.resolve(&url, None).unwrap();
+    assert_eq!(std::fs::read(&by_url).unwrap(), b"tampered");
+
+    // entries keyed by url are reused by the same cache, but fetched again by a new one
+    std::fs::write(served_dir.path().join("weights/model.onnx"), b"updated").unwrap();
+    assert_eq!(std::fs::read(cache.resolve(&url, None).unwrap()).unwrap(), b"tampered");
+    let new_cache = DownloadCache::new(cache_dir.path(), Arc::new(LocalDirFetcher::new(served_dir.path())));
+    assert_eq!(new_cache.resolve(&url, None).unwrap(), by_url);
+    assert_eq!(std::fs::read(&by_url).unwrap(), b"updated");
+
+    // corrupted content-addressed entries are fetched again
+    std::fs::write(&cached, b"planted").unwrap();
+    std::fs::write(served_dir.path().join("weights/model.onnx"), b"weights").unwrap();
+    assert_eq!(cache.resolve(&url, Some(&sha256)).unwrap(), cached);
+    assert_eq!(std::fs::read(&cached).unwrap(), b"weights");
+
+    let wrong_sha256 = Sha256::from_digest([0; 32]);
+    assert!(matches!(cache.resolve(&url, Some(&wrong_sha256)), Err(DownloadError::HashMismatch{..})));
+}
+
+#[test]
+fn test_cached_url_sources_are_fetched_lazily(){
+    use crate::{zip_archive_ext::SharedZipArchive, FileSource};
+
+    let served_dir = tempfile::tempdir().unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let package_dir = tempfile::tempdir().unwrap();
+    let cache = Arc::new(DownloadCache::new(cache_dir.path(), Arc::new(LocalDirFetcher::new(served_dir.path()))));
+    let archive = SharedZipArchive::open_dir(package_dir.path()).with_download_cache(cache);
+
+    let url = HttpUrl::try_from("https://example.com/model.onnx".to_owned()).unwrap();
+    let sha256 = Sha256::from_digest(sha2::Sha256::digest(b"weights").into());
+    // nothing is being served yet, so this would fail if it were fetched right away
+    let source = FileSource::from_rdf_file_reference_with_sha256(
+        archive, &bioimg_spec::rdf::FileReference::Url(url), Some(&sha256)
+    ).unwrap();
+    assert!(matches!(source, FileSource::CachedUrl{..}));
+    assert_eq!(source.file_name(), Some("model.onnx"));
+
+    std::fs::write(served_dir.path().join("model.onnx"), b"weights").unwrap();
+    let mut contents = vec![];
+    source.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"weights");
+}
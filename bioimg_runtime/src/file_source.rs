@@ -2,7 +2,7 @@ use std::{borrow::Borrow, fmt::Display, io::{Read, Seek, Write}, path::Path, syn
 
 use bioimg_spec::rdf::{self, FileReference, HttpUrl};
 
-use crate::{zip_archive_ext::SharedZipArchive, zip_writer_ext::ModelZipWriter, zoo_model::ModelPackingError};
+use crate::{download_cache::DownloadCache, zip_archive_ext::{RdfFileReferenceReadError, SharedZipArchive}, zip_writer_ext::ModelZipWriter, zoo_model::ModelPackingError};
 
 #[derive(thiserror::Error, Debug)]
 pub enum FileSourceError{
@@ -11,7 +11,9 @@ pub enum FileSourceError{
     #[error("IO error trying to read {path}: {inner}")]
     ZipError{inner: zip::result::ZipError, path: String},
     #[error("Error downloading file: {reason}")]
-    HttpError{reason: String}
+    HttpError{reason: String},
+    #[error("Could not fetch remote file: {0}")]
+    FetchError(#[from] RdfFileReferenceReadError),
 }
 
 #[derive(Clone, Debug)]
@@ -19,6 +21,8 @@ pub enum FileSource{
     LocalFile{path: Arc<Path>},
     FileInZipArchive{archive: SharedZipArchive, inner_path: Arc<str>},
     HttpUrl(Arc<HttpUrl>),
+    /// A url that is only fetched (through `cache`, and checked against `sha256`) once its contents are read
+    CachedUrl{url: Arc<HttpUrl>, sha256: Option<rdf::Sha256>, cache: Arc<DownloadCache>},
 }
 
 impl PartialEq for FileSource{
@@ -35,6 +39,10 @@ impl PartialEq for FileSource{
                 arch_self == arch_other && path_self == path_other
             },
             (Self::HttpUrl(self_url), Self::HttpUrl(other_url)) => self_url == other_url,
+            (
+                Self::CachedUrl{url: url_self, sha256: sha256_self, ..},
+                Self::CachedUrl{url: url_other, sha256: sha256_other, ..},
+            ) => url_self == url_other && sha256_self == sha256_other,
             _ => false
         }
     }
@@ -46,31 +54,47 @@ impl Display for FileSource{
         match self{
             Self::LocalFile { path } => write!(f, "{}", path.to_string_lossy()),
             Self::FileInZipArchive { inner_path, .. } => write!(f, "*.zip/{inner_path}"), //FIXME? *.zip?
-            Self::HttpUrl(http_url) => write!(f, "{}", http_url.as_str()),
+            Self::HttpUrl(http_url) | Self::CachedUrl{url: http_url, ..} => write!(f, "{}", http_url.as_str()),
         }
     }
 }
 
 impl FileSource{
-    //FIXME: add some cancellation token?
+    /// The name of the file this source points to, if it has one
+    pub fn file_name(&self) -> Option<&str>{
+        let name = match self{
+            Self::LocalFile { path } => path.file_name()?.to_str()?,
+            Self::FileInZipArchive { inner_path, .. } => inner_path.rsplit('/').next()?,
+            Self::HttpUrl(url) | Self::CachedUrl{url, ..} => url.path().rsplit('/').next()?,
+        };
+        if name.is_empty(){
+            None
+        }else{
+            Some(name)
+        }
+    }
+
+    /// The size of the file in bytes, if it can be known without downloading it
+    pub fn size_hint(&self) -> Option<u64>{
+        match self{
+            Self::LocalFile { path } => std::fs::metadata(path).ok().map(|meta| meta.len()),
+            Self::FileInZipArchive { archive, inner_path } => archive.entry_size(inner_path),
+            Self::HttpUrl(_) | Self::CachedUrl{..} => None,
+        }
+    }
+
     fn rdf_dump(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
-    ) -> Result<rdf::FsPath, ModelPackingError> {
-        let extension = match self{
-            Self::LocalFile { path } => path.extension().map(|ex| ex.to_string_lossy().to_string()),
-            Self::FileInZipArchive { inner_path, .. } => {
-                inner_path.split(".").last().map(|s| s.to_owned())
-            },
-            Self::HttpUrl(url) => {
-                url.path().split(".").last().map(|s| s.to_owned())
-            }
-        };
-        let output_inner_path = match extension{
-            Some(ext) => rdf::FsPath::unique_suffixed(&format!(".{ext}")),
-            None => rdf::FsPath::unique(),
+        stem: Option<&str>,
+    ) -> Result<(rdf::FsPath, rdf::Sha256), ModelPackingError> {
+        let file_name = self.file_name().unwrap_or("file");
+        let entry_name = match (stem, file_name.rsplit_once('.')){
+            (Some(stem), Some((_, extension))) => format!("{stem}.{extension}"),
+            (Some(stem), None) => stem.to_owned(),
+            (None, _) => file_name.to_owned(),
         };
-        zip_file.write_file(&output_inner_path, |writer| -> Result<u64, ModelPackingError>{
+        let (_, output_inner_path, sha256) = zip_file.write_new_file_of_size(&entry_name, self.size_hint(), |writer| -> Result<u64, ModelPackingError>{
             let copied_bytes: u64 = match self{
                 Self::LocalFile { path } => {
                     std::io::copy(&mut std::fs::File::open(path)?, writer)?
@@ -97,27 +121,41 @@ impl FileSource{
                     }
                     let mut response_reader = response.into_reader();
                     std::io::copy(&mut response_reader, writer)? //FIXME!! limit size or whatever
-                }
+                },
+                Self::CachedUrl{url, sha256, cache} => {
+                    std::io::copy(&mut std::fs::File::open(cache.resolve(url, sha256.as_ref())?)?, writer)?
+                },
             };
             Ok(copied_bytes)
         })?;
-        Ok(output_inner_path)
+        Ok((output_inner_path, sha256))
     }
 
     pub fn rdf_dump_as_file_reference(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<rdf::FileReference, ModelPackingError> {
-        let output_inner_path = self.rdf_dump(zip_file)?;
+        let (output_inner_path, _) = self.rdf_dump(zip_file, None)?;
         Ok(rdf::FileReference::Path(output_inner_path))
     }
 
+    /// Writes the file under its original name
     pub fn dump_as_file_description(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<rdf::FileDescription, ModelPackingError> {
-        let file_reference = self.rdf_dump_as_file_reference(zip_file)?;
-        Ok(rdf::FileDescription{source: file_reference, sha256: None})
+        let (output_inner_path, sha256) = self.rdf_dump(zip_file, None)?;
+        Ok(rdf::FileDescription{source: rdf::FileReference::Path(output_inner_path), sha256: Some(sha256)})
+    }
+
+    /// Writes the file as `{stem}.{original extension}`
+    pub fn dump_as_named_file_description(
+        &self,
+        zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        stem: &str,
+    ) -> Result<rdf::FileDescription, ModelPackingError> {
+        let (output_inner_path, sha256) = self.rdf_dump(zip_file, Some(stem))?;
+        Ok(rdf::FileDescription{source: rdf::FileReference::Path(output_inner_path), sha256: Some(sha256)})
     }
 }
 
@@ -125,17 +163,33 @@ impl FileSource{
     pub fn from_rdf_file_descr<T: Borrow<FileReference>>(
         archive: SharedZipArchive, file_reference: &rdf::FileDescription<T>
     ) -> Result<Self, FileSourceError>{
-        Self::from_rdf_file_reference(archive, file_reference.source.borrow())
+        Self::from_rdf_file_reference_with_sha256(archive, file_reference.source.borrow(), file_reference.sha256.as_ref())
     }
 
-
     pub fn from_rdf_file_reference(
         archive: SharedZipArchive, file_reference: &rdf::FileReference
+    ) -> Result<Self, FileSourceError>{
+        Self::from_rdf_file_reference_with_sha256(archive, file_reference, None)
+    }
+
+    /// Like `from_rdf_file_reference`, but urls go through the archive's download cache (if any) and
+    /// are checked against `sha256` when their contents are first read
+    pub fn from_rdf_file_reference_with_sha256(
+        archive: SharedZipArchive, file_reference: &rdf::FileReference, sha256: Option<&rdf::Sha256>,
     ) -> Result<Self, FileSourceError>{
         Ok(match file_reference{
-            rdf::FileReference::Url(url) => Self::HttpUrl(Arc::new(url.clone())),
+            rdf::FileReference::Url(url) => match archive.download_cache(){
+                Some(cache) => Self::CachedUrl{url: Arc::new(url.clone()), sha256: sha256.cloned(), cache: cache.clone()},
+                None => Self::HttpUrl(Arc::new(url.clone())),
+            },
             rdf::FileReference::Path(path) => {
                 let path = String::from(path);
+                if archive.directory().is_some(){
+                    let local_path = archive.entry_path(&path)
+                        .filter(|local_path| local_path.is_file())
+                        .ok_or_else(|| FileSourceError::ZipError{inner: zip::result::ZipError::FileNotFound, path: path.clone()})?;
+                    return Ok(Self::LocalFile{path: Arc::from(local_path)})
+                }
                 archive.with_entry(&path, |_| {}).map_err(|e|{
                     FileSourceError::ZipError{inner: e, path: path.clone()}
                 })?;
@@ -166,7 +220,11 @@ impl FileSource{
                 .map_err(|e| FileSourceError::HttpError { reason: e.to_string()})?
                 .into_reader();
                 Ok(response_reader.read_to_end(buf)?)
-            }
+            },
+            Self::CachedUrl{url, sha256, cache} => {
+                let path = cache.resolve(url, sha256.as_ref()).map_err(RdfFileReferenceReadError::from)?;
+                Ok(std::fs::File::open(path)?.read_to_end(buf)?)
+            },
         }
     }
 }
@@ -0,0 +1,178 @@
+use std::borrow::Borrow;
+use std::fmt::Display;
+
+use bioimg_spec::rdf::{self, FileReference, Sha256};
+use bioimg_spec::rdf::model::{self as modelrdf, ModelRdfV0_5};
+use sha2::Digest;
+
+use crate::zip_archive_ext::SharedZipArchive;
+
+/// What to do when a file in a model archive doesn't match the sha256 declared for it in the rdf
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashVerification{
+    /// Refuse to load the model
+    #[default]
+    Strict,
+    /// Load the model anyway, returning the mismatches to the caller
+    WarnOnly,
+}
+
+#[derive(Clone, Debug)]
+pub struct HashMismatch{
+    pub path: String,
+    pub expected: Sha256,
+    /// `None` if the file is missing from the archive
+    pub found: Option<Sha256>,
+}
+
+impl Display for HashMismatch{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.found{
+            Some(found) => write!(f, "{}: expected sha256 {}, found {found}", self.path, self.expected),
+            None => write!(f, "{}: expected sha256 {}, but file is missing", self.path, self.expected),
+        }
+    }
+}
+
+/// Every mismatched file found while verifying a model archive
+#[derive(Clone, Debug)]
+pub struct HashMismatches(pub Vec<HashMismatch>);
+
+impl Display for HashMismatches{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, mismatch) in self.0.iter().enumerate(){
+            if idx > 0{
+                write!(f, "; ")?;
+            }
+            write!(f, "{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the sha256 of entry `name` of `archive`, streaming its contents
+pub fn sha256_of_entry(archive: &SharedZipArchive, name: &str) -> Result<Sha256, zip::result::ZipError>{
+    let digest = archive.with_entry(name, |entry| -> std::io::Result<[u8; 32]>{
+        let mut hasher = sha2::Sha256::new();
+        std::io::copy(entry, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    })??;
+    Ok(Sha256::from_digest(digest))
+}
+
+fn push_declared<'a, R: Borrow<FileReference>>(
+    declared: &mut Vec<(&'a FileReference, &'a Sha256)>, file_descr: &'a rdf::FileDescription<R>
+){
+    if let Some(sha256) = &file_descr.sha256{
+        declared.push((file_descr.source.borrow(), sha256));
+    }
+}
+
+fn push_declared_weights_base<'a>(declared: &mut Vec<(&'a FileReference, &'a Sha256)>, base: &'a modelrdf::WeightsDescrBase){
+    if let Some(sha256) = &base.sha256{
+        declared.push((&base.source, sha256));
+    }
+}
+
+/// All file references in `model_rdf` that come with a sha256
+pub fn declared_hashes(model_rdf: &ModelRdfV0_5) -> Vec<(&FileReference, &Sha256)>{
+    let mut declared = vec![];
+    for attachment in &model_rdf.attachments{
+        push_declared(&mut declared, attachment);
+    }
+    if let Some(modelrdf::DatasetDescrEnum::DatasetDescr(dataset)) = &model_rdf.training_data{
+        for attachment in &dataset.attachments{
+            push_declared(&mut declared, attachment);
+        }
+    }
+    for input in model_rdf.inputs.iter(){
+        push_declared(&mut declared, &input.test_tensor);
+        if let Some(sample_tensor) = &input.sample_tensor{
+            push_declared(&mut declared, sample_tensor);
+        }
+    }
+    for output in model_rdf.outputs.iter(){
+        push_declared(&mut declared, &output.test_tensor);
+        if let Some(sample_tensor) = &output.sample_tensor{
+            push_declared(&mut declared, sample_tensor);
+        }
+    }
+
+    let weights = &model_rdf.weights;
+    if let Some(keras_hdf5) = &weights.keras_hdf5{
+        push_declared_weights_base(&mut declared, &keras_hdf5.base);
+    }
+    if let Some(onnx) = &weights.onnx{
+        push_declared_weights_base(&mut declared, &onnx.base);
+    }
+    if let Some(pytorch_state_dict) = &weights.pytorch_state_dict{
+        push_declared_weights_base(&mut declared, &pytorch_state_dict.base);
+        if let modelrdf::PytorchArchitectureDescr::FromFileDescr(arch) = &pytorch_state_dict.architecture{
+            push_declared(&mut declared, &arch.file_descr);
+        }
+        if let Some(dependencies) = &pytorch_state_dict.dependencies{
+            push_declared(&mut declared, dependencies);
+        }
+    }
+    if let Some(tensorflow_js) = &weights.tensorflow_js{
+        push_declared_weights_base(&mut declared, &tensorflow_js.base);
+    }
+    if let Some(tensorflow_saved_model_bundle) = &weights.tensorflow_saved_model_bundle{
+        push_declared_weights_base(&mut declared, &tensorflow_saved_model_bundle.base);
+        if let Some(dependencies) = &tensorflow_saved_model_bundle.dependencies{
+            push_declared(&mut declared, dependencies);
+        }
+    }
+    if let Some(torchscript) = &weights.torchscript{
+        push_declared_weights_base(&mut declared, &torchscript.base);
+    }
+    declared
+}
+
+/// Hashes every archive entry in `declared` and compares it with its declared sha256.
+///
+/// Url references are not inside the archive and are skipped.
+pub fn find_mismatches<'a>(
+    archive: &SharedZipArchive, declared: impl IntoIterator<Item=(&'a FileReference, &'a Sha256)>
+) -> Result<Vec<HashMismatch>, zip::result::ZipError>{
+    let mut mismatches = vec![];
+    for (file_reference, expected) in declared{
+        let FileReference::Path(path) = file_reference else {
+            continue
+        };
+        let path: String = path.clone().into();
+        let found = match sha256_of_entry(archive, &path){
+            Ok(found) => Some(found),
+            Err(zip::result::ZipError::FileNotFound) => None,
+            Err(err) => return Err(err),
+        };
+        if found.as_ref() != Some(expected){
+            mismatches.push(HashMismatch{path, expected: expected.clone(), found});
+        }
+    }
+    Ok(mismatches)
+}
+
+#[test]
+fn test_find_mismatches(){
+    use crate::zip_archive_ext::ZipArchiveIdentifier;
+    use crate::zip_writer_ext::ModelZipWriter;
+
+    let mut buffer = std::io::Cursor::new(Vec::<u8>::new());
+    let path = rdf::FsPath::unique_suffixed(".txt");
+    let mut writer = ModelZipWriter::new(&mut buffer);
+    let (_, sha256) = writer.write_file(&path, |writer| writer.write_all(b"weights")).unwrap();
+    writer.finish().unwrap();
+
+    let zip_archive = zip::ZipArchive::new(Box::new(std::io::Cursor::new(buffer.into_inner())) as Box<_>).unwrap();
+    let archive = SharedZipArchive::new(ZipArchiveIdentifier::Name("test".to_owned()), zip_archive);
+    let good = FileReference::Path(path.clone());
+    let missing = FileReference::Path(rdf::FsPath::unique_suffixed(".txt"));
+    let wrong_sha256 = Sha256::from_digest([0; 32]);
+
+    assert!(find_mismatches(&archive, [(&good, &sha256)]).unwrap().is_empty());
+    let mismatches = find_mismatches(&archive, [(&good, &wrong_sha256), (&missing, &sha256)]).unwrap();
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].found.as_ref(), Some(&sha256));
+    assert!(mismatches[1].found.is_none());
+}
@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::io::{Cursor, Read, Seek, Write};
+use std::io::{Cursor, Seek, Write};
 use std::sync::Arc;
 
 use bioimg_spec::rdf;
@@ -46,6 +46,7 @@ impl TryFrom<Arc<DynamicImage>> for Icon {
     }
 }
 
+#[derive(Clone)]
 pub enum Icon {
     Image(IconImage),
     Text(rdf::icon::EmojiIcon),
@@ -66,8 +67,7 @@ impl Icon{
             Self::Text(emoji) => return Ok(rdf::Icon::Emoji(emoji.clone())),
             Self::Image(icon_img) => icon_img,
         };
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed("_icon.png");
-        zip_file.write_file(&test_tensor_zip_path, |writer| -> Result<(), ModelPackingError> {
+        let (_, test_tensor_zip_path, _) = zip_file.write_new_file("icon.png", |writer| -> Result<(), ModelPackingError> {
             let encoder = PngEncoder::new(writer);
             Ok(icon_img.0.write_with_encoder(encoder)?)
         })?;
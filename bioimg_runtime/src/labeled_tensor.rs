@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::{AnyAxisSize, AxisId, AxisType, InputAxis, OutputAxis, ParameterizedAxisSize};
+
+use crate::npy_array::ArcNpyArray;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LabeledTensorError{
+    #[error("Array has {found} dimensions but {expected} axes were given")]
+    MismatchedNumDimensions{expected: usize, found: usize},
+    #[error("Axis '{0}' appears more than once")]
+    DuplicateAxis(AxisId),
+    #[error("No axis '{0}' in tensor")]
+    NoSuchAxis(AxisId),
+    #[error("Bad axis label '{0}'")]
+    BadAxisLabel(String),
+    #[error("Axis '{axis_id}' has extent {extent}, so it can't be dropped")]
+    CantSqueeze{axis_id: AxisId, extent: usize},
+    #[error("Axis '{axis_id}' is a {found} axis but a {expected} axis is expected")]
+    MismatchedAxisType{axis_id: AxisId, expected: AxisType, found: AxisType},
+    #[error("Extent {extent} of axis '{axis_id}' is invalid: {reason}")]
+    IncompatibleExtent{axis_id: AxisId, extent: usize, reason: String},
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorAxis{
+    pub id: AxisId,
+    pub axis_type: AxisType,
+}
+
+impl TensorAxis{
+    /// Interprets a single-letter axis label as used in e.g. "tczyx": `b`, `c`, `t` and `i` map to the
+    /// batch, channel, time and index axes with their default ids; any other letter is a space axis.
+    pub fn from_letter(letter: char) -> Result<Self, LabeledTensorError>{
+        let (id, axis_type) = match letter.to_ascii_lowercase(){
+            'b' => ("batch".to_owned(), AxisType::Batch),
+            'c' => ("channel".to_owned(), AxisType::Channel),
+            't' => ("time".to_owned(), AxisType::Time),
+            'i' => ("index".to_owned(), AxisType::Index),
+            other => (other.to_string(), AxisType::Space),
+        };
+        let id = AxisId::try_from(id).map_err(|_| LabeledTensorError::BadAxisLabel(letter.to_string()))?;
+        Ok(Self{id, axis_type})
+    }
+}
+
+impl From<&InputAxis> for TensorAxis{
+    fn from(axis: &InputAxis) -> Self {
+        Self{id: axis.id(), axis_type: axis.axis_type()}
+    }
+}
+
+impl From<&OutputAxis> for TensorAxis{
+    fn from(axis: &OutputAxis) -> Self {
+        Self{id: axis.id(), axis_type: axis.axis_type()}
+    }
+}
+
+/// An [NpyArray](crate::NpyArray) whose dimensions are labelled with axis ids and types
+#[derive(Clone)]
+pub struct LabeledTensor{
+    axes: Vec<TensorAxis>,
+    data: ArcNpyArray,
+}
+
+impl LabeledTensor{
+    pub fn new(data: ArcNpyArray, axes: Vec<TensorAxis>) -> Result<Self, LabeledTensorError>{
+        if data.shape().len() != axes.len(){
+            return Err(LabeledTensorError::MismatchedNumDimensions{expected: axes.len(), found: data.shape().len()})
+        }
+        for (idx, axis) in axes.iter().enumerate(){
+            if axes[..idx].iter().any(|previous| previous.id == axis.id){
+                return Err(LabeledTensorError::DuplicateAxis(axis.id.clone()))
+            }
+        }
+        Ok(Self{axes, data})
+    }
+
+    /// Labels `data` with one letter per dimension, e.g. "zyxc" or "tczyx". See [TensorAxis::from_letter]
+    pub fn from_axis_letters(data: ArcNpyArray, letters: &str) -> Result<Self, LabeledTensorError>{
+        let axes = letters.chars().map(TensorAxis::from_letter).collect::<Result<Vec<_>, _>>()?;
+        Self::new(data, axes)
+    }
+
+    pub fn axes(&self) -> &[TensorAxis]{
+        &self.axes
+    }
+
+    pub fn data(&self) -> &ArcNpyArray{
+        &self.data
+    }
+
+    pub fn shape(&self) -> &[usize]{
+        self.data.shape()
+    }
+
+    fn axis_index(&self, axis_id: &AxisId) -> Result<usize, LabeledTensorError>{
+        self.axes.iter().position(|axis| axis.id == *axis_id).ok_or_else(|| LabeledTensorError::NoSuchAxis(axis_id.clone()))
+    }
+
+    /// Reorders the axes of this tensor into `order`, which must contain each axis exactly once
+    pub fn transposed(&self, order: &[AxisId]) -> Result<Self, LabeledTensorError>{
+        if order.len() != self.axes.len(){
+            return Err(LabeledTensorError::MismatchedNumDimensions{expected: order.len(), found: self.axes.len()})
+        }
+        let permutation = order.iter().map(|axis_id| self.axis_index(axis_id)).collect::<Result<Vec<_>, _>>()?;
+        let axes: Vec<TensorAxis> = permutation.iter().map(|idx| self.axes[*idx].clone()).collect();
+        Self::new(Arc::new(self.data.permuted_axes(&permutation)), axes)
+    }
+
+    /// Inserts `axis` with extent 1 at `position`
+    pub fn with_singleton_axis(&self, position: usize, axis: TensorAxis) -> Result<Self, LabeledTensorError>{
+        let mut axes = self.axes.clone();
+        axes.insert(position.min(axes.len()), axis);
+        Self::new(Arc::new(self.data.insert_axis(position.min(self.axes.len()))), axes)
+    }
+
+    /// Removes axis `axis_id`, which must have extent 1
+    pub fn squeezed(&self, axis_id: &AxisId) -> Result<Self, LabeledTensorError>{
+        let idx = self.axis_index(axis_id)?;
+        let extent = self.shape()[idx];
+        if extent != 1{
+            return Err(LabeledTensorError::CantSqueeze{axis_id: axis_id.clone(), extent})
+        }
+        let mut axes = self.axes.clone();
+        axes.remove(idx);
+        Self::new(Arc::new(self.data.remove_axis(idx)), axes)
+    }
+
+    fn conform(&self, targets: &[(TensorAxis, Option<AnyAxisSize>)]) -> Result<Self, LabeledTensorError>{
+        let mut tensor = self.clone();
+        for axis in &self.axes{
+            if !targets.iter().any(|(target, _)| target.id == axis.id){
+                tensor = tensor.squeezed(&axis.id)?;
+            }
+        }
+        for (target, _) in targets{
+            if !tensor.axes.iter().any(|axis| axis.id == target.id){
+                tensor = tensor.with_singleton_axis(tensor.axes.len(), target.clone())?;
+            }
+        }
+        let order: Vec<AxisId> = targets.iter().map(|(target, _)| target.id.clone()).collect();
+        let tensor = tensor.transposed(&order)?;
+
+        for ((target, size), (axis, &extent)) in targets.iter().zip(tensor.axes.iter().zip(tensor.shape())){
+            if target.axis_type != axis.axis_type{
+                return Err(LabeledTensorError::MismatchedAxisType{
+                    axis_id: axis.id.clone(), expected: target.axis_type, found: axis.axis_type
+                })
+            }
+            let incompatible = |reason: String| LabeledTensorError::IncompatibleExtent{axis_id: axis.id.clone(), extent, reason};
+            match size{
+                Some(AnyAxisSize::Fixed(fixed)) if usize::from(*fixed) != extent => {
+                    return Err(incompatible(format!("expected {fixed}")))
+                },
+                Some(AnyAxisSize::Parameterized(ParameterizedAxisSize{min, step})) => {
+                    let (min, step) = (usize::from(*min), usize::from(*step));
+                    if extent < min || (extent - min) % step != 0{
+                        return Err(incompatible(format!("expected {min} + n * {step}")))
+                    }
+                },
+                _ => (),
+            }
+        }
+        Ok(tensor)
+    }
+
+    /// Squeezes, inserts and reorders axes so that this tensor matches input `axes`, validating channel counts and sizes.
+    ///
+    /// Axes missing from this tensor are added with extent 1, and axes not in `axes` must have extent 1 so they can be dropped.
+    /// Sizes that reference other axes are not checked.
+    pub fn conform_to_input_axes(&self, axes: &[InputAxis]) -> Result<Self, LabeledTensorError>{
+        let targets: Vec<_> = axes.iter().map(|axis| (TensorAxis::from(axis), axis.size())).collect();
+        self.conform(&targets)
+    }
+
+    /// Like [Self::conform_to_input_axes], but for output axes
+    pub fn conform_to_output_axes(&self, axes: &[OutputAxis]) -> Result<Self, LabeledTensorError>{
+        let targets: Vec<_> = axes.iter().map(|axis| (TensorAxis::from(axis), axis.size())).collect();
+        self.conform(&targets)
+    }
+}
+
+impl From<LabeledTensor> for ArcNpyArray{
+    fn from(value: LabeledTensor) -> Self {
+        value.data
+    }
+}
+
+#[test]
+fn test_conform_zyxc_to_bcyx(){
+    use bioimg_spec::rdf::model::InputAxisGroup;
+    use crate::npy_array::NpyArray;
+
+    let axes: InputAxisGroup = serde_json::from_value(serde_json::json!([
+        {"type": "batch"},
+        {"type": "channel", "channel_names": ["r", "g", "b"]},
+        {"type": "space", "id": "y", "size": {"min": 4, "step": 2}},
+        {"type": "space", "id": "x", "size": 5},
+    ])).unwrap();
+    let data = ndarray::ArrayD::from_shape_fn(ndarray::IxDyn(&[1, 4, 5, 3]), |idx| (idx[1] * 100 + idx[2] * 10 + idx[3]) as u16);
+    let zyxc = LabeledTensor::from_axis_letters(Arc::new(NpyArray::from(data)), "zyxc").unwrap();
+
+    let bcyx = zyxc.conform_to_input_axes(&axes).unwrap();
+    assert_eq!(bcyx.shape(), &[1, 3, 4, 5]);
+    let ids: Vec<&str> = bcyx.axes().iter().map(|axis| &*axis.id).collect();
+    assert_eq!(ids, vec!["batch", "channel", "y", "x"]);
+    let NpyArray::ArrayU16(arr) = &**bcyx.data() else { panic!("dtype changed") };
+    assert_eq!(arr[[0, 2, 3, 1]], 312);
+
+    let wrong_channels = LabeledTensor::from_axis_letters(Arc::new(NpyArray::from(ndarray::ArrayD::<u8>::zeros(ndarray::IxDyn(&[2, 4, 5])))), "cyx")
+        .unwrap()
+        .conform_to_input_axes(&axes);
+    assert!(matches!(wrong_channels, Err(LabeledTensorError::IncompatibleExtent{..})));
+}
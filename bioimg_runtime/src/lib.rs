@@ -1,13 +1,27 @@
 pub mod axis_size_resolver;
 pub mod cover_image;
+pub mod dataset_stats;
+pub mod download_cache;
 pub mod icon;
+pub mod labeled_tensor;
 pub mod file_reference;
+pub mod hash_verification;
 pub mod model_interface;
 pub mod model_record;
 pub mod npy_array;
+#[cfg(feature = "onnx")]
+pub mod onnx_backend;
 pub mod package_component;
+pub mod padding;
+pub mod processing;
+pub mod resampling;
+pub mod self_test;
+pub mod tiling;
+pub mod tiff_io;
 pub mod zip_writer_ext;
+pub mod zoo_dataset;
 pub mod zoo_model;
+pub mod zarr_store;
 pub mod model_weights;
 pub mod conda_env;
 pub mod file_source;
@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::io::{Seek, Write};
 use std::sync::Arc;
@@ -9,7 +9,7 @@ use bioimg_spec::rdf::model::postprocessing::PostprocessingDescr;
 use bioimg_spec::rdf::model::preprocessing::ScaleRangeDescr;
 use ndarray_npy::ReadNpyError;
 
-use crate::axis_size_resolver::{ResolvedAxisSizeExt, SlotResolver};
+use crate::axis_size_resolver::{AxisExtentDescr, ExtentResolver, ResolvedAxisSizeExt, ShapeInferenceError, SlotResolver};
 use crate::file_source::FileSourceError;
 use crate::npy_array::NpyArray;
 use crate::zip_archive_ext::SharedZipArchive;
@@ -18,7 +18,8 @@ use crate::zoo_model::ModelPackingError;
 use crate::FileSource;
 use bioimg_spec::rdf::model::axis_size::QualifiedAxisId;
 use bioimg_spec::rdf::model::{AnyAxisSize, InputAxis, OutputAxis, PreprocessingDescr};
-use bioimg_spec::rdf::model::{self as modelrdf, TensorId};
+use bioimg_spec::rdf::model::{self as modelrdf, TensorId, TensorDataDescr, TensorDataDescrs};
+use bioimg_spec::rdf::model::tensor_data_descr::TVs;
 
 use super::axis_size_resolver::AxisSizeResolutionError;
 
@@ -59,13 +60,14 @@ impl InputSlot<Arc<NpyArray>> {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<modelrdf::InputTensorDescr, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed(&format!("_{}_test_tensor.npy", self.tensor_meta.id));
-        zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
+        let (_, test_tensor_zip_path, sha256) = zip_file.write_new_file(
+            &format!("{}_test_tensor.npy", self.tensor_meta.id), |writer| self.test_tensor.write_npy(writer)
+        )?;
         Ok(modelrdf::input_tensor::InputTensorDescr{
             meta: self.tensor_meta.clone(),
             test_tensor: rdf::FileDescription{
                 source: test_tensor_zip_path.into(),
-                sha256: None,
+                sha256: Some(sha256),
             },
             sample_tensor: None, //FIXME
         })
@@ -134,13 +136,14 @@ impl OutputSlot<Arc<NpyArray>> {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<modelrdf::OutputTensorDescr, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed(&format!("_{}_test_tensor.npy", self.tensor_meta.id));
-        zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
+        let (_, test_tensor_zip_path, sha256) = zip_file.write_new_file(
+            &format!("{}_test_tensor.npy", self.tensor_meta.id), |writer| self.test_tensor.write_npy(writer)
+        )?;
         Ok(modelrdf::OutputTensorDescr{
             metadata: self.tensor_meta.clone(),
             test_tensor: rdf::FileDescription{
                 source: test_tensor_zip_path.into(),
-                sha256: None,
+                sha256: Some(sha256),
             },
             sample_tensor: None, //FIXME
         })
@@ -208,7 +211,68 @@ pub enum TensorValidationError {
     #[error("Empty model interface outputs")]
     EmptyOutputs,
     #[error("No tensor with ID {reference}")]
-    InvalidTensorReference{reference: TensorId}
+    InvalidTensorReference{reference: TensorId},
+    #[error("Test tensor '{tensor_id}' has data type {found} but its data description declares {declared}")]
+    DataTypeMismatch{tensor_id: TensorId, declared: modelrdf::DataType, found: modelrdf::DataType},
+    #[error("Test tensor '{tensor_id}' contains value {value}, which is not allowed by its data description")]
+    DisallowedValue{tensor_id: TensorId, value: f64},
+}
+
+fn is_allowed_value(descr: &TensorDataDescr, value: f64) -> bool{
+    match descr{
+        TensorDataDescr::IntervalOrRatio(descr) => {
+            let (min, max) = descr.range;
+            !matches!(min, Some(min) if value < min as f64) && !matches!(max, Some(max) if value > max as f64)
+        },
+        TensorDataDescr::NominalOrOrdinal(descr) => match &descr.values{
+            TVs::Ints(ints) => ints.iter().any(|int| *int as f64 == value),
+            TVs::Floats(floats) => floats.iter().any(|float| *float as f64 == value),
+            TVs::Bools(_) => value == 0.0 || value == 1.0,
+            // string values are labels for the tensor values 0, ..., N
+            TVs::Strings(labels) => value >= 0.0 && value.fract() == 0.0 && value < labels.len().get() as f64,
+        },
+    }
+}
+
+/// Checks that `test_tensor` has the data type and values described by `data`.
+///
+/// `channel_dim` is the index of the channel axis, used when `data` is given per channel
+pub fn validate_tensor_data(
+    tensor_id: &TensorId, data: &TensorDataDescrs, channel_dim: Option<usize>, test_tensor: &NpyArray,
+) -> Result<(), TensorValidationError>{
+    for descr in data.as_slice(){
+        if descr.data_type() != test_tensor.dtype(){
+            return Err(TensorValidationError::DataTypeMismatch{
+                tensor_id: tensor_id.clone(), declared: descr.data_type(), found: test_tensor.dtype()
+            })
+        }
+    }
+    let values = test_tensor.to_f64();
+    for (channel_idx, descr) in data.as_slice().iter().enumerate(){
+        let channel_values = match (data, channel_dim){
+            (TensorDataDescrs::PerChannel(_), Some(dim)) => values.index_axis(ndarray::Axis(dim), channel_idx),
+            _ => values.view(),
+        };
+        if let Some(value) = channel_values.iter().find(|value| !is_allowed_value(descr, **value)){
+            return Err(TensorValidationError::DisallowedValue{tensor_id: tensor_id.clone(), value: *value})
+        }
+    }
+    Ok(())
+}
+
+/// Concrete shape of an output tensor, as produced by the model
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InferredShape{
+    pub shape: Vec<usize>,
+    /// Number of elements to be cropped from both ends of each axis to discard boundary effects
+    pub halo: Vec<usize>,
+}
+
+impl InferredShape{
+    /// Shape after the halo has been cropped away
+    pub fn cropped(&self) -> Vec<usize>{
+        self.shape.iter().zip(&self.halo).map(|(extent, halo)| extent.saturating_sub(2 * halo)).collect()
+    }
 }
 
 #[allow(dead_code)]
@@ -296,6 +360,19 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
         validate_resolution!(inputs);
         validate_resolution!(outputs);
 
+        macro_rules! validate_data {( $slots:ident ) => {
+            for slot in $slots.iter(){
+                let channel_dim = slot.tensor_meta.axes().iter()
+                    .position(|axis| matches!(axis.axis_type(), modelrdf::AxisType::Channel));
+                // undeclared data is a default, not a promise about the values, so it isn't enforced
+                if let Some(data) = slot.tensor_meta.declared_data(){
+                    validate_tensor_data(&slot.tensor_meta.id, data, channel_dim, slot.test_tensor.borrow())?;
+                }
+            }
+        };}
+        validate_data!(inputs);
+        validate_data!(outputs);
+
         for input in inputs.iter(){
             for proc in input.tensor_meta.preprocessing() {
                 let tensor_ref = match proc{
@@ -322,4 +399,123 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
 
         Ok(Self{inputs, outputs})
     }
+
+    /// Computes the exact output shapes the model produces when fed inputs with shapes `input_shapes`.
+    ///
+    /// Input shapes are checked against their axis sizes and batch sizes must agree across inputs.
+    /// Referencing axes get `reference_extent * reference_scale / scale + offset`.
+    pub fn infer_output_shapes(
+        &self, input_shapes: &HashMap<TensorId, Vec<usize>>
+    ) -> Result<HashMap<TensorId, InferredShape>, ShapeInferenceError>{
+        let input_axes = self.inputs.iter().flat_map(|slot|{
+            slot.tensor_meta.axes().iter().map(|axis| (&slot.tensor_meta.id, axis.id(), axis.size(), axis.scale()))
+        });
+        let output_axes = self.outputs.iter().flat_map(|slot|{
+            slot.tensor_meta.axes().iter().map(|axis| (&slot.tensor_meta.id, axis.id(), axis.size(), axis.scale()))
+        });
+        let axes = input_axes.chain(output_axes)
+            .map(|(tensor_id, axis_id, size, scale)|{
+                let qual_id = QualifiedAxisId{tensor_id: tensor_id.clone(), axis_id};
+                (qual_id, AxisExtentDescr{size, scale: f32::from(scale) as f64})
+            })
+            .collect();
+        let mut resolver = ExtentResolver::new(axes);
+
+        let mut input_qual_ids = vec![];
+        for slot in self.inputs.iter(){
+            let tensor_id = &slot.tensor_meta.id;
+            let shape = input_shapes.get(tensor_id).ok_or_else(|| ShapeInferenceError::MissingInputShape(tensor_id.clone()))?;
+            let axes = slot.tensor_meta.axes();
+            if shape.len() != axes.len(){
+                return Err(ShapeInferenceError::MismatchedNumDimensions{
+                    tensor_id: tensor_id.clone(), expected: axes.len(), found: shape.len()
+                })
+            }
+            for (axis, extent) in axes.iter().zip(shape){
+                let qual_id = QualifiedAxisId{tensor_id: tensor_id.clone(), axis_id: axis.id()};
+                resolver.set_extent(qual_id.clone(), *extent)?;
+                input_qual_ids.push(qual_id);
+            }
+        }
+        for qual_id in &input_qual_ids{
+            resolver.validate(qual_id)?;
+        }
+
+        self.outputs.iter()
+            .map(|slot|{
+                let tensor_id = &slot.tensor_meta.id;
+                let mut inferred = InferredShape{shape: vec![], halo: vec![]};
+                for axis in slot.tensor_meta.axes().iter(){
+                    let qual_id = QualifiedAxisId{tensor_id: tensor_id.clone(), axis_id: axis.id()};
+                    inferred.shape.push(resolver.resolve(&qual_id)?);
+                    inferred.halo.push(axis.halo().map(|halo| u64::from(halo) as usize).unwrap_or(0));
+                }
+                Ok((tensor_id.clone(), inferred))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_infer_output_shapes(){
+    let input_meta = serde_json::from_value(serde_json::json!({
+        "id": "raw",
+        "axes": [
+            {"type": "batch"},
+            {"type": "channel", "channel_names": ["c0"]},
+            {"type": "space", "id": "y", "size": {"min": 64, "step": 16}, "scale": 2.0},
+            {"type": "space", "id": "x", "size": {"min": 64, "step": 16}},
+        ],
+    })).unwrap();
+    let output_meta = serde_json::from_value(serde_json::json!({
+        "id": "out",
+        "axes": [
+            {"type": "batch"},
+            {"type": "channel", "channel_names": ["fg", "bg"]},
+            {"type": "space", "id": "y", "size": {"tensor_id": "raw", "axis_id": "y"}, "halo": 8},
+            {"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x", "offset": 16}, "scale": 0.5},
+        ],
+    })).unwrap();
+    let interface = ModelInterface::try_build(
+        vec![InputSlot{tensor_meta: input_meta, test_tensor: Arc::new(NpyArray::from(ndarray::ArrayD::<f32>::zeros(vec![1, 1, 64, 64])))}],
+        vec![OutputSlot{tensor_meta: output_meta, test_tensor: Arc::new(NpyArray::from(ndarray::ArrayD::<f32>::zeros(vec![1, 2, 128, 144])))}],
+    ).unwrap();
+
+    let raw = TensorId::try_from("raw").unwrap();
+    let shapes = interface.infer_output_shapes(&HashMap::from([(raw.clone(), vec![3, 1, 512, 80])])).unwrap();
+    let out = &shapes[&TensorId::try_from("out").unwrap()];
+    assert_eq!(out.shape, vec![3, 2, 1024, 176]);
+    assert_eq!(out.cropped(), vec![3, 2, 1008, 176]);
+
+    let bad_shape = interface.infer_output_shapes(&HashMap::from([(raw, vec![1, 1, 100, 80])]));
+    assert!(matches!(bad_shape, Err(ShapeInferenceError::IncompatibleExtent{..})));
+}
+
+#[test]
+fn test_validate_tensor_data(){
+    let tensor_id = TensorId::try_from("labels").unwrap();
+    let labels: TensorDataDescrs = serde_json::from_value(serde_json::json!(
+        {"type": "uint8", "values": ["background", "cell", "boundary"]}
+    )).unwrap();
+    let ok_labels = NpyArray::from(ndarray::ArrayD::<u8>::from_shape_vec(vec![1, 4], vec![0, 1, 2, 1]).unwrap());
+    validate_tensor_data(&tensor_id, &labels, None, &ok_labels).unwrap();
+    let bad_labels = NpyArray::from(ndarray::ArrayD::<u8>::from_shape_vec(vec![1, 4], vec![0, 1, 3, 1]).unwrap());
+    assert!(matches!(
+        validate_tensor_data(&tensor_id, &labels, None, &bad_labels),
+        Err(TensorValidationError::DisallowedValue{value, ..}) if value == 3.0
+    ));
+    let float_labels = NpyArray::from(ndarray::ArrayD::<f32>::zeros(vec![1, 4]));
+    assert!(matches!(
+        validate_tensor_data(&tensor_id, &labels, None, &float_labels),
+        Err(TensorValidationError::DataTypeMismatch{..})
+    ));
+
+    // one range per channel, along dimension 1
+    let per_channel: TensorDataDescrs = serde_json::from_value(serde_json::json!([
+        {"type": "float32", "range": [0.0, 1.0]},
+        {"type": "float32", "range": [-1.0, 0.0]},
+    ])).unwrap();
+    let values = NpyArray::from(ndarray::ArrayD::<f32>::from_shape_vec(vec![1, 2, 2], vec![0.5, 1.0, -0.5, -1.0]).unwrap());
+    validate_tensor_data(&tensor_id, &per_channel, Some(1), &values).unwrap();
+    assert!(validate_tensor_data(&tensor_id, &per_channel, Some(2), &values).is_err());
 }
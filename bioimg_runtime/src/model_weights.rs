@@ -41,6 +41,22 @@ impl ModelWeights{
     pub fn torchscript(&self) -> Option<&TorchscriptWeights>{
         self.torchscript.as_ref()
     }
+    /// Every file that gets written into the package when these weights are dumped
+    pub fn file_sources(&self) -> Vec<&FileSource>{
+        let mut sources = vec![];
+        sources.extend(self.keras_hdf5.as_ref().map(|w| &w.weights.source));
+        sources.extend(self.onnx.as_ref().map(|w| &w.weights.source));
+        if let Some(pytorch_state_dict) = &self.pytorch_state_dict{
+            sources.push(&pytorch_state_dict.weights.source);
+            if let PytorchArch::FromFile{file_source, ..} = &pytorch_state_dict.architecture{
+                sources.push(file_source);
+            }
+        }
+        sources.extend(self.tensorflow_js.as_ref().map(|w| &w.weights.source));
+        sources.extend(self.tensorflow_saved_model_bundle.as_ref().map(|w| &w.weights.source));
+        sources.extend(self.torchscript.as_ref().map(|w| &w.weights.source));
+        sources
+    }
 }
 
 
@@ -133,6 +149,7 @@ impl ModelWeights{
 pub struct WeightsBase{
     pub source: FileSource,
     pub authors: Option<Vec<rdf::Author2>>,
+    pub comment: Option<String>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -149,12 +166,15 @@ impl WeightsBase{
     fn rdf_dump(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        stem: &str,
     ) -> Result<modelrdf::WeightsDescrBase, ModelPackingError> {
+        let rdf::FileDescription{source, sha256} = self.source.dump_as_named_file_description(zip_file, stem)?;
         Ok(modelrdf::WeightsDescrBase{
-            source: self.source.rdf_dump_as_file_reference(zip_file)?,
+            source,
             authors: self.authors.clone(),
             parent: None, //FIXME
-            sha256: None, //FIXME
+            sha256,
+            comment: self.comment.clone(),
         })
     }
 
@@ -164,7 +184,10 @@ impl WeightsBase{
     ) -> Result<Self, ModelWeightsLoadingError>{
         Ok(Self{
             authors: rdf_weights_base.authors,
-            source: FileSource::from_rdf_file_reference(archive, &rdf_weights_base.source)?
+            comment: rdf_weights_base.comment,
+            source: FileSource::from_rdf_file_reference_with_sha256(
+                archive, &rdf_weights_base.source, rdf_weights_base.sha256.as_ref()
+            )?
         })
     }
 }
@@ -178,7 +201,7 @@ impl KerasHdf5Weights{
     fn rdf_dump(
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::KerasHdf5WeightsDescr, ModelPackingError> {
-        let weights = self.weights.rdf_dump(zip_file)?;
+        let weights = self.weights.rdf_dump(zip_file, "keras_hdf5_weights")?;
         Ok(modelrdf::KerasHdf5WeightsDescr{
             base: weights,
             tensorflow_version: self.tensorflow_version.clone(),
@@ -206,7 +229,7 @@ impl OnnxWeights{
     fn rdf_dump(
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::OnnxWeightsDescr, ModelPackingError> {
-        let weights = self.weights.rdf_dump(zip_file)?;
+        let weights = self.weights.rdf_dump(zip_file, "onnx_weights")?;
         Ok(modelrdf::OnnxWeightsDescr{
             base: weights,
             opset_version: self.opset_version.clone(),
@@ -285,7 +308,7 @@ impl PytorchStateDictWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::PytorchStateDictWeightsDescr, ModelPackingError> {
         Ok(modelrdf::PytorchStateDictWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, "pytorch_state_dict_weights")?,
             architecture: self.architecture.rdf_dump(zip_file)?,
             pytorch_version: self.pytorch_version.clone(),
             dependencies: self.dependencies.as_ref().map(|env|{
@@ -323,7 +346,7 @@ impl TensorflowJsWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TensorflowJsWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TensorflowJsWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, "tensorflow_js_weights")?,
             tensorflow_version: self.tensorflow_version.clone(),
         })
     }
@@ -352,7 +375,7 @@ impl TensorflowSavedModelBundleWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TensorflowSavedModelBundleWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TensorflowSavedModelBundleWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, "tensorflow_saved_model_bundle_weights")?,
             tensorflow_version: self.tensorflow_version.clone(),
             dependencies: self.dependencies.as_ref().map(|env|{
                 env.rdf_dump(zip_file)
@@ -386,7 +409,7 @@ impl TorchscriptWeights {
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TorchscriptWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TorchscriptWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, "torchscript_weights")?,
             pytorch_version: self.pytorch_version.clone(),
         })
     }
@@ -1,6 +1,12 @@
+use bioimg_spec::rdf::model::DataType;
 use ndarray_npy::{ReadNpyError, WriteNpyExt, ReadNpyExt};
+
+use crate::padding::{self, PadMode};
+use ndarray::SliceInfoElem;
 use std::{
+    borrow::Cow,
     io::{Read, Seek},
+    ops::Range,
     fmt::Display,
     sync::Arc,
 };
@@ -14,8 +20,75 @@ impl Display for UnsupportedNumpyElementType {
     }
 }
 
+/// Conversion of npy elements to and from wider types, used when casting between element types.
+///
+/// Integer (and bool) elements go through `i128`, which represents all of them exactly; floats go through `f64`.
+pub trait NpyElement: Copy{
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+    /// The exact integer value of this element, or `None` for floating point elements
+    fn to_i128(self) -> Option<i128>;
+    fn from_i128(value: i128) -> Self;
+}
+
+impl NpyElement for bool{
+    fn to_f64(self) -> f64{
+        self as u8 as f64
+    }
+    fn from_f64(value: f64) -> Self{
+        value != 0.0
+    }
+    fn to_i128(self) -> Option<i128>{
+        Some(self as i128)
+    }
+    fn from_i128(value: i128) -> Self{
+        value != 0
+    }
+}
+
+macro_rules! impl_NpyElement_for_integer {( $($element_type:ident),+ ) => {
+    $(
+        impl NpyElement for $element_type{
+            fn to_f64(self) -> f64{
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self{
+                value as $element_type
+            }
+            fn to_i128(self) -> Option<i128>{
+                Some(self as i128)
+            }
+            fn from_i128(value: i128) -> Self{
+                value as $element_type
+            }
+        }
+    )+
+};}
+
+macro_rules! impl_NpyElement_for_float {( $($element_type:ident),+ ) => {
+    $(
+        impl NpyElement for $element_type{
+            fn to_f64(self) -> f64{
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self{
+                value as $element_type
+            }
+            fn to_i128(self) -> Option<i128>{
+                None
+            }
+            fn from_i128(value: i128) -> Self{
+                value as $element_type
+            }
+        }
+    )+
+};}
+
+impl_NpyElement_for_integer!(u8, i8, u16, i16, u32, i32, u64, i64);
+impl_NpyElement_for_float!(f32, f64);
+
 #[rustfmt::skip]
-macro_rules! impl_NpyArray_try_read {( $($element_type:ident),+ ) => { paste::paste! {
+macro_rules! impl_NpyArray_try_read {( $($element_type:ident => $data_type:ident),+ ) => { paste::paste! {
     #[derive(Clone)]
     pub enum NpyArray {$(
         [<Array $element_type:upper>](ndarray::ArrayD<$element_type>),
@@ -50,9 +123,158 @@ macro_rules! impl_NpyArray_try_read {( $($element_type:ident),+ ) => { paste::pa
                 Self::[<Array $element_type:upper>](arr) => arr.shape(),
             )*}
         }
+
+        pub fn dtype(&self) -> DataType {
+            match self {$(
+                Self::[<Array $element_type:upper>](_) => DataType::$data_type,
+            )*}
+        }
+
+        pub fn to_f64(&self) -> ndarray::ArrayD<f64> {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => arr.mapv(NpyElement::to_f64),
+            )*}
+        }
+
+        pub fn to_f32(&self) -> ndarray::ArrayD<f32> {
+            match self {
+                Self::ArrayF32(arr) => arr.clone(),
+                other => other.to_f64().mapv(|v| v as f32),
+            }
+        }
+
+        pub fn from_f64(arr: &ndarray::ArrayD<f64>, dtype: DataType) -> Self {
+            match dtype {$(
+                DataType::$data_type => Self::[<Array $element_type:upper>](arr.mapv($element_type::from_f64)),
+            )*}
+        }
+
+        /// Reorders the dimensions of this array so that dimension `i` of the result is dimension `axes[i]` of `self`
+        pub fn permuted_axes(&self, axes: &[usize]) -> Self {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::[<Array $element_type:upper>](
+                    arr.view().permuted_axes(ndarray::IxDyn(axes)).as_standard_layout().into_owned()
+                ),
+            )*}
+        }
+
+        /// Inserts a new dimension of extent 1 at `axis`
+        pub fn insert_axis(&self, axis: usize) -> Self {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::[<Array $element_type:upper>](
+                    arr.clone().insert_axis(ndarray::Axis(axis))
+                ),
+            )*}
+        }
+
+        /// Removes dimension `axis`, keeping only its first element
+        pub fn remove_axis(&self, axis: usize) -> Self {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::[<Array $element_type:upper>](
+                    arr.index_axis(ndarray::Axis(axis), 0).to_owned()
+                ),
+            )*}
+        }
+
+        /// An array of `shape` filled with zeros (or `false`)
+        pub fn zeros(shape: &[usize], dtype: DataType) -> Self {
+            match dtype {$(
+                DataType::$data_type => Self::[<Array $element_type:upper>](
+                    ndarray::ArrayD::from_elem(ndarray::IxDyn(shape), $element_type::from_i128(0))
+                ),
+            )*}
+        }
+
+        /// Overwrites the region of this array selected by `slices` with `values`, cast to the element type of this array
+        pub fn assign_slice(&mut self, slices: &[SliceInfoElem], values: &NpyArray) {
+            let values = if values.dtype() == self.dtype() { Cow::Borrowed(values) } else { Cow::Owned(values.cast(self.dtype())) };
+            match (self, values.as_ref()) {
+                $(
+                    (Self::[<Array $element_type:upper>](arr), Self::[<Array $element_type:upper>](values)) => {
+                        arr.slice_mut(slices).assign(values)
+                    },
+                )*
+                _ => unreachable!("values were cast to the element type of the array"),
+            }
+        }
+
+        /// Copies the region of this array selected by `slices`
+        pub fn slice(&self, slices: &[SliceInfoElem]) -> Self {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::[<Array $element_type:upper>](arr.slice(slices).to_owned()),
+            )*}
+        }
+
+        /// Copies `region` of this array, making up values with `mode` where the region extends beyond it
+        pub fn padded_region(&self, region: &[Range<isize>], mode: PadMode) -> Self {
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::[<Array $element_type:upper>](
+                    padding::extract_region(arr, region, mode)
+                ),
+            )*}
+        }
+
+        /// Converts the elements of this array into `dtype`, with the same semantics as a rust `as` cast
+        pub fn cast(&self, dtype: DataType) -> Self {
+            if self.dtype() == dtype{
+                return self.clone()
+            }
+            match self {$(
+                Self::[<Array $element_type:upper>](arr) => Self::cast_elements(arr, dtype),
+            )*}
+        }
+
+        fn cast_elements<T: NpyElement>(arr: &ndarray::ArrayD<T>, dtype: DataType) -> Self {
+            match dtype {$(
+                DataType::$data_type => Self::[<Array $element_type:upper>](arr.mapv(|value| match value.to_i128(){
+                    Some(integer) => $element_type::from_i128(integer),
+                    None => $element_type::from_f64(value.to_f64()),
+                })),
+            )*}
+        }
     }
+
+    $(
+        impl From<ndarray::ArrayD<$element_type>> for NpyArray{
+            fn from(value: ndarray::ArrayD<$element_type>) -> Self {
+                Self::[<Array $element_type:upper>](value)
+            }
+        }
+    )+
 }};}
 
-impl_NpyArray_try_read!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_NpyArray_try_read!(
+    bool => Bool,
+    u8 => Uint8,
+    i8 => Int8,
+    u16 => Uint16,
+    i16 => Int16,
+    u32 => Uint32,
+    i32 => Int32,
+    u64 => Uint64,
+    i64 => Int64,
+    f32 => Float32,
+    f64 => Float64
+);
 
 pub type ArcNpyArray = Arc<NpyArray>;
+
+#[test]
+fn test_cast_integers_exactly(){
+    let big = (1i64 << 53) + 1;
+    let arr = NpyArray::from(ndarray::arr1(&[big, -1]).into_dyn());
+
+    let NpyArray::ArrayU64(unsigned) = arr.cast(DataType::Uint64) else { panic!("Expected a u64 array") };
+    assert_eq!(unsigned.as_slice().unwrap(), &[big as u64, u64::MAX]);
+    let NpyArray::ArrayI64(round_trip) = NpyArray::ArrayU64(unsigned).cast(DataType::Int64) else { panic!("Expected an i64 array") };
+    assert_eq!(round_trip.as_slice().unwrap(), &[big, -1]);
+
+    let NpyArray::ArrayU8(truncated) = arr.cast(DataType::Uint8) else { panic!("Expected a u8 array") };
+    assert_eq!(truncated.as_slice().unwrap(), &[1, 255]);
+    let NpyArray::ArrayBOOL(flags) = arr.cast(DataType::Bool) else { panic!("Expected a bool array") };
+    assert_eq!(flags.as_slice().unwrap(), &[true, true]);
+
+    let floats = NpyArray::from(ndarray::arr1(&[1.7f32, -2.5]).into_dyn());
+    let NpyArray::ArrayI8(ints) = floats.cast(DataType::Int8) else { panic!("Expected an i8 array") };
+    assert_eq!(ints.as_slice().unwrap(), &[1, -2]);
+}
@@ -0,0 +1,258 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bioimg_spec::rdf::model::TensorId;
+use tract_onnx::prelude::{
+    DatumType, Framework, InferenceFact, InferenceModel, InferenceModelExt, IntoTensor, Tensor, TractError, TValue,
+    TypedModel, TypedRunnableModel,
+};
+
+use crate::file_source::FileSourceError;
+use crate::model_weights::OnnxWeights;
+use crate::npy_array::{ArcNpyArray, NpyArray};
+use crate::ModelInterface;
+
+#[derive(thiserror::Error, Debug)]
+pub enum OnnxInferenceError{
+    #[error("Could not read onnx weights: {0}")]
+    FileSourceError(#[from] FileSourceError),
+    #[error("Onnx runtime error: {0:#}")]
+    Tract(TractError),
+    #[error("Model has {found} {kind}s but its description declares {expected}")]
+    MismatchedNumTensors{kind: &'static str, expected: usize, found: usize},
+    #[error("Onnx {kind} '{name}' does not match any {kind} tensor id in the model description")]
+    UnmatchedTensorName{kind: &'static str, name: String},
+    #[error("Missing data for input '{0}'")]
+    MissingInput(TensorId),
+    #[error("Unsupported output element type: {0:?}")]
+    UnsupportedDatumType(DatumType),
+}
+
+impl From<TractError> for OnnxInferenceError{
+    fn from(value: TractError) -> Self {
+        Self::Tract(value)
+    }
+}
+
+fn npy_to_tensor(data: &NpyArray) -> Tensor{
+    match data{
+        NpyArray::ArrayBOOL(arr) => arr.clone().into(),
+        NpyArray::ArrayU8(arr) => arr.clone().into(),
+        NpyArray::ArrayI8(arr) => arr.clone().into(),
+        NpyArray::ArrayU16(arr) => arr.clone().into(),
+        NpyArray::ArrayI16(arr) => arr.clone().into(),
+        NpyArray::ArrayU32(arr) => arr.clone().into(),
+        NpyArray::ArrayI32(arr) => arr.clone().into(),
+        NpyArray::ArrayU64(arr) => arr.clone().into(),
+        NpyArray::ArrayI64(arr) => arr.clone().into(),
+        NpyArray::ArrayF32(arr) => arr.clone().into(),
+        NpyArray::ArrayF64(arr) => arr.clone().into(),
+    }
+}
+
+fn tensor_to_npy(tensor: Tensor) -> Result<NpyArray, OnnxInferenceError>{
+    Ok(match tensor.datum_type(){
+        DatumType::Bool => tensor.into_array::<bool>()?.into(),
+        DatumType::U8 => tensor.into_array::<u8>()?.into(),
+        DatumType::I8 => tensor.into_array::<i8>()?.into(),
+        DatumType::U16 => tensor.into_array::<u16>()?.into(),
+        DatumType::I16 => tensor.into_array::<i16>()?.into(),
+        DatumType::U32 => tensor.into_array::<u32>()?.into(),
+        DatumType::I32 => tensor.into_array::<i32>()?.into(),
+        DatumType::U64 => tensor.into_array::<u64>()?.into(),
+        DatumType::I64 => tensor.into_array::<i64>()?.into(),
+        DatumType::F32 => tensor.into_array::<f32>()?.into(),
+        DatumType::F64 => tensor.into_array::<f64>()?.into(),
+        other => return Err(OnnxInferenceError::UnsupportedDatumType(other)),
+    })
+}
+
+/// Data types and shapes of the inputs a plan was optimized for
+type PlanKey = Vec<(DatumType, Vec<usize>)>;
+
+/// Pure-rust, CPU-only inference of onnx weights.
+///
+/// Model inputs and outputs are matched to the tensor ids of the model description by name.
+/// The model is optimized once for every distinct combination of input shapes it is run with, so running
+/// it repeatedly over same-sized tiles only pays for that once.
+pub struct OnnxModel{
+    model: InferenceModel,
+    input_ids: Vec<TensorId>,
+    output_ids: Vec<TensorId>,
+    plans: Mutex<HashMap<PlanKey, Arc<TypedRunnableModel<TypedModel>>>>,
+}
+
+impl OnnxModel{
+    pub fn try_load<DATA: Borrow<NpyArray>>(
+        weights: &OnnxWeights, interface: &ModelInterface<DATA>
+    ) -> Result<Self, OnnxInferenceError>{
+        let mut raw_weights = vec![];
+        weights.weights.source.read_to_end(&mut raw_weights)?;
+        let model = tract_onnx::onnx().model_for_read(&mut raw_weights.as_slice())?;
+
+        let declared_inputs: Vec<TensorId> = interface.inputs().iter().map(|slot| slot.tensor_meta.id.clone()).collect();
+        let declared_outputs: Vec<TensorId> = interface.outputs().iter().map(|slot| slot.tensor_meta.id.clone()).collect();
+        let onnx_input_names: Vec<String> = model.input_outlets()?.iter()
+            .map(|outlet| model.node(outlet.node).name.clone())
+            .collect();
+        let onnx_output_names: Vec<String> = model.output_outlets()?.iter()
+            .map(|outlet| model.outlet_label(*outlet).unwrap_or(&model.node(outlet.node).name).to_owned())
+            .collect();
+
+        Ok(Self{
+            input_ids: Self::match_names("input", &declared_inputs, onnx_input_names)?,
+            output_ids: Self::match_names("output", &declared_outputs, onnx_output_names)?,
+            model,
+            plans: Default::default(),
+        })
+    }
+
+    fn match_names(
+        kind: &'static str, declared: &[TensorId], onnx_names: Vec<String>
+    ) -> Result<Vec<TensorId>, OnnxInferenceError>{
+        if declared.len() != onnx_names.len(){
+            return Err(OnnxInferenceError::MismatchedNumTensors { kind, expected: declared.len(), found: onnx_names.len() })
+        }
+        onnx_names.into_iter()
+            .map(|name| match declared.iter().find(|tensor_id| name.as_str() == &***tensor_id){
+                Some(tensor_id) => Ok(tensor_id.clone()),
+                None => Err(OnnxInferenceError::UnmatchedTensorName{kind, name}),
+            })
+            .collect()
+    }
+
+    /// The runnable plan for inputs like `input_tensors`, optimizing the model for them if needed
+    fn plan_for(&self, input_tensors: &[Tensor]) -> Result<Arc<TypedRunnableModel<TypedModel>>, OnnxInferenceError>{
+        let key: PlanKey = input_tensors.iter().map(|tensor| (tensor.datum_type(), tensor.shape().to_vec())).collect();
+        if let Some(plan) = self.plans.lock().unwrap().get(&key){
+            return Ok(plan.clone())
+        }
+        let mut model = self.model.clone();
+        for (input_idx, tensor) in input_tensors.iter().enumerate(){
+            model.set_input_fact(input_idx, InferenceFact::dt_shape_from_tensor(tensor))?;
+        }
+        // output facts declared with symbolic dimensions would otherwise clash with the concrete input shapes
+        for output_idx in 0..self.output_ids.len(){
+            model.set_output_fact(output_idx, InferenceFact::default())?;
+        }
+        let plan = Arc::new(model.into_optimized()?.into_runnable()?);
+        self.plans.lock().unwrap().insert(key, plan.clone());
+        Ok(plan)
+    }
+
+    /// Runs the model on already preprocessed `inputs`, returning the raw outputs keyed by their tensor ids
+    pub fn run(&self, inputs: &HashMap<TensorId, ArcNpyArray>) -> Result<HashMap<TensorId, ArcNpyArray>, OnnxInferenceError>{
+        let input_tensors: Vec<Tensor> = self.input_ids.iter()
+            .map(|tensor_id|{
+                let data = inputs.get(tensor_id).ok_or_else(|| OnnxInferenceError::MissingInput(tensor_id.clone()))?;
+                Ok(npy_to_tensor(data))
+            })
+            .collect::<Result<_, OnnxInferenceError>>()?;
+
+        let plan = self.plan_for(&input_tensors)?;
+        let outputs = plan.run(input_tensors.into_iter().map(TValue::from).collect())?;
+
+        self.output_ids.iter().zip(outputs)
+            .map(|(tensor_id, value)|{
+                let tensor = value.into_tensor();
+                Ok((tensor_id.clone(), Arc::new(tensor_to_npy(tensor)?)))
+            })
+            .collect()
+    }
+}
+
+/// A minimal onnx model computing `out = Relu(raw)` over a 1D float32 tensor of any length
+#[cfg(test)]
+fn relu_onnx_bytes() -> Vec<u8>{
+    fn varint(mut value: u64, out: &mut Vec<u8>){
+        while value >= 0x80{
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+    fn int_field(number: u64, value: u64) -> Vec<u8>{
+        let mut out = vec![];
+        varint(number << 3, &mut out);
+        varint(value, &mut out);
+        out
+    }
+    fn bytes_field(number: u64, payload: &[u8]) -> Vec<u8>{
+        let mut out = vec![];
+        varint((number << 3) | 2, &mut out);
+        varint(payload.len() as u64, &mut out);
+        out.extend_from_slice(payload);
+        out
+    }
+    let value_info = |name: &str|{
+        let shape = bytes_field(1, &bytes_field(2, b"n"));
+        let tensor_type = [int_field(1, 1), bytes_field(2, &shape)].concat();
+        [bytes_field(1, name.as_bytes()), bytes_field(2, &bytes_field(1, &tensor_type))].concat()
+    };
+    let node = [bytes_field(1, b"raw"), bytes_field(2, b"out"), bytes_field(4, b"Relu")].concat();
+    let graph = [
+        bytes_field(1, &node), bytes_field(2, b"relu"), bytes_field(11, &value_info("raw")), bytes_field(12, &value_info("out")),
+    ].concat();
+    [int_field(1, 7), bytes_field(7, &graph), bytes_field(8, &int_field(2, 13))].concat()
+}
+
+#[test]
+fn test_run_onnx_model(){
+    use crate::model_interface::{InputSlot, OutputSlot};
+    use crate::model_weights::WeightsBase;
+    use crate::FileSource;
+
+    let dir = tempfile::tempdir().unwrap();
+    let weights_path = dir.path().join("relu.onnx");
+    std::fs::write(&weights_path, relu_onnx_bytes()).unwrap();
+    let weights = OnnxWeights{
+        weights: WeightsBase{source: FileSource::LocalFile{path: Arc::from(weights_path.as_path())}, authors: None, comment: None},
+        opset_version: 13.try_into().unwrap(),
+    };
+    let interface = ModelInterface::try_build(
+        vec![InputSlot{
+            tensor_meta: serde_json::from_value(serde_json::json!({
+                "id": "raw", "axes": [{"type": "space", "id": "x", "size": {"min": 1, "step": 1}}],
+            })).unwrap(),
+            test_tensor: Arc::new(NpyArray::from(ndarray::array![-1.0f32, 0.0, 2.0].into_dyn())),
+        }],
+        vec![OutputSlot{
+            tensor_meta: serde_json::from_value(serde_json::json!({
+                "id": "out", "axes": [{"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x"}}],
+            })).unwrap(),
+            test_tensor: Arc::new(NpyArray::from(ndarray::array![0.0f32, 0.0, 2.0].into_dyn())),
+        }],
+    ).unwrap();
+    let model = OnnxModel::try_load(&weights, &interface).unwrap();
+
+    let raw = TensorId::try_from("raw").unwrap();
+    let out = TensorId::try_from("out").unwrap();
+    let run = |values: Vec<f32>|{
+        let input = Arc::new(NpyArray::from(ndarray::Array1::from(values).into_dyn()));
+        model.run(&HashMap::from([(raw.clone(), input)])).unwrap()[&out].to_f32().into_raw_vec()
+    };
+    assert_eq!(run(vec![-1.0, 0.0, 2.0]), vec![0.0, 0.0, 2.0]);
+    assert_eq!(run(vec![3.0, -3.0, 1.0]), vec![3.0, 0.0, 1.0]);
+    assert_eq!(run(vec![-5.0, 5.0]), vec![0.0, 5.0]);
+    assert_eq!(model.plans.lock().unwrap().len(), 2);
+
+    assert!(matches!(model.run(&HashMap::new()), Err(OnnxInferenceError::MissingInput(_))));
+}
+
+#[test]
+fn test_match_names(){
+    let declared = vec![TensorId::try_from("raw").unwrap(), TensorId::try_from("mask").unwrap()];
+
+    let matched = OnnxModel::match_names("input", &declared, vec!["mask".to_owned(), "raw".to_owned()]).unwrap();
+    assert_eq!(matched, vec![declared[1].clone(), declared[0].clone()]);
+
+    assert!(matches!(
+        OnnxModel::match_names("input", &declared, vec!["raw".to_owned(), "input.1".to_owned()]),
+        Err(OnnxInferenceError::UnmatchedTensorName{name, ..}) if name == "input.1"
+    ));
+    assert!(matches!(
+        OnnxModel::match_names("input", &declared, vec!["raw".to_owned()]),
+        Err(OnnxInferenceError::MismatchedNumTensors{expected: 2, found: 1, ..})
+    ));
+}
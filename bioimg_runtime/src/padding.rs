@@ -0,0 +1,278 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use bioimg_spec::rdf::model::{AnyAxisSize, AxisId, AxisSizeReference, ParameterizedAxisSize, TensorId};
+use ndarray::{ArrayD, Axis, IxDyn, SliceInfoElem};
+
+use crate::model_interface::InputSlot;
+use crate::npy_array::{NpyArray, NpyElement};
+use crate::ModelInterface;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PaddingError{
+    #[error("No input with id '{0}'")]
+    NoSuchInput(TensorId),
+    #[error("Expected {expected} dimensions, found {found}")]
+    MismatchedNumDimensions{expected: usize, found: usize},
+    #[error("Can't reflect-pad dimension #{dim_index}, since it has extent {extent}")]
+    CantReflect{dim_index: usize, extent: usize},
+    #[error("Can't crop {cropping:?} from dimension #{dim_index} with extent {extent}")]
+    CropTooLarge{dim_index: usize, extent: usize, cropping: (usize, usize)},
+    #[error("Padding of {padding:?} maps onto fractional coordinates on axis '{axis_id}' of output '{tensor_id}'")]
+    FractionalOutputCrop{tensor_id: TensorId, axis_id: AxisId, padding: (usize, usize)},
+}
+
+/// How values are made up for the region outside of an array
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadMode{
+    Constant(f64),
+    /// Repeats the outermost value
+    Edge,
+    /// Mirrors the array around its outermost value, without repeating it (numpy's "reflect")
+    Reflect,
+}
+
+impl PadMode{
+    /// Index into an axis of extent `extent` that should be read for position `idx`, or `None` for a constant
+    pub(crate) fn source_index(&self, idx: isize, extent: usize) -> Option<usize>{
+        let extent = extent as isize;
+        if (0..extent).contains(&idx){
+            return Some(idx as usize)
+        }
+        match self{
+            Self::Constant(_) => None,
+            Self::Edge => Some(idx.clamp(0, extent - 1) as usize),
+            Self::Reflect if extent < 2 => Some(0),
+            Self::Reflect => {
+                let period = 2 * (extent - 1);
+                let idx = idx.rem_euclid(period);
+                Some(if idx < extent { idx } else { period - idx } as usize)
+            },
+        }
+    }
+}
+
+/// Copies `region` of `arr`, making up values with `mode` where the region extends beyond it.
+///
+/// Only the part of `arr` that the region reads from is copied, and values keep their element type.
+pub(crate) fn extract_region<T: NpyElement>(arr: &ArrayD<T>, region: &[Range<isize>], mode: PadMode) -> ArrayD<T>{
+    let shape = arr.shape();
+    if let PadMode::Constant(value) = mode{
+        let region_shape: Vec<usize> = region.iter().map(|range| range.len()).collect();
+        let mut out = ArrayD::from_elem(IxDyn(&region_shape), T::from_f64(value));
+        let mut source_slices = vec![];
+        let mut dest_slices = vec![];
+        for (range, &extent) in region.iter().zip(shape){
+            let start = range.start.clamp(0, extent as isize);
+            let end = range.end.clamp(0, extent as isize);
+            if start >= end{
+                return out
+            }
+            source_slices.push(SliceInfoElem::from(start as usize..end as usize));
+            dest_slices.push(SliceInfoElem::from((start - range.start) as usize..(end - range.start) as usize));
+        }
+        out.slice_mut(dest_slices.as_slice()).assign(&arr.slice(source_slices.as_slice()));
+        return out
+    }
+
+    let source_indices: Vec<Vec<usize>> = region.iter().zip(shape)
+        .map(|(range, &extent)| range.clone().filter_map(|idx| mode.source_index(idx, extent)).collect())
+        .collect();
+    let bounds: Vec<Range<usize>> = source_indices.iter()
+        .map(|indices| match (indices.iter().min(), indices.iter().max()){
+            (Some(min), Some(max)) => *min..max + 1,
+            _ => 0..0,
+        })
+        .collect();
+    let bounding_slices: Vec<SliceInfoElem> = bounds.iter().cloned().map(SliceInfoElem::from).collect();
+    let mut out = arr.slice(bounding_slices.as_slice()).to_owned();
+    for (axis, (indices, bounds)) in source_indices.iter().zip(&bounds).enumerate(){
+        let is_identity = indices.len() == bounds.len() && indices.first() == Some(&bounds.start) && indices.windows(2).all(|w| w[1] == w[0] + 1);
+        if !is_identity{
+            let shifted: Vec<usize> = indices.iter().map(|idx| idx - bounds.start).collect();
+            out = out.select(Axis(axis), &shifted);
+        }
+    }
+    out
+}
+
+/// The extents closest to `extent` that an input axis accepts
+#[derive(Clone, Debug)]
+pub struct AxisFit{
+    pub axis_id: AxisId,
+    pub extent: usize,
+    /// Largest valid extent not greater than `extent`
+    pub below: Option<usize>,
+    /// Smallest valid extent not smaller than `extent`
+    pub above: Option<usize>,
+}
+
+impl AxisFit{
+    pub fn is_valid(&self) -> bool{
+        self.below == Some(self.extent)
+    }
+
+    /// Amount to pad before and after so that the extent becomes [Self::above]
+    pub fn padding(&self) -> Option<(usize, usize)>{
+        let total = self.above? - self.extent;
+        Some((total / 2, total - total / 2))
+    }
+
+    /// Amount to crop before and after so that the extent becomes [Self::below]
+    pub fn cropping(&self) -> Option<(usize, usize)>{
+        let total = self.extent - self.below?;
+        Some((total / 2, total - total / 2))
+    }
+}
+
+/// Finds the valid extents around `shape` for each axis of `slot`.
+///
+/// Batch axes and axes whose size references another axis accept their current extent.
+pub fn fit_shape<DATA: Borrow<NpyArray>>(slot: &InputSlot<DATA>, shape: &[usize]) -> Result<Vec<AxisFit>, PaddingError>{
+    let axes = slot.tensor_meta.axes();
+    if axes.len() != shape.len(){
+        return Err(PaddingError::MismatchedNumDimensions{expected: axes.len(), found: shape.len()})
+    }
+    Ok(axes.iter().zip(shape)
+        .map(|(axis, &extent)|{
+            let (below, above) = match axis.size(){
+                None | Some(AnyAxisSize::Reference(_)) => (Some(extent), Some(extent)),
+                Some(AnyAxisSize::Fixed(fixed)) => {
+                    let fixed = usize::from(fixed);
+                    (Some(fixed).filter(|fixed| *fixed <= extent), Some(fixed).filter(|fixed| *fixed >= extent))
+                },
+                Some(AnyAxisSize::Parameterized(ParameterizedAxisSize{min, step})) => {
+                    let (min, step) = (usize::from(min), usize::from(step));
+                    if extent <= min{
+                        (Some(min).filter(|min| *min == extent), Some(min))
+                    } else {
+                        let below = min + (extent - min) / step * step;
+                        (Some(below), Some(below + if below == extent { 0 } else { step }))
+                    }
+                },
+            };
+            AxisFit{axis_id: axis.id(), extent, below, above}
+        })
+        .collect())
+}
+
+/// Pads each dimension of `data` with `padding[dim]` elements (before, after)
+pub fn pad(data: &NpyArray, padding: &[(usize, usize)], mode: PadMode) -> Result<NpyArray, PaddingError>{
+    let shape = data.shape();
+    if padding.len() != shape.len(){
+        return Err(PaddingError::MismatchedNumDimensions{expected: shape.len(), found: padding.len()})
+    }
+    if mode == PadMode::Reflect{
+        let unreflectable = shape.iter().zip(padding).position(|(extent, (before, after))| *extent < 2 && before + after > 0);
+        if let Some(dim_index) = unreflectable{
+            return Err(PaddingError::CantReflect{dim_index, extent: shape[dim_index]})
+        }
+    }
+    let region: Vec<Range<isize>> = shape.iter().zip(padding)
+        .map(|(&extent, &(before, after))| -(before as isize)..(extent + after) as isize)
+        .collect();
+    Ok(data.padded_region(&region, mode))
+}
+
+/// Removes `cropping[dim]` elements (before, after) from each dimension of `data`
+pub fn crop(data: &NpyArray, cropping: &[(usize, usize)]) -> Result<NpyArray, PaddingError>{
+    let shape = data.shape();
+    if cropping.len() != shape.len(){
+        return Err(PaddingError::MismatchedNumDimensions{expected: shape.len(), found: cropping.len()})
+    }
+    let slices = shape.iter().zip(cropping).enumerate()
+        .map(|(dim_index, (&extent, &(before, after)))|{
+            if before + after > extent{
+                return Err(PaddingError::CropTooLarge{dim_index, extent, cropping: (before, after)})
+            }
+            Ok(SliceInfoElem::from(before..extent - after))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(data.slice(&slices))
+}
+
+/// Cropping that undoes `padding` of input `input_id` on every output of `interface`.
+///
+/// Output axes whose size references a padded input axis get that padding scaled by the ratio of the axis scales;
+/// all other output axes are left alone.
+pub fn output_cropping<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>, input_id: &TensorId, padding: &[(usize, usize)]
+) -> Result<HashMap<TensorId, Vec<(usize, usize)>>, PaddingError>{
+    let Some(input_slot) = interface.inputs().iter().find(|slot| slot.tensor_meta.id == *input_id) else {
+        return Err(PaddingError::NoSuchInput(input_id.clone()))
+    };
+    let input_axes = input_slot.tensor_meta.axes();
+    if input_axes.len() != padding.len(){
+        return Err(PaddingError::MismatchedNumDimensions{expected: input_axes.len(), found: padding.len()})
+    }
+    interface.outputs().iter()
+        .map(|slot|{
+            let tensor_id = &slot.tensor_meta.id;
+            let cropping = slot.tensor_meta.axes().iter()
+                .map(|axis|{
+                    let Some(AnyAxisSize::Reference(AxisSizeReference{qualified_axis_id, ..})) = axis.size() else {
+                        return Ok((0, 0))
+                    };
+                    if qualified_axis_id.tensor_id != *input_id{
+                        return Ok((0, 0))
+                    }
+                    let Some(input_axis) = input_axes.iter().position(|inp_axis| inp_axis.id() == qualified_axis_id.axis_id) else {
+                        return Ok((0, 0))
+                    };
+                    let (before, after) = padding[input_axis];
+                    let ratio = f32::from(input_axes[input_axis].scale()) as f64 / f32::from(axis.scale()) as f64;
+                    let scaled = |value: usize| {
+                        let scaled = value as f64 * ratio;
+                        ((scaled - scaled.round()).abs() < 1e-6).then_some(scaled.round() as usize)
+                    };
+                    match (scaled(before), scaled(after)){
+                        (Some(before), Some(after)) => Ok((before, after)),
+                        _ => Err(PaddingError::FractionalOutputCrop{
+                            tensor_id: tensor_id.clone(), axis_id: axis.id(), padding: (before, after)
+                        }),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((tensor_id.clone(), cropping))
+        })
+        .collect()
+}
+
+#[test]
+fn test_fit_pad_and_crop(){
+    let tensor_meta = serde_json::from_value(serde_json::json!({
+        "id": "raw",
+        "axes": [
+            {"type": "batch"},
+            {"type": "space", "id": "x", "size": {"min": 8, "step": 4}},
+        ],
+    })).unwrap();
+    let slot = InputSlot{tensor_meta, test_tensor: NpyArray::from(ndarray::ArrayD::<u8>::zeros(IxDyn(&[1, 8])))};
+    let fits = fit_shape(&slot, &[2, 5]).unwrap();
+    assert!(fits[0].is_valid());
+    assert_eq!((fits[1].below, fits[1].above), (None, Some(8)));
+    let fits = fit_shape(&slot, &[2, 14]).unwrap();
+    assert_eq!((fits[1].below, fits[1].above), (Some(12), Some(16)));
+    assert_eq!(fits[1].padding(), Some((1, 1)));
+    assert_eq!(fits[1].cropping(), Some((1, 1)));
+
+    let data = NpyArray::from(ndarray::array![[1u8, 2, 3]].into_dyn());
+    let padded = |mode| pad(&data, &[(0, 0), (2, 3)], mode).unwrap().to_f64().into_raw_vec();
+    assert_eq!(padded(PadMode::Reflect), vec![3.0, 2.0, 1.0, 2.0, 3.0, 2.0, 1.0, 2.0]);
+    assert_eq!(padded(PadMode::Edge), vec![1.0, 1.0, 1.0, 2.0, 3.0, 3.0, 3.0, 3.0]);
+    assert_eq!(padded(PadMode::Constant(7.0)), vec![7.0, 7.0, 1.0, 2.0, 3.0, 7.0, 7.0, 7.0]);
+
+    let padded = pad(&data, &[(0, 0), (2, 3)], PadMode::Reflect).unwrap();
+    let cropped = crop(&padded, &[(0, 0), (2, 3)]).unwrap();
+    assert_eq!(cropped.to_f64(), data.to_f64());
+    assert_eq!(cropped.dtype(), data.dtype());
+
+    // values that f64 can't represent exactly survive padding and cropping
+    let big = (1u64 << 53) + 1;
+    let data = NpyArray::from(ndarray::array![[big, big + 2], [1, 2]].into_dyn());
+    let NpyArray::ArrayU64(padded) = pad(&data, &[(1, 0), (1, 1)], PadMode::Edge).unwrap() else { panic!("Expected a u64 array") };
+    assert_eq!(padded, ndarray::array![[big, big, big + 2, big + 2], [big, big, big + 2, big + 2], [1, 1, 2, 2]].into_dyn());
+    let NpyArray::ArrayU64(cropped) = crop(&NpyArray::from(padded), &[(1, 0), (1, 1)]).unwrap() else { panic!("Expected a u64 array") };
+    assert_eq!(cropped, ndarray::array![[big, big + 2], [1, 2]].into_dyn());
+}
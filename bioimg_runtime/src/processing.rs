@@ -0,0 +1,474 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::axes::NonBatchAxisId;
+use bioimg_spec::rdf::model::input_tensor::InputTensorMetadata;
+use bioimg_spec::rdf::model::postprocessing::{PostprocessingDescr, ScaleMeanVarianceDescr};
+use bioimg_spec::rdf::model::preprocessing::{
+    BinarizeDescr, ClipDescr, EnsureDtype, FixedZmuv, PreprocessingEpsilon, ScaleLinearDescr, ScaleRangeDescr, Zmuv
+};
+use bioimg_spec::rdf::model::{AxisId, PreprocessingDescr, TensorId};
+use ndarray::{ArrayD, IxDyn, Zip};
+
+use crate::npy_array::{ArcNpyArray, NpyArray};
+use crate::ModelInterface;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProcessingError{
+    #[error("Tensor '{tensor_id}' has {found} dimensions but {expected} axes are described")]
+    MismatchedNumDimensions{tensor_id: TensorId, expected: usize, found: usize},
+    #[error("Axis '{axis_id}' does not exist in tensor '{tensor_id}'")]
+    AxisNotFound{tensor_id: TensorId, axis_id: AxisId},
+    #[error("Expected {expected} values along axis '{axis_id}', found {found}")]
+    MismatchedAxisValues{axis_id: AxisId, expected: usize, found: usize},
+    #[error("Could not resolve reference to tensor '{0}'")]
+    UnresolvedReference(TensorId),
+    #[error("Model has no tensor with id '{0}'")]
+    NoSuchTensor(TensorId),
+    #[error("Reference tensor '{reference}' has shape {reference_shape:?}, incompatible with {shape:?}")]
+    IncompatibleReferenceShape{reference: TensorId, reference_shape: Vec<usize>, shape: Vec<usize>},
+}
+
+/// A tensor whose dimensions are labeled by the axis ids of its description
+#[derive(Clone)]
+pub(crate) struct LabeledData{
+    pub tensor_id: TensorId,
+    pub axis_ids: Vec<AxisId>,
+    pub data: ArcNpyArray,
+}
+
+impl LabeledData{
+    pub fn new(tensor_id: TensorId, axis_ids: Vec<AxisId>, data: ArcNpyArray) -> Result<Self, ProcessingError>{
+        if axis_ids.len() != data.shape().len(){
+            return Err(ProcessingError::MismatchedNumDimensions {
+                tensor_id, expected: axis_ids.len(), found: data.shape().len()
+            })
+        }
+        Ok(Self{tensor_id, axis_ids, data})
+    }
+
+    fn axis_index(&self, axis_id: &AxisId) -> Result<usize, ProcessingError>{
+        self.axis_ids.iter().position(|ax| ax == axis_id).ok_or_else(|| ProcessingError::AxisNotFound{
+            tensor_id: self.tensor_id.clone(), axis_id: axis_id.clone()
+        })
+    }
+
+    /// Indices of the dimensions that should be reduced jointly. `None` means all axes
+    fn reduced_axes(&self, axes: Option<&[AxisId]>) -> Result<Vec<usize>, ProcessingError>{
+        match axes{
+            None => Ok((0..self.axis_ids.len()).collect()),
+            Some(axes) => axes.iter().map(|axis_id| self.axis_index(axis_id)).collect(),
+        }
+    }
+
+    /// Produces an array that can be broadcast over `self.data`, with `values` laid along `axis_id`
+    fn along_axis<T: Clone>(&self, axis_id: &NonBatchAxisId, values: &[T]) -> Result<ArrayD<T>, ProcessingError>{
+        let axis_id: &AxisId = axis_id.borrow();
+        let axis_index = self.axis_index(axis_id)?;
+        let extent = self.data.shape()[axis_index];
+        let values: Vec<T> = if values.len() == extent {
+            values.to_vec()
+        } else if values.len() == 1 {
+            vec![values[0].clone(); extent]
+        } else {
+            return Err(ProcessingError::MismatchedAxisValues { axis_id: axis_id.clone(), expected: extent, found: values.len() })
+        };
+        let mut param_shape = vec![1; self.axis_ids.len()];
+        param_shape[axis_index] = extent;
+        Ok(ArrayD::from_shape_vec(IxDyn(&param_shape), values).unwrap())
+    }
+
+    fn scalar<T>(&self, value: T) -> ArrayD<T>{
+        ArrayD::from_shape_vec(IxDyn(&vec![1; self.axis_ids.len()]), vec![value]).unwrap()
+    }
+}
+
+/// Computes `f` over each group of values that share the same coordinates on the non-reduced axes.
+///
+/// The output has the same number of dimensions as `arr`, with the reduced axes having extent 1,
+/// so that it can be broadcast back over `arr`.
+pub(crate) fn reduce_groups<T>(arr: &ArrayD<f32>, reduced_axes: &[usize], mut f: impl FnMut(&mut [f32]) -> T) -> ArrayD<T>{
+    let kept_axes: Vec<usize> = (0..arr.ndim()).filter(|ax| !reduced_axes.contains(ax)).collect();
+    let permutation: Vec<usize> = kept_axes.iter().chain(reduced_axes.iter()).copied().collect();
+    let num_groups: usize = kept_axes.iter().map(|ax| arr.shape()[*ax]).product();
+    let group_size: usize = reduced_axes.iter().map(|ax| arr.shape()[*ax]).product();
+
+    let mut values: Vec<f32> = arr.view().permuted_axes(IxDyn(&permutation)).iter().copied().collect();
+    let results: Vec<T> = if group_size == 0 {
+        (0..num_groups).map(|_| f(&mut [])).collect()
+    } else {
+        values.chunks_mut(group_size).map(f).collect()
+    };
+
+    let stats_shape: Vec<usize> = (0..arr.ndim())
+        .map(|ax| if reduced_axes.contains(&ax) { 1 } else { arr.shape()[ax] })
+        .collect();
+    ArrayD::from_shape_vec(IxDyn(&stats_shape), results).unwrap()
+}
+
+/// Population mean and standard deviation of `values`
+pub(crate) fn mean_and_std(values: &mut [f32]) -> (f32, f32){
+    let num_values = values.len() as f64;
+    let mean = values.iter().map(|v| *v as f64).sum::<f64>() / num_values;
+    let variance = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / num_values;
+    (mean as f32, variance.sqrt() as f32)
+}
+
+/// Percentiles (in the range [0, 100]) of `values` with linear interpolation, like numpy's default
+pub(crate) fn percentiles<const N: usize>(values: &mut [f32], percentiles: [f32; N]) -> [f32; N]{
+    if values.is_empty(){
+        return [f32::NAN; N]
+    }
+    values.sort_unstable_by(|a, b| a.total_cmp(b));
+    percentiles.map(|percentile|{
+        let position = (percentile as f64 / 100.0) * (values.len() - 1) as f64;
+        let lower = values[position.floor() as usize] as f64;
+        let upper = values[position.ceil() as usize] as f64;
+        (lower + (upper - lower) * position.fract()) as f32
+    })
+}
+
+pub(crate) fn binarize(tensor: &LabeledData, descr: &BinarizeDescr) -> Result<NpyArray, ProcessingError>{
+    let thresholds = match descr{
+        BinarizeDescr::Simple(descr) => tensor.scalar(descr.threshold),
+        BinarizeDescr::AlongAxis(descr) => tensor.along_axis(&descr.axis, &descr.threshold)?,
+    };
+    let data = tensor.data.to_f32();
+    Ok(Zip::from(&data).and_broadcast(&thresholds).map_collect(|value, threshold| value > threshold).into())
+}
+
+pub(crate) fn clip(tensor: &LabeledData, descr: &ClipDescr) -> NpyArray{
+    tensor.data.to_f32().mapv(|value| value.clamp(descr.min(), descr.max())).into()
+}
+
+pub(crate) fn ensure_dtype(tensor: &LabeledData, descr: &EnsureDtype) -> NpyArray{
+    tensor.data.cast(descr.dtype)
+}
+
+pub(crate) fn scale_linear(tensor: &LabeledData, descr: &ScaleLinearDescr) -> Result<NpyArray, ProcessingError>{
+    let gain_offsets = match descr{
+        ScaleLinearDescr::Simple(descr) => tensor.scalar((descr.gain, descr.offset)),
+        ScaleLinearDescr::AlongAxis(descr) => tensor.along_axis(&descr.axis, &descr.gain_offsets)?,
+    };
+    let mut data = tensor.data.to_f32();
+    Zip::from(&mut data).and_broadcast(&gain_offsets).for_each(|value, (gain, offset)|{
+        *value = *value * gain + offset;
+    });
+    Ok(data.into())
+}
+
+pub(crate) fn sigmoid(tensor: &LabeledData) -> NpyArray{
+    tensor.data.to_f32().mapv(|value| 1.0 / (1.0 + (-value).exp())).into()
+}
+
+pub(crate) fn fixed_zmuv(tensor: &LabeledData, descr: &FixedZmuv) -> Result<NpyArray, ProcessingError>{
+    let means_and_stds = match descr{
+        FixedZmuv::Simple(descr) => tensor.scalar((descr.mean, f32::from(descr.std))),
+        FixedZmuv::AlongAxis(descr) => {
+            let values: Vec<(f32, f32)> = descr.mean_and_std.iter()
+                .map(|mean_and_std| (mean_and_std.mean, f32::from(mean_and_std.std)))
+                .collect();
+            tensor.along_axis(&descr.axis, &values)?
+        }
+    };
+    let eps = f32::from(PreprocessingEpsilon::default());
+    let mut data = tensor.data.to_f32();
+    Zip::from(&mut data).and_broadcast(&means_and_stds).for_each(|value, (mean, std)|{
+        *value = (*value - mean) / (std + eps);
+    });
+    Ok(data.into())
+}
+
+pub(crate) fn zmuv(tensor: &LabeledData, descr: &Zmuv) -> Result<NpyArray, ProcessingError>{
+    let reduced_axes = tensor.reduced_axes(descr.axes.as_deref())?;
+    let eps = f32::from(descr.eps);
+    let mut data = tensor.data.to_f32();
+    let means_and_stds = reduce_groups(&data, &reduced_axes, mean_and_std);
+    Zip::from(&mut data).and_broadcast(&means_and_stds).for_each(|value, (mean, std)|{
+        *value = (*value - mean) / (std + eps);
+    });
+    Ok(data.into())
+}
+
+/// Scales `tensor` into the range defined by the percentiles of `reference`, which may be `tensor` itself
+pub(crate) fn scale_range(
+    tensor: &LabeledData, reference: &LabeledData, descr: &ScaleRangeDescr
+) -> Result<NpyArray, ProcessingError>{
+    let reduced_axes = reference.reduced_axes(descr.axes.as_deref())?;
+    let reference_data = reference.data.to_f32();
+    let bounds = reduce_groups(&reference_data, &reduced_axes, |values|{
+        percentiles(values, [descr.percentiles.min(), descr.percentiles.max()])
+    });
+    let mut data = tensor.data.to_f32();
+    let eps = f32::from(descr.eps);
+    let Some(bounds) = bounds.broadcast(data.raw_dim()) else {
+        return Err(ProcessingError::IncompatibleReferenceShape {
+            reference: reference.tensor_id.clone(),
+            reference_shape: reference.data.shape().to_owned(),
+            shape: data.shape().to_owned(),
+        })
+    };
+    Zip::from(&mut data).and(&bounds).for_each(|value, [lower, upper]|{
+        *value = (*value - lower) / (upper - lower + eps);
+    });
+    Ok(data.into())
+}
+
+pub(crate) fn scale_mean_variance(
+    tensor: &LabeledData, reference: &LabeledData, descr: &ScaleMeanVarianceDescr
+) -> Result<NpyArray, ProcessingError>{
+    let mut data = tensor.data.to_f32();
+    let means_and_stds = reduce_groups(&data, &tensor.reduced_axes(descr.axes.as_deref())?, mean_and_std);
+    let reference_data = reference.data.to_f32();
+    let ref_means_and_stds = reduce_groups(&reference_data, &reference.reduced_axes(descr.axes.as_deref())?, mean_and_std);
+    let Some(ref_means_and_stds) = ref_means_and_stds.broadcast(data.raw_dim()) else {
+        return Err(ProcessingError::IncompatibleReferenceShape {
+            reference: reference.tensor_id.clone(),
+            reference_shape: reference.data.shape().to_owned(),
+            shape: data.shape().to_owned(),
+        })
+    };
+    let eps = f32::from(descr.eps);
+    Zip::from(&mut data).and_broadcast(&means_and_stds).and(&ref_means_and_stds).for_each(
+        |value, (mean, std), (ref_mean, ref_std)|{
+            *value = (*value - mean) / (std + eps) * (ref_std + eps) + ref_mean;
+        }
+    );
+    Ok(data.into())
+}
+
+/// Finds the description of the tensor `tensor_id` in `interface` and labels its data in `tensors` with it
+fn resolve_reference<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>, tensors: &HashMap<TensorId, ArcNpyArray>, tensor_id: &TensorId,
+) -> Result<LabeledData, ProcessingError>{
+    let axis_ids: Vec<AxisId> = if let Some(slot) = interface.inputs().iter().find(|slot| slot.tensor_meta.id == *tensor_id){
+        slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect()
+    } else if let Some(slot) = interface.outputs().iter().find(|slot| slot.tensor_meta.id == *tensor_id){
+        slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect()
+    } else {
+        return Err(ProcessingError::UnresolvedReference(tensor_id.clone()))
+    };
+    let Some(data) = tensors.get(tensor_id) else {
+        return Err(ProcessingError::UnresolvedReference(tensor_id.clone()))
+    };
+    LabeledData::new(tensor_id.clone(), axis_ids, data.clone())
+}
+
+/// Resolves the `reference_tensor` of a processing step, which defaults to the tensor being processed
+fn reference_or_self(
+    tensor: &LabeledData,
+    reference: Option<&TensorId>,
+    resolve: &impl Fn(&TensorId) -> Result<LabeledData, ProcessingError>,
+) -> Result<LabeledData, ProcessingError>{
+    match reference{
+        Some(reference) if *reference != tensor.tensor_id => resolve(reference),
+        _ => Ok(tensor.clone()),
+    }
+}
+
+fn run_preprocessing(
+    tensor_meta: &InputTensorMetadata,
+    data: ArcNpyArray,
+    resolve: impl Fn(&TensorId) -> Result<LabeledData, ProcessingError>,
+) -> Result<ArcNpyArray, ProcessingError>{
+    let axis_ids: Vec<AxisId> = tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+    let mut tensor = LabeledData::new(tensor_meta.id.clone(), axis_ids, data)?;
+    for step in tensor_meta.normalized_preprocessing().iter(){
+        let processed = match step{
+            PreprocessingDescr::Binarize(descr) => binarize(&tensor, descr)?,
+            PreprocessingDescr::Clip(descr) => clip(&tensor, descr),
+            PreprocessingDescr::EnsureDtype(descr) => ensure_dtype(&tensor, descr),
+            PreprocessingDescr::ScaleLinear(descr) => scale_linear(&tensor, descr)?,
+            PreprocessingDescr::Sigmoid(_) => sigmoid(&tensor),
+            PreprocessingDescr::FixedZeroMeanUnitVariance(descr) => fixed_zmuv(&tensor, descr)?,
+            PreprocessingDescr::ZeroMeanUnitVariance(descr) => zmuv(&tensor, descr)?,
+            PreprocessingDescr::ScaleRange(descr) => {
+                let reference = reference_or_self(&tensor, descr.reference_tensor.as_ref(), &resolve)?;
+                scale_range(&tensor, &reference, descr)?
+            },
+        };
+        tensor.data = Arc::new(processed);
+    }
+    Ok(tensor.data)
+}
+
+/// Applies all preprocessing steps described in `tensor_meta` to `data`.
+///
+/// The steps are those of [InputTensorMetadata::normalized_preprocessing], so the data is first converted to the
+/// tensor's data type and, unless the last step is an `ensure_dtype` or a `binarize`, the output is float32.
+/// Steps referencing other tensors fail with [ProcessingError::UnresolvedReference]; use
+/// [preprocess_with_references] for those.
+pub fn preprocess(tensor_meta: &InputTensorMetadata, data: ArcNpyArray) -> Result<ArcNpyArray, ProcessingError>{
+    run_preprocessing(tensor_meta, data, |reference| Err(ProcessingError::UnresolvedReference(reference.clone())))
+}
+
+/// Like [preprocess], but resolves references to other tensors of `interface` via `tensors`
+pub fn preprocess_with_references<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>,
+    input_id: &TensorId,
+    data: ArcNpyArray,
+    tensors: &HashMap<TensorId, ArcNpyArray>,
+) -> Result<ArcNpyArray, ProcessingError>{
+    let Some(slot) = interface.inputs().iter().find(|slot| slot.tensor_meta.id == *input_id) else {
+        return Err(ProcessingError::NoSuchTensor(input_id.clone()))
+    };
+    run_preprocessing(&slot.tensor_meta, data, |reference| resolve_reference(interface, tensors, reference))
+}
+
+/// Applies the postprocessing steps of the output `output_id` of `interface` to `raw_output`, as produced by the model.
+///
+/// Steps that reference other tensors (e.g. `scale_mean_variance` or `scale_range` with a `reference_tensor`)
+/// are computed over the data in `tensors`, which maps tensor ids to the data those references should resolve to.
+/// The steps are those of `OutputTensorMetadata::normalized_postprocessing`, so the result has the output's data type.
+pub fn postprocess<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>,
+    output_id: &TensorId,
+    raw_output: ArcNpyArray,
+    tensors: &HashMap<TensorId, ArcNpyArray>,
+) -> Result<ArcNpyArray, ProcessingError>{
+    let Some(slot) = interface.outputs().iter().find(|slot| slot.tensor_meta.id == *output_id) else {
+        return Err(ProcessingError::NoSuchTensor(output_id.clone()))
+    };
+    let tensor_meta = &slot.tensor_meta;
+    let resolve = |reference: &TensorId| resolve_reference(interface, tensors, reference);
+    let axis_ids: Vec<AxisId> = tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+    let mut tensor = LabeledData::new(tensor_meta.id.clone(), axis_ids, raw_output)?;
+    for step in tensor_meta.normalized_postprocessing().iter(){
+        let processed = match step{
+            PostprocessingDescr::Binarize(descr) => binarize(&tensor, descr)?,
+            PostprocessingDescr::Clip(descr) => clip(&tensor, descr),
+            PostprocessingDescr::EnsureDtype(descr) => ensure_dtype(&tensor, descr),
+            PostprocessingDescr::ScaleLinear(descr) => scale_linear(&tensor, descr)?,
+            PostprocessingDescr::Sigmoid(_) => sigmoid(&tensor),
+            PostprocessingDescr::FixedZeroMeanUnitVariance(descr) => fixed_zmuv(&tensor, descr)?,
+            PostprocessingDescr::ZeroMeanUnitVariance(descr) => zmuv(&tensor, descr)?,
+            PostprocessingDescr::ScaleRange(descr) => {
+                let reference = reference_or_self(&tensor, descr.reference_tensor.as_ref(), &resolve)?;
+                scale_range(&tensor, &reference, descr)?
+            },
+            PostprocessingDescr::ScaleMeanVarianceDescr(descr) => {
+                let reference = reference_or_self(&tensor, Some(&descr.reference_tensor), &resolve)?;
+                scale_mean_variance(&tensor, &reference, descr)?
+            },
+        };
+        tensor.data = Arc::new(processed);
+    }
+    Ok(tensor.data)
+}
+
+#[test]
+fn test_zmuv_along_reduced_axes(){
+    let tensor_meta: InputTensorMetadata = serde_json::from_value(serde_json::json!({
+        "id": "raw",
+        "axes": [
+            {"type": "channel", "channel_names": ["c0", "c1"]},
+            {"type": "space", "id": "x", "size": 4},
+        ],
+        "preprocessing": [
+            {"id": "zero_mean_unit_variance", "kwargs": {"axes": ["x"]}},
+        ],
+    })).unwrap();
+
+    let raw = ndarray::array![[1.0f32, 2.0, 3.0, 4.0], [10.0, 10.0, 30.0, 30.0]].into_dyn();
+    let processed = preprocess(&tensor_meta, Arc::new(raw.into())).unwrap().to_f32();
+
+    let channel_1 = processed.index_axis(ndarray::Axis(0), 1);
+    assert!(channel_1.iter().zip([-1.0, -1.0, 1.0, 1.0]).all(|(a, b)| (a - b).abs() < 1e-4));
+    let channel_0_mean = processed.index_axis(ndarray::Axis(0), 0).sum() / 4.0;
+    assert!(channel_0_mean.abs() < 1e-6);
+}
+
+#[test]
+fn test_elementwise_preprocessing(){
+    let run = |step: serde_json::Value, raw: ndarray::ArrayD<f32>| -> NpyArray {
+        let tensor_meta: InputTensorMetadata = serde_json::from_value(serde_json::json!({
+            "id": "raw",
+            "axes": [{"type": "space", "id": "x", "size": 4}],
+            "preprocessing": [step],
+        })).unwrap();
+        preprocess(&tensor_meta, Arc::new(raw.into())).unwrap().as_ref().clone()
+    };
+    let raw = ndarray::array![-1.0f32, 0.25, 0.75, 300.0].into_dyn();
+    let close = |a: &ArrayD<f32>, b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= 1e-4 * b.abs().max(1.0));
+
+    let NpyArray::ArrayBOOL(binarized) = run(serde_json::json!({"id": "binarize", "kwargs": {"threshold": 0.5}}), raw.clone()) else {
+        panic!("binarize should produce booleans");
+    };
+    assert_eq!(binarized.as_slice().unwrap(), &[false, false, true, true]);
+
+    let clipped = run(serde_json::json!({"id": "clip", "kwargs": {"min": 0.0, "max": 1.0}}), raw.clone());
+    assert_eq!(clipped.dtype(), bioimg_spec::rdf::model::DataType::Float32);
+    assert!(close(&clipped.to_f32(), &[0.0, 0.25, 0.75, 1.0]));
+
+    let fixed = run(serde_json::json!({"id": "fixed_zero_mean_unit_variance", "kwargs": {"mean": 0.25, "std": 0.5}}), raw.clone());
+    assert!(close(&fixed.to_f32(), &[-2.5, 0.0, 1.0, 599.5]));
+
+    let scaled = run(
+        serde_json::json!({"id": "scale_range", "kwargs": {"min_percentile": 0.0, "max_percentile": 100.0, "eps": 1e-6}}),
+        ndarray::array![2.0f32, 4.0, 6.0, 10.0].into_dyn(),
+    );
+    assert!(close(&scaled.to_f32(), &[0.0, 0.25, 0.5, 1.0]));
+
+    let NpyArray::ArrayU8(ensured) = run(serde_json::json!({"id": "ensure_dtype", "kwargs": {"dtype": "uint8"}}), raw) else {
+        panic!("ensure_dtype should produce the requested data type");
+    };
+    assert_eq!(ensured.as_slice().unwrap(), &[0, 0, 0, 255]);
+}
+
+#[test]
+fn test_processing_with_references(){
+    use crate::model_interface::{InputSlot, OutputSlot};
+
+    fn meta<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T{
+        serde_json::from_value(value).unwrap()
+    }
+    let x_axis = serde_json::json!([{"type": "space", "id": "x", "size": 4}]);
+    let zeros = || Arc::new(NpyArray::from(ArrayD::<f32>::zeros(vec![4])));
+    let interface = ModelInterface::try_build(
+        vec![
+            InputSlot{tensor_meta: meta(serde_json::json!({"id": "raw", "axes": x_axis})), test_tensor: zeros()},
+            InputSlot{
+                tensor_meta: meta(serde_json::json!({"id": "aux", "axes": x_axis, "preprocessing": [
+                    {"id": "scale_range", "kwargs": {"reference_tensor": "raw", "eps": 1e-6}},
+                ]})),
+                test_tensor: zeros(),
+            },
+        ],
+        vec![
+            OutputSlot{
+                tensor_meta: meta(serde_json::json!({"id": "rescaled", "axes": x_axis, "postprocessing": [
+                    {"id": "scale_mean_variance", "kwargs": {"reference_tensor": "raw", "eps": 1e-6}},
+                ]})),
+                test_tensor: zeros(),
+            },
+            OutputSlot{
+                tensor_meta: meta(serde_json::json!({"id": "ranged", "axes": x_axis, "postprocessing": [
+                    {"id": "scale_range", "kwargs": {"reference_tensor": "raw", "eps": 1e-6}},
+                ]})),
+                test_tensor: zeros(),
+            },
+        ],
+    ).unwrap();
+    let raw_id = TensorId::try_from("raw").unwrap();
+    let tensors = HashMap::from([(raw_id, Arc::new(NpyArray::from(ndarray::array![10.0f32, 30.0, 10.0, 30.0].into_dyn())))]);
+    let close = |a: &ArcNpyArray, b: &[f32]| a.to_f32().iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3);
+
+    let aux = Arc::new(NpyArray::from(ndarray::array![20.0f32, 10.0, 30.0, 40.0].into_dyn()));
+    let aux_id = TensorId::try_from("aux").unwrap();
+    let preprocessed = preprocess_with_references(&interface, &aux_id, aux.clone(), &tensors).unwrap();
+    assert!(close(&preprocessed, &[0.5, 0.0, 1.0, 1.5]));
+    let aux_meta = &interface.inputs()[1].tensor_meta;
+    assert!(matches!(preprocess(aux_meta, aux.clone()), Err(ProcessingError::UnresolvedReference(_))));
+
+    let raw_output = Arc::new(NpyArray::from(ndarray::array![1.0f32, 3.0, 1.0, 3.0].into_dyn()));
+    let rescaled = postprocess(&interface, &TensorId::try_from("rescaled").unwrap(), raw_output, &tensors).unwrap();
+    assert_eq!(rescaled.dtype(), bioimg_spec::rdf::model::DataType::Float32);
+    assert!(close(&rescaled, &[10.0, 30.0, 10.0, 30.0]));
+
+    let ranged = postprocess(&interface, &TensorId::try_from("ranged").unwrap(), aux, &tensors).unwrap();
+    assert!(close(&ranged, &[0.5, 0.0, 1.0, 1.5]));
+
+    let missing = postprocess(&interface, &TensorId::try_from("ranged").unwrap(), preprocessed, &HashMap::new());
+    assert!(matches!(missing, Err(ProcessingError::UnresolvedReference(_))));
+}
@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use bioimg_spec::rdf::model::{DataType, InputAxis, OutputAxis, SpaceUnit, TimeUnit};
+use ndarray::{ArrayD, Axis, IxDyn};
+
+use crate::npy_array::NpyArray;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResamplingError{
+    #[error("Expected {expected} axes, found {found}")]
+    MismatchedNumDimensions{expected: usize, found: usize},
+    #[error("Can't compare spacing in {from} with spacing in {to}")]
+    IncompatibleUnits{from: PhysicalUnit, to: PhysicalUnit},
+    #[error("Bad zoom factor for dimension #{dim_index}: {factor}")]
+    BadZoomFactor{dim_index: usize, factor: f64},
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicalUnit{
+    Space(SpaceUnit),
+    Time(TimeUnit),
+}
+
+impl Display for PhysicalUnit{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Space(unit) => unit.fmt(f),
+            Self::Time(unit) => unit.fmt(f),
+        }
+    }
+}
+
+/// Physical size of one pixel along an axis
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelSpacing{
+    pub size: f64,
+    /// If `None`, the spacing is assumed to be in the same unit as whatever it is compared against
+    pub unit: Option<PhysicalUnit>,
+}
+
+impl PixelSpacing{
+    pub fn of_input_axis(axis: &InputAxis) -> Option<Self>{
+        match axis{
+            InputAxis::Space(axis) => Some(Self{size: f32::from(axis.scale) as f64, unit: axis.unit.map(PhysicalUnit::Space)}),
+            InputAxis::Time(axis) => Some(Self{size: f32::from(axis.scale) as f64, unit: axis.unit.map(PhysicalUnit::Time)}),
+            _ => None,
+        }
+    }
+
+    pub fn of_output_axis(axis: &OutputAxis) -> Option<Self>{
+        match axis{
+            OutputAxis::Space(axis) => Some(Self{size: f32::from(axis.scale) as f64, unit: axis.unit.map(PhysicalUnit::Space)}),
+            OutputAxis::Time(axis) => Some(Self{size: f32::from(axis.scale) as f64, unit: axis.unit.map(PhysicalUnit::Time)}),
+            _ => None,
+        }
+    }
+
+    /// This spacing expressed in `unit`
+    pub fn size_in(&self, unit: Option<PhysicalUnit>) -> Result<f64, ResamplingError>{
+        match (self.unit, unit){
+            (Some(PhysicalUnit::Space(from)), Some(PhysicalUnit::Space(to))) => Ok(from.convert(self.size, to)),
+            (Some(PhysicalUnit::Time(from)), Some(PhysicalUnit::Time(to))) => Ok(from.convert(self.size, to)),
+            (Some(from), Some(to)) => Err(ResamplingError::IncompatibleUnits{from, to}),
+            _ => Ok(self.size),
+        }
+    }
+}
+
+/// Per-axis factors by which data with spacing `from` must be zoomed to have spacing `to`.
+///
+/// Axes without a spacing on either side (e.g. batch or channel) get a factor of 1.
+pub fn zoom_factors(from: &[Option<PixelSpacing>], to: &[Option<PixelSpacing>]) -> Result<Vec<f64>, ResamplingError>{
+    if from.len() != to.len(){
+        return Err(ResamplingError::MismatchedNumDimensions{expected: to.len(), found: from.len()})
+    }
+    from.iter().zip(to)
+        .map(|(from, to)| match (from, to){
+            (Some(from), Some(to)) => Ok(from.size_in(to.unit)? / to.size),
+            _ => Ok(1.0),
+        })
+        .collect()
+}
+
+/// Zoom factors that bring data with pixel spacing `spacing` to the resolution expected by input `axes`
+pub fn zoom_factors_to_input(spacing: &[Option<PixelSpacing>], axes: &[InputAxis]) -> Result<Vec<f64>, ResamplingError>{
+    let model_spacing: Vec<_> = axes.iter().map(PixelSpacing::of_input_axis).collect();
+    zoom_factors(spacing, &model_spacing)
+}
+
+/// Zoom factors that bring data produced for output `axes` back to pixel spacing `spacing`
+pub fn zoom_factors_from_output(axes: &[OutputAxis], spacing: &[Option<PixelSpacing>]) -> Result<Vec<f64>, ResamplingError>{
+    let model_spacing: Vec<_> = axes.iter().map(PixelSpacing::of_output_axis).collect();
+    zoom_factors(&model_spacing, spacing)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Interpolation{
+    #[default]
+    Nearest,
+    Linear,
+}
+
+fn resample_axis(values: &ArrayD<f64>, axis: usize, new_extent: usize, interpolation: Interpolation) -> ArrayD<f64>{
+    let extent = values.shape()[axis];
+    if extent == new_extent{
+        return values.clone()
+    }
+    let mut shape = values.shape().to_owned();
+    shape[axis] = new_extent;
+    if extent == 0{
+        return ArrayD::zeros(IxDyn(&shape))
+    }
+    // pixel centers of source and destination are aligned, as in scikit-image and OpenCV
+    let factor = new_extent as f64 / extent as f64;
+    let sources: Vec<(usize, usize, f64)> = (0..new_extent)
+        .map(|dst| {
+            let src = ((dst as f64 + 0.5) / factor - 0.5).clamp(0.0, (extent - 1) as f64);
+            match interpolation{
+                Interpolation::Nearest => (src.round() as usize, src.round() as usize, 0.0),
+                Interpolation::Linear => {
+                    let lower = src.floor() as usize;
+                    (lower, (lower + 1).min(extent - 1), src - lower as f64)
+                },
+            }
+        })
+        .collect();
+    let mut out = ArrayD::zeros(IxDyn(&shape));
+    for (dst, (lower, upper, weight)) in sources.into_iter().enumerate(){
+        let mut out_lane = out.index_axis_mut(Axis(axis), dst);
+        let lower_lane = values.index_axis(Axis(axis), lower);
+        if weight == 0.0{
+            out_lane.assign(&lower_lane);
+        } else {
+            let upper_lane = values.index_axis(Axis(axis), upper);
+            out_lane.zip_mut_with(&lower_lane, |o, l| *o = l * (1.0 - weight));
+            out_lane.zip_mut_with(&upper_lane, |o, u| *o += u * weight);
+        }
+    }
+    out
+}
+
+/// Resamples `data` to `shape`, keeping its element type
+pub fn resample(data: &NpyArray, shape: &[usize], interpolation: Interpolation) -> Result<NpyArray, ResamplingError>{
+    if shape.len() != data.shape().len(){
+        return Err(ResamplingError::MismatchedNumDimensions{expected: data.shape().len(), found: shape.len()})
+    }
+    let mut values = data.to_f64();
+    for (axis, new_extent) in shape.iter().enumerate(){
+        values = resample_axis(&values, axis, *new_extent, interpolation);
+    }
+    if !matches!(data.dtype(), DataType::Float32 | DataType::Float64){
+        values.mapv_inplace(f64::round);
+    }
+    Ok(NpyArray::from_f64(&values, data.dtype()))
+}
+
+/// Resamples `data` so that each dimension is `factors[dim]` times its current extent (rounded to the nearest integer)
+pub fn zoom(data: &NpyArray, factors: &[f64], interpolation: Interpolation) -> Result<NpyArray, ResamplingError>{
+    if factors.len() != data.shape().len(){
+        return Err(ResamplingError::MismatchedNumDimensions{expected: data.shape().len(), found: factors.len()})
+    }
+    let shape = data.shape().iter().zip(factors).enumerate()
+        .map(|(dim_index, (extent, factor))|{
+            if !factor.is_finite() || *factor <= 0.0{
+                return Err(ResamplingError::BadZoomFactor{dim_index, factor: *factor})
+            }
+            Ok(((*extent as f64 * factor).round() as usize).max(1))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    resample(data, &shape, interpolation)
+}
+
+#[test]
+fn test_zoom_between_units(){
+    let data_spacing = [None, Some(PixelSpacing{size: 100.0, unit: Some(PhysicalUnit::Space(SpaceUnit::Nanometer))})];
+    let model_spacing = [None, Some(PixelSpacing{size: 0.2, unit: Some(PhysicalUnit::Space(SpaceUnit::Micrometer))})];
+    let factors = zoom_factors(&data_spacing, &model_spacing).unwrap();
+    assert!((factors[0] - 1.0).abs() < 1e-9 && (factors[1] - 0.5).abs() < 1e-9);
+
+    let data = NpyArray::from(ndarray::array![[0.0f32, 2.0, 4.0, 6.0]].into_dyn());
+    let zoomed = zoom(&data, &factors, Interpolation::Linear).unwrap();
+    assert_eq!(zoomed.to_f32().into_raw_vec(), vec![1.0, 5.0]);
+    let restored = resample(&zoomed, data.shape(), Interpolation::Nearest).unwrap();
+    assert_eq!(restored.to_f32().into_raw_vec(), vec![1.0, 1.0, 5.0, 5.0]);
+
+    let bad_units = [None, Some(PixelSpacing{size: 1.0, unit: Some(PhysicalUnit::Time(TimeUnit::Second))})];
+    assert!(zoom_factors(&data_spacing, &bad_units).is_err());
+}
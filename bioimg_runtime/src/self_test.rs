@@ -0,0 +1,233 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::{DataType, TensorId};
+
+use crate::npy_array::{ArcNpyArray, NpyArray};
+use crate::processing::{self, ProcessingError};
+use crate::ModelInterface;
+
+pub type InferenceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Something capable of running the model on already preprocessed inputs, producing raw outputs
+pub trait InferenceBackend{
+    fn infer(&self, inputs: &HashMap<TensorId, ArcNpyArray>) -> Result<HashMap<TensorId, ArcNpyArray>, InferenceError>;
+}
+
+impl<F> InferenceBackend for F
+where
+    F: Fn(&HashMap<TensorId, ArcNpyArray>) -> Result<HashMap<TensorId, ArcNpyArray>, InferenceError>
+{
+    fn infer(&self, inputs: &HashMap<TensorId, ArcNpyArray>) -> Result<HashMap<TensorId, ArcNpyArray>, InferenceError> {
+        self(inputs)
+    }
+}
+
+#[cfg(feature = "onnx")]
+impl InferenceBackend for crate::onnx_backend::OnnxModel{
+    fn infer(&self, inputs: &HashMap<TensorId, ArcNpyArray>) -> Result<HashMap<TensorId, ArcNpyArray>, InferenceError> {
+        Ok(self.run(inputs)?)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SelfTestError{
+    #[error("Could not process tensor: {0}")]
+    ProcessingError(#[from] ProcessingError),
+    #[error("Inference failed: {0}")]
+    InferenceError(InferenceError),
+    #[error("Inference did not produce output '{0}'")]
+    MissingOutput(TensorId),
+    #[error("No inference backend available for any of the model weights")]
+    NoAvailableBackend,
+}
+
+/// Element-wise tolerance, with the same semantics as numpy's `allclose`:
+/// `|found - expected| <= absolute + relative * |expected|`
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance{
+    pub absolute: f64,
+    pub relative: f64,
+}
+
+impl Default for Tolerance{
+    fn default() -> Self {
+        Self{absolute: 1e-4, relative: 1e-3}
+    }
+}
+
+impl Tolerance{
+    pub fn accepts(&self, expected: f64, found: f64) -> bool{
+        if expected.is_nan() || found.is_nan(){
+            return expected.is_nan() && found.is_nan()
+        }
+        expected == found || (found - expected).abs() <= self.absolute + self.relative * expected.abs()
+    }
+}
+
+/// Comparison between an output produced by the model and its test tensor
+#[derive(Clone, Debug)]
+pub struct TensorComparison{
+    pub tensor_id: TensorId,
+    pub expected_shape: Vec<usize>,
+    pub found_shape: Vec<usize>,
+    pub expected_dtype: DataType,
+    pub found_dtype: DataType,
+    /// `None` if the shapes don't match, since then there is nothing to compare element-wise
+    pub max_abs_error: Option<f64>,
+    pub num_mismatched_elements: usize,
+    pub num_elements: usize,
+}
+
+impl TensorComparison{
+    pub fn new(tensor_id: TensorId, expected: &NpyArray, found: &NpyArray, tolerance: &Tolerance) -> Self{
+        let mut comparison = Self{
+            tensor_id,
+            expected_shape: expected.shape().to_owned(),
+            found_shape: found.shape().to_owned(),
+            expected_dtype: expected.dtype(),
+            found_dtype: found.dtype(),
+            max_abs_error: None,
+            num_mismatched_elements: 0,
+            num_elements: expected.shape().iter().product(),
+        };
+        if !comparison.shape_matches(){
+            return comparison
+        }
+        let expected = expected.to_f64();
+        let found = found.to_f64();
+        let mut max_abs_error = 0.0f64;
+        for (exp, fnd) in expected.iter().zip(found.iter()){
+            if !tolerance.accepts(*exp, *fnd){
+                comparison.num_mismatched_elements += 1;
+            }
+            max_abs_error = max_abs_error.max((fnd - exp).abs());
+        }
+        comparison.max_abs_error = Some(max_abs_error);
+        comparison
+    }
+    pub fn shape_matches(&self) -> bool{
+        self.expected_shape == self.found_shape
+    }
+    pub fn dtype_matches(&self) -> bool{
+        self.expected_dtype == self.found_dtype
+    }
+    pub fn passed(&self) -> bool{
+        self.shape_matches() && self.dtype_matches() && self.num_mismatched_elements == 0
+    }
+}
+
+impl Display for TensorComparison{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.tensor_id)?;
+        if !self.shape_matches(){
+            return write!(f, "expected shape {:?} but found {:?}", self.expected_shape, self.found_shape)
+        }
+        if !self.dtype_matches(){
+            write!(f, "expected dtype {} but found {}; ", self.expected_dtype, self.found_dtype)?;
+        }
+        write!(
+            f, "{} of {} elements out of tolerance, max abs error: {}",
+            self.num_mismatched_elements, self.num_elements, self.max_abs_error.unwrap_or(f64::NAN)
+        )
+    }
+}
+
+/// Per-output result of running a model on its input test tensors
+#[derive(Clone, Debug)]
+pub struct SelfTestReport{
+    pub tolerance: Tolerance,
+    pub outputs: Vec<TensorComparison>,
+}
+
+impl SelfTestReport{
+    pub fn passed(&self) -> bool{
+        self.outputs.iter().all(|comparison| comparison.passed())
+    }
+}
+
+impl Display for SelfTestReport{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = if self.passed(){ "passed" } else { "failed" };
+        writeln!(
+            f, "Self test {outcome} (absolute tolerance: {}, relative tolerance: {})",
+            self.tolerance.absolute, self.tolerance.relative
+        )?;
+        for comparison in &self.outputs{
+            writeln!(f, "  {comparison}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `backend` on the input test tensors of `interface` and compares the outputs against the output test tensors.
+///
+/// Inputs are preprocessed and outputs postprocessed as described in their metadata. References from
+/// postprocessing steps resolve to the preprocessed inputs and to the raw outputs.
+pub fn run_self_test<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>,
+    backend: &(impl InferenceBackend + ?Sized),
+    tolerance: Tolerance,
+) -> Result<SelfTestReport, SelfTestError>{
+    let raw_inputs: HashMap<TensorId, ArcNpyArray> = interface.inputs().iter()
+        .map(|slot| (slot.tensor_meta.id.clone(), Arc::new(slot.test_tensor.borrow().clone())))
+        .collect();
+    let mut tensors = HashMap::<TensorId, ArcNpyArray>::new();
+    for slot in interface.inputs().iter(){
+        let tensor_id = &slot.tensor_meta.id;
+        let preprocessed = processing::preprocess_with_references(
+            interface, tensor_id, Arc::clone(&raw_inputs[tensor_id]), &raw_inputs
+        )?;
+        tensors.insert(tensor_id.clone(), preprocessed);
+    }
+
+    let raw_outputs = backend.infer(&tensors).map_err(SelfTestError::InferenceError)?;
+    for slot in interface.outputs().iter(){
+        let tensor_id = &slot.tensor_meta.id;
+        let raw_output = raw_outputs.get(tensor_id).ok_or_else(|| SelfTestError::MissingOutput(tensor_id.clone()))?;
+        tensors.insert(tensor_id.clone(), Arc::clone(raw_output));
+    }
+
+    let outputs = interface.outputs().iter()
+        .map(|slot|{
+            let tensor_id = &slot.tensor_meta.id;
+            let output = processing::postprocess(interface, tensor_id, Arc::clone(&raw_outputs[tensor_id]), &tensors)?;
+            Ok(TensorComparison::new(tensor_id.clone(), slot.test_tensor.borrow(), &output, &tolerance))
+        })
+        .collect::<Result<_, SelfTestError>>()?;
+    Ok(SelfTestReport{tolerance, outputs})
+}
+
+#[test]
+fn test_self_test_reports_mismatches(){
+    use crate::model_interface::{InputSlot, OutputSlot};
+
+    let input_meta = serde_json::from_value(serde_json::json!({
+        "id": "raw",
+        "axes": [{"type": "space", "id": "x", "size": 4}],
+        "preprocessing": [{"id": "scale_linear", "kwargs": {"gain": 2.0, "offset": 0.0}}],
+    })).unwrap();
+    let output_meta = serde_json::from_value(serde_json::json!({
+        "id": "out",
+        "axes": [{"type": "space", "id": "x", "size": 4}],
+    })).unwrap();
+    let interface = ModelInterface::try_build(
+        vec![InputSlot{tensor_meta: input_meta, test_tensor: Arc::new(NpyArray::from(ndarray::array![1u8, 2, 3, 4].into_dyn()))}],
+        vec![OutputSlot{tensor_meta: output_meta, test_tensor: Arc::new(NpyArray::from(ndarray::array![2.0f32, 4.0, 6.0, 8.1].into_dyn()))}],
+    ).unwrap();
+    let identity = |inputs: &HashMap<TensorId, ArcNpyArray>| -> Result<HashMap<TensorId, ArcNpyArray>, InferenceError>{
+        Ok(inputs.values().map(|data| (TensorId::try_from("out").unwrap(), Arc::clone(data))).collect())
+    };
+
+    let report = run_self_test(&interface, &identity, Tolerance::default()).unwrap();
+    let comparison = &report.outputs[0];
+    assert!(!report.passed());
+    assert!(comparison.shape_matches() && comparison.dtype_matches());
+    assert_eq!(comparison.num_mismatched_elements, 1);
+    assert!((comparison.max_abs_error.unwrap() - 0.1).abs() < 1e-5);
+
+    let report = run_self_test(&interface, &identity, Tolerance{absolute: 0.2, relative: 0.0}).unwrap();
+    assert!(report.passed());
+}
@@ -0,0 +1,506 @@
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::{DataType, SpaceUnit, TimeUnit};
+use ndarray::{ArrayD, IxDyn};
+use tiff::decoder::{ifd::Value, Decoder, DecodingResult};
+use tiff::encoder::{colortype, TiffEncoder, TiffValue};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+use crate::labeled_tensor::{LabeledTensor, LabeledTensorError};
+use crate::npy_array::NpyArray;
+use crate::resampling::{PhysicalUnit, PixelSpacing};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TiffIoError{
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    TiffError(#[from] tiff::TiffError),
+    #[error("Unsupported tiff color type: {0:?}")]
+    UnsupportedColorType(ColorType),
+    #[error("Page #{page_index} doesn't have the same dimensions or pixel type as the first page")]
+    InconsistentPages{page_index: usize},
+    #[error("Bad image metadata: {0}")]
+    BadMetadata(String),
+    #[error("Bad axes '{axes}' for an array with {num_dims} dimensions: {reason}")]
+    BadAxes{axes: String, num_dims: usize, reason: String},
+    #[error("Can't write {0} data to an OME-TIFF")]
+    UnsupportedDataType(DataType),
+    #[error("Image of shape {0:?} can't be written as tiff pages")]
+    BadPageShape(Vec<usize>),
+}
+
+/// Axes of a [TiffImage], in the order in which they appear in the data
+const CANONICAL_AXES: &str = "tczyx";
+
+const SPACE_UNIT_SYMBOLS: [(SpaceUnit, &str); 26] = [
+    (SpaceUnit::Attometer, "am"),
+    (SpaceUnit::Angstrom, "\u{c5}"),
+    (SpaceUnit::Centimeter, "cm"),
+    (SpaceUnit::Decimeter, "dm"),
+    (SpaceUnit::Exameter, "Em"),
+    (SpaceUnit::Femtometer, "fm"),
+    (SpaceUnit::Foot, "ft"),
+    (SpaceUnit::Gigameter, "Gm"),
+    (SpaceUnit::Hectometer, "hm"),
+    (SpaceUnit::Inch, "in"),
+    (SpaceUnit::Kilometer, "km"),
+    (SpaceUnit::Megameter, "Mm"),
+    (SpaceUnit::Meter, "m"),
+    (SpaceUnit::Micrometer, "\u{b5}m"),
+    (SpaceUnit::Mile, "mi"),
+    (SpaceUnit::Millimeter, "mm"),
+    (SpaceUnit::Nanometer, "nm"),
+    (SpaceUnit::Parsec, "pc"),
+    (SpaceUnit::Petameter, "Pm"),
+    (SpaceUnit::Picometer, "pm"),
+    (SpaceUnit::Terameter, "Tm"),
+    (SpaceUnit::Yard, "yd"),
+    (SpaceUnit::Yoctometer, "ym"),
+    (SpaceUnit::Yottameter, "Ym"),
+    (SpaceUnit::Zeptometer, "zm"),
+    (SpaceUnit::Zettameter, "Zm"),
+];
+
+const TIME_UNIT_SYMBOLS: [(TimeUnit, &str); 23] = [
+    (TimeUnit::Attosecond, "as"),
+    (TimeUnit::Centisecond, "cs"),
+    (TimeUnit::Day, "d"),
+    (TimeUnit::Decisecond, "ds"),
+    (TimeUnit::Exasecond, "Es"),
+    (TimeUnit::Femtosecond, "fs"),
+    (TimeUnit::Gigasecond, "Gs"),
+    (TimeUnit::Hectosecond, "hs"),
+    (TimeUnit::Hour, "h"),
+    (TimeUnit::Kilosecond, "ks"),
+    (TimeUnit::Megasecond, "Ms"),
+    (TimeUnit::Microsecond, "\u{b5}s"),
+    (TimeUnit::Millisecond, "ms"),
+    (TimeUnit::Minute, "min"),
+    (TimeUnit::Nanosecond, "ns"),
+    (TimeUnit::Petasecond, "Ps"),
+    (TimeUnit::Picosecond, "ps"),
+    (TimeUnit::Second, "s"),
+    (TimeUnit::Terasecond, "Ts"),
+    (TimeUnit::Yoctosecond, "ys"),
+    (TimeUnit::Yottasecond, "Ys"),
+    (TimeUnit::Zeptosecond, "zs"),
+    (TimeUnit::Zettasecond, "Zs"),
+];
+
+/// Normalizes the different spellings of "micro" found in OME and ImageJ metadata
+fn normalize_unit_symbol(symbol: &str) -> String{
+    let symbol = symbol.trim().replace("\\u00B5", "\u{b5}").replace('\u{3bc}', "\u{b5}");
+    match symbol.as_str(){
+        "um" | "micron" | "microns" => "\u{b5}m".to_owned(),
+        "us" => "\u{b5}s".to_owned(),
+        "A" => "\u{c5}".to_owned(),
+        _ => symbol,
+    }
+}
+
+fn parse_space_unit(symbol: &str) -> Option<SpaceUnit>{
+    let symbol = normalize_unit_symbol(symbol);
+    SPACE_UNIT_SYMBOLS.iter().find(|(_, sym)| *sym == symbol).map(|(unit, _)| *unit)
+}
+
+fn parse_time_unit(symbol: &str) -> Option<TimeUnit>{
+    let symbol = normalize_unit_symbol(symbol);
+    TIME_UNIT_SYMBOLS.iter().find(|(_, sym)| *sym == symbol).map(|(unit, _)| *unit)
+}
+
+fn unit_symbol(unit: PhysicalUnit) -> &'static str{
+    match unit{
+        PhysicalUnit::Space(unit) => SPACE_UNIT_SYMBOLS.iter().find(|(u, _)| *u == unit).map(|(_, sym)| *sym),
+        PhysicalUnit::Time(unit) => TIME_UNIT_SYMBOLS.iter().find(|(u, _)| *u == unit).map(|(_, sym)| *sym),
+    }.expect("All units should have a symbol")
+}
+
+/// How the pages of a tiff file are arranged into dimensions
+struct PageLayout{
+    /// Axis letter and extent of the dimensions spanned by the pages, slowest-varying first
+    page_axes: Vec<(char, usize)>,
+    spacing: Vec<(char, PixelSpacing)>,
+}
+
+impl PageLayout{
+    fn from_ome_xml(xml: &str, samples_per_pixel: usize) -> Result<Self, TiffIoError>{
+        let bad_metadata = |message: String| TiffIoError::BadMetadata(message);
+        let document = roxmltree::Document::parse(xml).map_err(|e| bad_metadata(e.to_string()))?;
+        let Some(pixels) = document.descendants().find(|node| node.tag_name().name() == "Pixels") else {
+            return Err(bad_metadata("No 'Pixels' element in OME-XML".to_owned()))
+        };
+        let size_of = |letter: char| -> Result<usize, TiffIoError>{
+            let attr_name = format!("Size{}", letter.to_ascii_uppercase());
+            pixels.attribute(attr_name.as_str())
+                .ok_or_else(|| bad_metadata(format!("Missing attribute {attr_name}")))?
+                .parse::<usize>()
+                .map_err(|e| bad_metadata(format!("Bad {attr_name}: {e}")))
+        };
+        let dimension_order = pixels.attribute("DimensionOrder").unwrap_or("XYZCT").to_ascii_lowercase();
+        if !dimension_order.starts_with("xy") || dimension_order.len() != 5 || !"zct".chars().all(|l| dimension_order.contains(l)){
+            return Err(bad_metadata(format!("Unsupported DimensionOrder '{dimension_order}'")))
+        }
+        let page_letters: Vec<char> = dimension_order.chars().skip(2).collect();
+        let page_axes = page_letters.into_iter().rev()
+            .map(|letter|{
+                let size = size_of(letter)?;
+                // rgb(a) samples count towards SizeC, but they are stored within each page
+                Ok((letter, if letter == 'c' { (size / samples_per_pixel).max(1) } else { size }))
+            })
+            .collect::<Result<Vec<_>, TiffIoError>>()?;
+
+        let mut spacing = vec![];
+        for letter in ['x', 'y', 'z']{
+            let attr_name = format!("PhysicalSize{}", letter.to_ascii_uppercase());
+            let Some(size) = pixels.attribute(attr_name.as_str()).and_then(|size| size.parse::<f64>().ok()) else {
+                continue
+            };
+            let unit = pixels.attribute(format!("{attr_name}Unit").as_str()).unwrap_or("\u{b5}m");
+            if let Some(unit) = parse_space_unit(unit){
+                spacing.push((letter, PixelSpacing{size, unit: Some(PhysicalUnit::Space(unit))}));
+            }
+        }
+        if let Some(size) = pixels.attribute("TimeIncrement").and_then(|size| size.parse::<f64>().ok()){
+            let unit = pixels.attribute("TimeIncrementUnit").unwrap_or("s");
+            if let Some(unit) = parse_time_unit(unit){
+                spacing.push(('t', PixelSpacing{size, unit: Some(PhysicalUnit::Time(unit))}));
+            }
+        }
+        Ok(Self{page_axes, spacing})
+    }
+
+    /// ImageJ hyperstacks store their pages in XYCZT order, with sizes and units in a plain-text description
+    fn from_imagej_description(description: &str, x_resolution: Option<f64>, y_resolution: Option<f64>) -> Self{
+        let field = |key: &str| description.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, v)| v.trim());
+        let count = |key: &str| field(key).and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+        let page_axes = vec![('t', count("frames")), ('z', count("slices")), ('c', count("channels"))];
+
+        let mut spacing = vec![];
+        if let Some(unit) = field("unit").and_then(parse_space_unit){
+            let unit = Some(PhysicalUnit::Space(unit));
+            for (letter, resolution) in [('x', x_resolution), ('y', y_resolution)]{
+                if let Some(resolution) = resolution.filter(|r| *r > 0.0){
+                    spacing.push((letter, PixelSpacing{size: 1.0 / resolution, unit}));
+                }
+            }
+            if let Some(size) = field("spacing").and_then(|v| v.parse::<f64>().ok()){
+                spacing.push(('z', PixelSpacing{size, unit}));
+            }
+        }
+        if let Some(size) = field("finterval").and_then(|v| v.parse::<f64>().ok()){
+            spacing.push(('t', PixelSpacing{size, unit: Some(PhysicalUnit::Time(TimeUnit::Second))}));
+        }
+        Self{page_axes, spacing}
+    }
+}
+
+fn append_page(pixels: &mut DecodingResult, page: DecodingResult) -> bool{
+    match (pixels, page){
+        (DecodingResult::U8(pixels), DecodingResult::U8(page)) => pixels.extend(page),
+        (DecodingResult::I8(pixels), DecodingResult::I8(page)) => pixels.extend(page),
+        (DecodingResult::U16(pixels), DecodingResult::U16(page)) => pixels.extend(page),
+        (DecodingResult::I16(pixels), DecodingResult::I16(page)) => pixels.extend(page),
+        (DecodingResult::U32(pixels), DecodingResult::U32(page)) => pixels.extend(page),
+        (DecodingResult::I32(pixels), DecodingResult::I32(page)) => pixels.extend(page),
+        (DecodingResult::U64(pixels), DecodingResult::U64(page)) => pixels.extend(page),
+        (DecodingResult::I64(pixels), DecodingResult::I64(page)) => pixels.extend(page),
+        (DecodingResult::F32(pixels), DecodingResult::F32(page)) => pixels.extend(page),
+        (DecodingResult::F64(pixels), DecodingResult::F64(page)) => pixels.extend(page),
+        _ => return false,
+    }
+    true
+}
+
+fn pixels_to_array(pixels: DecodingResult, shape: &[usize]) -> Result<NpyArray, TiffIoError>{
+    let bad_shape = |_| TiffIoError::BadPageShape(shape.to_vec());
+    let shape = IxDyn(shape);
+    Ok(match pixels{
+        DecodingResult::U8(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::I8(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::U16(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::I16(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::U32(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::I32(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::U64(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::I64(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::F32(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+        DecodingResult::F64(pixels) => ArrayD::from_shape_vec(shape.clone(), pixels).map_err(bad_shape)?.into(),
+    })
+}
+
+fn resolution_of<R: Read + Seek>(decoder: &mut Decoder<R>, tag: Tag) -> Option<f64>{
+    match decoder.find_tag(tag).ok()??{
+        Value::Rational(num, denom) if denom != 0 => Some(num as f64 / denom as f64),
+        _ => None,
+    }
+}
+
+/// A multi-page (OME-)TIFF image, with its dimensions labelled by axis letters
+#[derive(Clone)]
+pub struct TiffImage{
+    data: NpyArray,
+    axes: String,
+    pixel_spacing: Vec<Option<PixelSpacing>>,
+}
+
+impl TiffImage{
+    /// Creates an image from `data`, whose dimensions are labelled by the letters in `axes`.
+    ///
+    /// `axes` must be made of distinct letters out of "tczyx", and `pixel_spacing` must have one entry per dimension.
+    pub fn new(data: NpyArray, axes: &str, pixel_spacing: Vec<Option<PixelSpacing>>) -> Result<Self, TiffIoError>{
+        let num_dims = data.shape().len();
+        let bad_axes = |reason: &str| TiffIoError::BadAxes{axes: axes.to_owned(), num_dims, reason: reason.to_owned()};
+        if axes.chars().count() != num_dims{
+            return Err(bad_axes("expected one letter per dimension"))
+        }
+        if pixel_spacing.len() != num_dims{
+            return Err(bad_axes("expected one pixel spacing per dimension"))
+        }
+        for (idx, letter) in axes.chars().enumerate(){
+            if !CANONICAL_AXES.contains(letter){
+                return Err(bad_axes("only letters in 'tczyx' are allowed"))
+            }
+            if axes.chars().take(idx).any(|previous| previous == letter){
+                return Err(bad_axes("letters must not repeat"))
+            }
+        }
+        Ok(Self{data, axes: axes.to_owned(), pixel_spacing})
+    }
+
+    /// Reads all pages of a tiff file.
+    ///
+    /// Pages are arranged according to the OME-XML or ImageJ metadata in the first page, if present;
+    /// otherwise multiple pages are taken to be a z-stack. Axes with extent 1 other than 'y' and 'x' are
+    /// dropped, and the remaining ones follow the "tczyx" order.
+    pub fn try_load(reader: impl Read + Seek) -> Result<Self, TiffIoError>{
+        let mut decoder = Decoder::new(reader)?;
+        let (width, height) = decoder.dimensions()?;
+        let color_type = decoder.colortype()?;
+        let samples_per_pixel: usize = match color_type{
+            ColorType::Gray(_) => 1,
+            ColorType::GrayA(_) => 2,
+            ColorType::RGB(_) => 3,
+            ColorType::RGBA(_) | ColorType::CMYK(_) => 4,
+            other => return Err(TiffIoError::UnsupportedColorType(other)),
+        };
+        let description = decoder.get_tag_ascii_string(Tag::ImageDescription).ok();
+        let x_resolution = resolution_of(&mut decoder, Tag::XResolution);
+        let y_resolution = resolution_of(&mut decoder, Tag::YResolution);
+
+        let mut pixels = decoder.read_image()?;
+        let mut num_pages = 1;
+        while decoder.more_images(){
+            decoder.next_image()?;
+            let page_index = num_pages;
+            if decoder.dimensions()? != (width, height) || decoder.colortype()? != color_type{
+                return Err(TiffIoError::InconsistentPages{page_index})
+            }
+            if !append_page(&mut pixels, decoder.read_image()?){
+                return Err(TiffIoError::InconsistentPages{page_index})
+            }
+            num_pages += 1;
+        }
+
+        let layout = match &description{
+            Some(description) if description.contains("<OME") => PageLayout::from_ome_xml(description, samples_per_pixel)?,
+            Some(description) if description.starts_with("ImageJ=") => {
+                PageLayout::from_imagej_description(description, x_resolution, y_resolution)
+            },
+            _ => PageLayout{page_axes: vec![('z', num_pages)], spacing: vec![]},
+        };
+        let num_described_pages: usize = layout.page_axes.iter().map(|(_, size)| size).product();
+        if num_described_pages != num_pages{
+            return Err(TiffIoError::BadMetadata(format!(
+                "Metadata describes {num_described_pages} pages, but file has {num_pages}"
+            )))
+        }
+
+        // pages, then rows, columns and samples within each page. Samples are labelled 's' for now
+        let mut letters: Vec<char> = layout.page_axes.iter().map(|(letter, _)| *letter).collect();
+        letters.extend(['y', 'x', 's']);
+        let mut shape: Vec<usize> = layout.page_axes.iter().map(|(_, size)| *size).collect();
+        shape.extend([height as usize, width as usize, samples_per_pixel]);
+        let mut data = pixels_to_array(pixels, &shape)?;
+
+        if samples_per_pixel > 1{
+            let Some(c_idx) = letters.iter().position(|l| *l == 'c') else { unreachable!("All layouts have a 'c' axis") };
+            if shape[c_idx] != 1{
+                return Err(TiffIoError::BadMetadata("Can't combine multi-sample pixels with multiple channel pages".to_owned()))
+            }
+            letters[c_idx] = 's';
+            *letters.last_mut().unwrap() = 'c';
+        }
+        let mut idx = 0;
+        while idx < letters.len(){
+            if data.shape()[idx] == 1 && !['y', 'x'].contains(&letters[idx]){
+                data = data.remove_axis(idx);
+                letters.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        let permutation: Vec<usize> = CANONICAL_AXES.chars()
+            .filter_map(|letter| letters.iter().position(|l| *l == letter))
+            .collect();
+        let data = data.permuted_axes(&permutation);
+        let axes: String = permutation.iter().map(|idx| letters[*idx]).collect();
+        let pixel_spacing = axes.chars()
+            .map(|letter| layout.spacing.iter().find(|(l, _)| *l == letter).map(|(_, spacing)| *spacing))
+            .collect();
+        Ok(Self{data, axes, pixel_spacing})
+    }
+
+    pub fn data(&self) -> &NpyArray{
+        &self.data
+    }
+
+    /// One letter out of "tczyx" per dimension of [Self::data]
+    pub fn axes(&self) -> &str{
+        &self.axes
+    }
+
+    /// Physical size of a pixel along each dimension of [Self::data], if known
+    pub fn pixel_spacing(&self) -> &[Option<PixelSpacing>]{
+        &self.pixel_spacing
+    }
+
+    pub fn to_labeled_tensor(&self) -> Result<LabeledTensor, LabeledTensorError>{
+        LabeledTensor::from_axis_letters(Arc::new(self.data.clone()), &self.axes)
+    }
+
+    fn ome_xml(&self, shape: &[usize]) -> Result<String, TiffIoError>{
+        let pixel_type = match self.data.dtype(){
+            DataType::Uint8 => "uint8",
+            DataType::Int8 => "int8",
+            DataType::Uint16 => "uint16",
+            DataType::Int16 => "int16",
+            DataType::Uint32 => "uint32",
+            DataType::Int32 => "int32",
+            DataType::Float32 => "float",
+            DataType::Float64 => "double",
+            other => return Err(TiffIoError::UnsupportedDataType(other)),
+        };
+        let [size_t, size_c, size_z, size_y, size_x] = shape else { unreachable!("Data should be 5D") };
+        let mut pixels_attrs = format!(
+            r#"ID="Pixels:0" DimensionOrder="XYZCT" Type="{pixel_type}" SizeX="{size_x}" SizeY="{size_y}" SizeZ="{size_z}" SizeC="{size_c}" SizeT="{size_t}""#
+        );
+        for (letter, spacing) in self.axes.chars().zip(&self.pixel_spacing){
+            let Some(spacing) = spacing else { continue };
+            let attr_name = match letter{
+                'x' => "PhysicalSizeX",
+                'y' => "PhysicalSizeY",
+                'z' => "PhysicalSizeZ",
+                't' => "TimeIncrement",
+                _ => continue,
+            };
+            pixels_attrs += &format!(r#" {attr_name}="{}""#, spacing.size);
+            if let Some(unit) = spacing.unit{
+                pixels_attrs += &format!(r#" {attr_name}Unit="{}""#, unit_symbol(unit));
+            }
+        }
+        let channels: String = (0..*size_c)
+            .map(|c| format!(r#"<Channel ID="Channel:0:{c}" SamplesPerPixel="1"/>"#))
+            .collect();
+        let num_pages = size_t * size_c * size_z;
+        let xml = format!(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<OME xmlns="http://www.openmicroscopy.org/Schemas/OME/2016-06">"#,
+            r#"<Image ID="Image:0"><Pixels {}>{}<TiffData PlaneCount="{}"/></Pixels></Image>"#,
+            r#"</OME>"#,
+        ), pixels_attrs, channels, num_pages);
+        // tiff ascii tags can't hold e.g. the 'µ' in unit symbols, so those become character references
+        Ok(xml.chars().map(|c| if c.is_ascii() { c.to_string() } else { format!("&#{};", c as u32) }).collect())
+    }
+
+    /// Writes this image as an uncompressed OME-TIFF, with one grayscale page per (t, c, z) plane.
+    ///
+    /// Boolean data is rejected, since it would be read back as uint8; cast it first if that is acceptable.
+    pub fn write_ome_tiff(&self, writer: impl Write + Seek) -> Result<(), TiffIoError>{
+        let letters: Vec<char> = self.axes.chars().collect();
+        let permutation: Vec<usize> = CANONICAL_AXES.chars()
+            .filter_map(|letter| letters.iter().position(|l| *l == letter))
+            .collect();
+        let mut data = self.data.permuted_axes(&permutation);
+        for (idx, letter) in CANONICAL_AXES.chars().enumerate(){
+            if !letters.contains(&letter){
+                data = data.insert_axis(idx);
+            }
+        }
+        let shape = data.shape().to_owned();
+        let description = self.ome_xml(&shape)?;
+        let mut encoder = TiffEncoder::new(writer)?;
+        match &data{
+            NpyArray::ArrayU8(arr) => write_pages::<colortype::Gray8, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayI8(arr) => write_pages::<colortype::GrayI8, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayU16(arr) => write_pages::<colortype::Gray16, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayI16(arr) => write_pages::<colortype::GrayI16, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayU32(arr) => write_pages::<colortype::Gray32, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayI32(arr) => write_pages::<colortype::GrayI32, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayF32(arr) => write_pages::<colortype::Gray32Float, _>(&mut encoder, arr, &description),
+            NpyArray::ArrayF64(arr) => write_pages::<colortype::Gray64Float, _>(&mut encoder, arr, &description),
+            other => Err(TiffIoError::UnsupportedDataType(other.dtype())),
+        }
+    }
+}
+
+/// Writes the 5D `data` as one page per 2D plane in its last two dimensions
+fn write_pages<C, W>(encoder: &mut TiffEncoder<W>, data: &ArrayD<C::Inner>, description: &str) -> Result<(), TiffIoError>
+where
+    C: colortype::ColorType,
+    C::Inner: Clone,
+    [C::Inner]: TiffValue,
+    W: Write + Seek,
+{
+    let shape = data.shape();
+    let bad_shape = || TiffIoError::BadPageShape(shape.to_owned());
+    let (height, width) = (shape[3], shape[4]);
+    if shape.contains(&0){
+        return Err(bad_shape())
+    }
+    let (page_height, page_width) = (u32::try_from(height).map_err(|_| bad_shape())?, u32::try_from(width).map_err(|_| bad_shape())?);
+    let data = data.as_standard_layout();
+    let pixels = data.as_slice().expect("Standard layout arrays should be contiguous");
+    for (page_index, page) in pixels.chunks(height * width).enumerate(){
+        let mut image = encoder.new_image::<C>(page_width, page_height)?;
+        if page_index == 0{
+            image.encoder().write_tag(Tag::ImageDescription, description)?;
+        }
+        image.write_data(page)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_ome_tiff_round_trip(){
+    let data = ArrayD::from_shape_fn(IxDyn(&[3, 2, 4, 5]), |idx| (idx[0] * 1000 + idx[1] * 100 + idx[2] * 10 + idx[3]) as u16);
+    let spacing = vec![
+        Some(PixelSpacing{size: 2.0, unit: Some(PhysicalUnit::Space(SpaceUnit::Micrometer))}),
+        None,
+        Some(PixelSpacing{size: 0.5, unit: Some(PhysicalUnit::Space(SpaceUnit::Micrometer))}),
+        Some(PixelSpacing{size: 250.0, unit: Some(PhysicalUnit::Space(SpaceUnit::Nanometer))}),
+    ];
+    let image = TiffImage::new(NpyArray::from(data.clone()), "zcyx", spacing).unwrap();
+    let mut buffer = std::io::Cursor::new(Vec::<u8>::new());
+    image.write_ome_tiff(&mut buffer).unwrap();
+
+    buffer.rewind().unwrap();
+    let loaded = TiffImage::try_load(buffer).unwrap();
+    assert_eq!(loaded.axes(), "czyx");
+    let NpyArray::ArrayU16(loaded_data) = loaded.data() else { panic!("dtype changed") };
+    assert_eq!(loaded_data, &data.permuted_axes(IxDyn(&[1, 0, 2, 3])));
+    assert_eq!(loaded.pixel_spacing()[0], None);
+    assert_eq!(loaded.pixel_spacing()[1].unwrap().size, 2.0);
+    assert_eq!(loaded.pixel_spacing()[3].unwrap().unit, Some(PhysicalUnit::Space(SpaceUnit::Nanometer)));
+
+    let mask = TiffImage::new(NpyArray::from(ArrayD::<bool>::default(IxDyn(&[4, 5]))), "yx", vec![None, None]).unwrap();
+    assert!(matches!(
+        mask.write_ome_tiff(std::io::Cursor::new(Vec::<u8>::new())),
+        Err(TiffIoError::UnsupportedDataType(DataType::Bool))
+    ));
+}
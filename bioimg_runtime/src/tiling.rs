@@ -0,0 +1,425 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::{
+    AnyAxisSize, AxisId, AxisSizeReference, DataType, InputAxis, OutputAxis, ParameterizedAxisSize, TensorId
+};
+use ndarray::SliceInfoElem;
+
+use crate::npy_array::{ArcNpyArray, NpyArray};
+use crate::padding::PadMode;
+use crate::ModelInterface;
+
+#[derive(thiserror::Error, Debug)]
+pub enum TilingError{
+    #[error("No input with id '{0}'")]
+    NoSuchInput(TensorId),
+    #[error("Input '{tensor_id}' has {expected} axes but shape has {found} dimensions")]
+    MismatchedNumDimensions{tensor_id: TensorId, expected: usize, found: usize},
+    #[error("Extent {extent} of axis '{axis_id}' is incompatible with its size description")]
+    IncompatibleExtent{axis_id: AxisId, extent: usize},
+    #[error("No valid tile size for axis '{axis_id}' fits within {max_extent} while leaving room for a halo of {halo}")]
+    TileTooSmall{axis_id: AxisId, max_extent: usize, halo: usize},
+    #[error("Axis '{axis_id}' of output '{tensor_id}' can't be mapped back to the tiled input")]
+    UnmappedOutputAxis{tensor_id: TensorId, axis_id: AxisId},
+    #[error("Axis '{axis_id}' of output '{tensor_id}' has an odd offset ({offset}), so it can't be centered on its input")]
+    OddOffset{tensor_id: TensorId, axis_id: AxisId, offset: usize},
+    #[error("Scale ratio {ratio} maps axis '{axis_id}' of output '{tensor_id}' onto fractional coordinates")]
+    FractionalOutputRegion{tensor_id: TensorId, axis_id: AxisId, ratio: f64},
+    #[error("Tile output '{tensor_id}' has shape {found:?}, which is too small for the planned region")]
+    BadTileOutputShape{tensor_id: TensorId, found: Vec<usize>},
+    #[error("Missing output '{0}' for tile")]
+    MissingTileOutput(TensorId),
+}
+
+/// How a single input axis is split into tiles
+#[derive(Clone, Debug)]
+pub struct AxisTiling{
+    pub axis_id: AxisId,
+    /// Extent of the full, untiled input along this axis
+    pub extent: usize,
+    /// Extent of each input tile along this axis, including the halo on both sides
+    pub tile_extent: usize,
+    /// Context added before and after the core of each tile
+    pub halo: usize,
+    /// Extent of the region of each tile that ends up in the stitched output
+    pub core_extent: usize,
+    pub num_tiles: usize,
+}
+
+impl AxisTiling{
+    fn whole(axis_id: AxisId, extent: usize) -> Self{
+        Self{axis_id, extent, tile_extent: extent, halo: 0, core_extent: extent, num_tiles: 1}
+    }
+    fn core(&self, tile_index: usize) -> Range<usize>{
+        let start = tile_index * self.core_extent;
+        start..(start + self.core_extent).min(self.extent)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum OutputAxisMapping{
+    /// Output axis is not tiled, and every tile produces its full extent
+    Whole{extent: usize},
+    /// Output axis follows input axis `input_axis` with `ratio` output pixels per input pixel.
+    /// `crop` is the number of leading tile output pixels that precede the tile core
+    Mapped{input_axis: usize, ratio: f64, crop: usize},
+}
+
+#[derive(Clone, Debug)]
+struct OutputTiling{
+    tensor_id: TensorId,
+    mappings: Vec<OutputAxisMapping>,
+    shape: Vec<usize>,
+}
+
+/// A single input tile of a [TilingPlan]
+#[derive(Clone, Debug)]
+pub struct Tile{
+    /// Position of this tile in the grid of tiles, one entry per input axis
+    pub grid_position: Vec<usize>,
+    /// Region of the input covered by the tile, including halo. Might extend beyond the input
+    pub input_region: Vec<Range<isize>>,
+    /// Region of the input whose results are kept when stitching
+    pub core: Vec<Range<usize>>,
+}
+
+impl Tile{
+    /// Copies the region of `data` covered by this tile, making up values with `mode` where the tile extends beyond the data
+    pub fn extract_input(&self, data: &NpyArray, mode: PadMode) -> NpyArray{
+        data.padded_region(&self.input_region, mode)
+    }
+}
+
+/// Plan for running a model tile by tile over an input that is too large for a single forward pass.
+///
+/// Space and time axes with fixed or parameterized sizes are split into overlapping tiles, each grown by the
+/// halo that the outputs need to discard. Outputs must reference the tiled input's axes for their sizes
+/// (optionally with a halo) so that the core of each tile can be located in the stitched output. Other inputs
+/// of the model are not tiled and should be passed whole alongside every tile.
+#[derive(Clone, Debug)]
+pub struct TilingPlan{
+    input_id: TensorId,
+    axes: Vec<AxisTiling>,
+    outputs: Vec<OutputTiling>,
+}
+
+fn scaled(value: usize, ratio: f64) -> Option<usize>{
+    let scaled = value as f64 * ratio;
+    if (scaled - scaled.round()).abs() > 1e-6{
+        return None
+    }
+    Some(scaled.round() as usize)
+}
+
+fn smallest_valid_size(size: &ParameterizedAxisSize, at_least: usize) -> usize{
+    let (min, step) = (usize::from(size.min), usize::from(size.step));
+    if at_least <= min{
+        return min
+    }
+    min + (at_least - min).div_ceil(step) * step
+}
+
+fn largest_valid_size(size: &ParameterizedAxisSize, at_most: usize) -> Option<usize>{
+    let (min, step) = (usize::from(size.min), usize::from(size.step));
+    if at_most < min{
+        return None
+    }
+    Some(min + (at_most - min) / step * step)
+}
+
+impl TilingPlan{
+    /// Plans tiles for input `input_id` of `interface` with shape `input_shape`.
+    ///
+    /// `max_tile_shape` bounds the extent of each tile (halo included) along each input axis; tiles are made
+    /// as large as possible within those bounds. Axes that can't be tiled ignore it.
+    pub fn new<DATA: Borrow<NpyArray>>(
+        interface: &ModelInterface<DATA>,
+        input_id: &TensorId,
+        input_shape: &[usize],
+        max_tile_shape: &[usize],
+    ) -> Result<Self, TilingError>{
+        let Some(input_slot) = interface.inputs().iter().find(|slot| slot.tensor_meta.id == *input_id) else {
+            return Err(TilingError::NoSuchInput(input_id.clone()))
+        };
+        let input_axes: &[InputAxis] = input_slot.tensor_meta.axes();
+        for shape in [input_shape, max_tile_shape]{
+            if shape.len() != input_axes.len(){
+                return Err(TilingError::MismatchedNumDimensions{
+                    tensor_id: input_id.clone(), expected: input_axes.len(), found: shape.len()
+                })
+            }
+        }
+
+        struct OutputAxisRef{input_axis: usize, ratio: f64, offset: usize}
+        let mut output_refs: Vec<Vec<Option<OutputAxisRef>>> = vec![];
+        let mut halos = vec![0usize; input_axes.len()];
+        for slot in interface.outputs().iter(){
+            let tensor_id = &slot.tensor_meta.id;
+            let mut refs = vec![];
+            for axis in slot.tensor_meta.axes().iter(){
+                let is_spacetime = matches!(axis, OutputAxis::Space(_) | OutputAxis::Time(_));
+                let Some(AnyAxisSize::Reference(AxisSizeReference{qualified_axis_id, offset})) = axis.size().filter(|_| is_spacetime) else {
+                    refs.push(None);
+                    continue;
+                };
+                let halo = axis.halo().map(|halo| u64::from(halo) as usize).unwrap_or(0);
+                let input_axis = input_axes.iter().position(|inp_axis| inp_axis.id() == qualified_axis_id.axis_id);
+                let Some(input_axis) = input_axis.filter(|_| qualified_axis_id.tensor_id == *input_id) else {
+                    return Err(TilingError::UnmappedOutputAxis{tensor_id: tensor_id.clone(), axis_id: axis.id()})
+                };
+                if offset % 2 != 0{
+                    return Err(TilingError::OddOffset{tensor_id: tensor_id.clone(), axis_id: axis.id(), offset})
+                }
+                let ratio = f32::from(input_axes[input_axis].scale()) as f64 / f32::from(axis.scale()) as f64;
+                let context = ((halo as f64 - (offset / 2) as f64) / ratio).ceil().max(0.0) as usize;
+                halos[input_axis] = halos[input_axis].max(context);
+                refs.push(Some(OutputAxisRef{input_axis, ratio, offset}));
+            }
+            output_refs.push(refs);
+        }
+
+        let axes = input_axes.iter().zip(input_shape.iter().zip(max_tile_shape)).zip(&halos)
+            .map(|((axis, (&extent, &max_extent)), &halo)|{
+                let axis_id = axis.id();
+                let size = match axis{
+                    InputAxis::Space(axis) => axis.size.clone(),
+                    InputAxis::Time(axis) => axis.size.clone(),
+                    InputAxis::Channel(axis) => {
+                        if !axis.is_compatible_with_extent(extent){
+                            return Err(TilingError::IncompatibleExtent{axis_id, extent})
+                        }
+                        return Ok(AxisTiling::whole(axis_id, extent))
+                    },
+                    _ => return Ok(AxisTiling::whole(axis_id, extent)),
+                };
+                let tile_extent = match size{
+                    AnyAxisSize::Fixed(fixed) => usize::from(fixed),
+                    AnyAxisSize::Parameterized(size) => {
+                        let single_tile_extent = smallest_valid_size(&size, extent + 2 * halo);
+                        if single_tile_extent <= max_extent{
+                            single_tile_extent
+                        } else {
+                            largest_valid_size(&size, max_extent).unwrap_or(0)
+                        }
+                    },
+                    AnyAxisSize::Reference(_) => return Ok(AxisTiling::whole(axis_id, extent)),
+                };
+                if tile_extent <= 2 * halo{
+                    return Err(TilingError::TileTooSmall{axis_id, max_extent, halo})
+                }
+                let core_extent = tile_extent - 2 * halo;
+                Ok(AxisTiling{axis_id, extent, tile_extent, halo, core_extent, num_tiles: extent.div_ceil(core_extent).max(1)})
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let num_tiles: usize = axes.iter().map(|axis| axis.num_tiles).product();
+
+        let outputs = interface.outputs().iter().zip(output_refs)
+            .map(|(slot, refs)|{
+                let tensor_id = &slot.tensor_meta.id;
+                let mut output = OutputTiling{tensor_id: tensor_id.clone(), mappings: vec![], shape: vec![]};
+                for (axis, axis_ref) in slot.tensor_meta.axes().iter().zip(refs){
+                    let axis_id = axis.id();
+                    let (mapping, extent) = match (axis, axis_ref){
+                        (_, Some(OutputAxisRef{input_axis, ratio, offset})) => {
+                            let tiling = &axes[input_axis];
+                            let fractional = || TilingError::FractionalOutputRegion{
+                                tensor_id: tensor_id.clone(), axis_id: axis_id.clone(), ratio
+                            };
+                            let extent = scaled(tiling.extent, ratio).ok_or_else(fractional)?;
+                            scaled(tiling.core_extent, ratio).ok_or_else(fractional)?;
+                            // tiles carry the largest context needed by any output; untiled axes carry none
+                            let crop = scaled(tiling.halo, ratio).ok_or_else(fractional)? + offset / 2;
+                            (OutputAxisMapping::Mapped{input_axis, ratio, crop}, extent)
+                        },
+                        (OutputAxis::Batch(_), None) => {
+                            let batch_extent = input_axes.iter().position(|axis| matches!(axis, InputAxis::Batch(_)))
+                                .map(|batch_axis| input_shape[batch_axis])
+                                .unwrap_or(1);
+                            (OutputAxisMapping::Whole{extent: batch_extent}, batch_extent)
+                        },
+                        (axis, None) => match axis.size(){
+                            Some(AnyAxisSize::Fixed(size)) if num_tiles == 1 || !matches!(axis, OutputAxis::Space(_) | OutputAxis::Time(_)) => {
+                                (OutputAxisMapping::Whole{extent: size.into()}, size.into())
+                            },
+                            _ => return Err(TilingError::UnmappedOutputAxis{tensor_id: tensor_id.clone(), axis_id}),
+                        },
+                    };
+                    output.mappings.push(mapping);
+                    output.shape.push(extent);
+                }
+                Ok(output)
+            })
+            .collect::<Result<Vec<_>, TilingError>>()?;
+
+        Ok(Self{input_id: input_id.clone(), axes, outputs})
+    }
+
+    pub fn input_id(&self) -> &TensorId{
+        &self.input_id
+    }
+
+    pub fn axes(&self) -> &[AxisTiling]{
+        &self.axes
+    }
+
+    pub fn num_tiles(&self) -> usize{
+        self.axes.iter().map(|axis| axis.num_tiles).product()
+    }
+
+    /// Shape of the stitched output `tensor_id`
+    pub fn output_shape(&self, tensor_id: &TensorId) -> Option<&[usize]>{
+        self.outputs.iter().find(|output| output.tensor_id == *tensor_id).map(|output| output.shape.as_slice())
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item=Tile> + '_{
+        (0..self.num_tiles()).map(|mut flat_index|{
+            let mut grid_position = vec![0; self.axes.len()];
+            for (axis_idx, axis) in self.axes.iter().enumerate().rev(){
+                grid_position[axis_idx] = flat_index % axis.num_tiles;
+                flat_index /= axis.num_tiles;
+            }
+            let core: Vec<Range<usize>> = self.axes.iter().zip(&grid_position)
+                .map(|(axis, tile_index)| axis.core(*tile_index))
+                .collect();
+            let input_region = self.axes.iter().zip(&core)
+                .map(|(axis, core)|{
+                    let start = core.start as isize - axis.halo as isize;
+                    start..start + axis.tile_extent as isize
+                })
+                .collect();
+            Tile{grid_position, input_region, core}
+        })
+    }
+
+    pub fn stitcher(&self) -> Stitcher<'_>{
+        Stitcher{
+            plan: self,
+            outputs: self.outputs.iter()
+                .map(|output| (output.tensor_id.clone(), None))
+                .collect(),
+        }
+    }
+}
+
+/// Assembles the outputs of every [Tile] of a [TilingPlan] into full-size outputs
+pub struct Stitcher<'plan>{
+    plan: &'plan TilingPlan,
+    /// Stitched outputs, allocated with the element type of the first tile output
+    outputs: HashMap<TensorId, Option<NpyArray>>,
+}
+
+impl Stitcher<'_>{
+    /// Copies the core of each of the model outputs for `tile` into the stitched outputs
+    pub fn push(&mut self, tile: &Tile, tile_outputs: &HashMap<TensorId, ArcNpyArray>) -> Result<(), TilingError>{
+        for output in &self.plan.outputs{
+            let tensor_id = &output.tensor_id;
+            let tile_output = tile_outputs.get(tensor_id).ok_or_else(|| TilingError::MissingTileOutput(tensor_id.clone()))?;
+            let bad_shape = || TilingError::BadTileOutputShape{tensor_id: tensor_id.clone(), found: tile_output.shape().to_owned()};
+            if tile_output.shape().len() != output.mappings.len(){
+                return Err(bad_shape())
+            }
+
+            let mut source_slices = vec![];
+            let mut dest_slices = vec![];
+            for (mapping, &tile_output_extent) in output.mappings.iter().zip(tile_output.shape()){
+                let (source, dest) = match *mapping{
+                    OutputAxisMapping::Whole{extent} => (0..extent, 0..extent),
+                    OutputAxisMapping::Mapped{input_axis, ratio, crop} => {
+                        let core = &tile.core[input_axis];
+                        let start = (core.start as f64 * ratio).round() as usize;
+                        let end = (core.end as f64 * ratio).round() as usize;
+                        (crop..crop + (end - start), start..end)
+                    },
+                };
+                if source.end > tile_output_extent{
+                    return Err(bad_shape())
+                }
+                source_slices.push(SliceInfoElem::from(source));
+                dest_slices.push(SliceInfoElem::from(dest));
+            }
+
+            let stitched = self.outputs.get_mut(tensor_id).unwrap()
+                .get_or_insert_with(|| NpyArray::zeros(&output.shape, tile_output.dtype()));
+            stitched.assign_slice(&dest_slices, &tile_output.slice(&source_slices));
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> HashMap<TensorId, ArcNpyArray>{
+        self.outputs.into_iter()
+            .map(|(tensor_id, stitched)|{
+                let stitched = stitched.unwrap_or_else(|| {
+                    let shape = &self.plan.outputs.iter().find(|output| output.tensor_id == tensor_id).unwrap().shape;
+                    NpyArray::zeros(shape, DataType::default())
+                });
+                (tensor_id, Arc::new(stitched))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_tiled_identity_model_reproduces_input(){
+    use crate::model_interface::{InputSlot, OutputSlot};
+    use ndarray::{ArrayD, IxDyn};
+
+    let input_meta = serde_json::from_value(serde_json::json!({
+        "id": "raw",
+        "axes": [
+            {"type": "batch"},
+            {"type": "channel", "channel_names": ["c0"]},
+            {"type": "space", "id": "y", "size": {"min": 16, "step": 8}},
+            {"type": "space", "id": "x", "size": {"min": 16, "step": 8}},
+        ],
+    })).unwrap();
+    let output_meta = serde_json::from_value(serde_json::json!({
+        "id": "out",
+        "axes": [
+            {"type": "batch"},
+            {"type": "channel", "channel_names": ["c0"]},
+            {"type": "space", "id": "y", "size": {"tensor_id": "raw", "axis_id": "y"}, "halo": 4},
+            {"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x"}, "halo": 4},
+        ],
+    })).unwrap();
+    let test_tensor = Arc::new(NpyArray::from(ArrayD::<f32>::zeros(IxDyn(&[1, 1, 16, 16]))));
+    let interface = ModelInterface::try_build(
+        vec![InputSlot{tensor_meta: input_meta, test_tensor: Arc::clone(&test_tensor)}],
+        vec![OutputSlot{tensor_meta: output_meta, test_tensor}],
+    ).unwrap();
+
+    let input_id = TensorId::try_from("raw").unwrap();
+    let output_id = TensorId::try_from("out").unwrap();
+    let data = NpyArray::from(ArrayD::from_shape_fn(IxDyn(&[1, 1, 100, 50]), |idx| (idx[2] * 1000 + idx[3]) as f32));
+    let plan = TilingPlan::new(&interface, &input_id, data.shape(), &[1, 1, 40, 64]).unwrap();
+    assert_eq!(plan.axes()[2].tile_extent, 40);
+    assert_eq!(plan.axes()[2].num_tiles, 4);
+    assert_eq!(plan.axes()[3].tile_extent, 64);
+    assert_eq!(plan.axes()[3].num_tiles, 1);
+
+    let mut stitcher = plan.stitcher();
+    for tile in plan.tiles(){
+        let tile_input = tile.extract_input(&data, PadMode::Reflect);
+        stitcher.push(&tile, &HashMap::from([(output_id.clone(), Arc::new(tile_input))])).unwrap();
+    }
+    let stitched = stitcher.finish();
+    assert_eq!(plan.output_shape(&output_id).unwrap(), data.shape());
+    assert_eq!(stitched[&output_id].to_f32(), data.to_f32());
+
+    // tiles and stitched outputs keep the element type of the data, so large integers survive unchanged
+    let big = 1i64 << 53;
+    let data = ArrayD::from_shape_fn(IxDyn(&[1, 1, 100, 50]), |idx| big + (idx[2] * 1000 + idx[3]) as i64);
+    let data = NpyArray::from(data);
+    let mut stitcher = plan.stitcher();
+    for tile in plan.tiles(){
+        let tile_input = tile.extract_input(&data, PadMode::Constant(0.0));
+        assert_eq!(tile_input.dtype(), DataType::Int64);
+        stitcher.push(&tile, &HashMap::from([(output_id.clone(), Arc::new(tile_input))])).unwrap();
+    }
+    let (NpyArray::ArrayI64(stitched), NpyArray::ArrayI64(expected)) = (&*stitcher.finish()[&output_id], &data) else {
+        panic!("Expected i64 arrays")
+    };
+    assert_eq!(stitched, expected);
+}
@@ -0,0 +1,638 @@
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use bioimg_spec::rdf::model::{AxisType, DataType, SpaceUnit, TimeUnit};
+use ndarray::{ArrayD, IxDyn, ShapeBuilder, Slice};
+
+use crate::npy_array::NpyArray;
+use crate::resampling::{PhysicalUnit, PixelSpacing};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ZarrError{
+    #[error("Could not read {path}: {source}")]
+    IoError{path: PathBuf, source: std::io::Error},
+    #[error("Bad metadata in {path}: {reason}")]
+    BadMetadata{path: PathBuf, reason: String},
+    #[error("Unsupported zarr data type '{0}'")]
+    UnsupportedDataType(String),
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodec(String),
+    #[error("Could not decode chunk {path}: {reason}")]
+    BadChunk{path: PathBuf, reason: String},
+    #[error("Region {region:?} is out of bounds for array of shape {shape:?}")]
+    BadRegion{region: Vec<Range<usize>>, shape: Vec<usize>},
+    #[error("No multiscale level #{0}")]
+    NoSuchLevel(usize),
+}
+
+fn read_json(path: &Path) -> Result<serde_json::Value, ZarrError>{
+    let contents = std::fs::read(path).map_err(|source| ZarrError::IoError{path: path.to_owned(), source})?;
+    serde_json::from_slice(&contents).map_err(|e| ZarrError::BadMetadata{path: path.to_owned(), reason: e.to_string()})
+}
+
+/// Decoding of the elements of a chunk from their on-disk bytes
+trait ZarrElement: Copy{
+    const SIZE: usize;
+    fn from_bytes(bytes: &[u8], big_endian: bool) -> Self;
+    fn from_fill_value(value: &serde_json::Value) -> Option<Self>;
+}
+
+impl ZarrElement for bool{
+    const SIZE: usize = 1;
+    fn from_bytes(bytes: &[u8], _big_endian: bool) -> Self{
+        bytes[0] != 0
+    }
+    fn from_fill_value(value: &serde_json::Value) -> Option<Self>{
+        value.as_bool()
+    }
+}
+
+macro_rules! impl_ZarrElement_for_int {( $($element_type:ident),+ ) => {
+    $(
+        impl ZarrElement for $element_type{
+            const SIZE: usize = std::mem::size_of::<$element_type>();
+            fn from_bytes(bytes: &[u8], big_endian: bool) -> Self{
+                let bytes = bytes.try_into().expect("Element should have the right number of bytes");
+                if big_endian { Self::from_be_bytes(bytes) } else { Self::from_le_bytes(bytes) }
+            }
+            fn from_fill_value(value: &serde_json::Value) -> Option<Self>{
+                value.as_i64().map(|v| v as Self).or_else(|| value.as_u64().map(|v| v as Self))
+            }
+        }
+    )+
+}}
+
+macro_rules! impl_ZarrElement_for_float {( $($element_type:ident),+ ) => {
+    $(
+        impl ZarrElement for $element_type{
+            const SIZE: usize = std::mem::size_of::<$element_type>();
+            fn from_bytes(bytes: &[u8], big_endian: bool) -> Self{
+                let bytes = bytes.try_into().expect("Element should have the right number of bytes");
+                if big_endian { Self::from_be_bytes(bytes) } else { Self::from_le_bytes(bytes) }
+            }
+            fn from_fill_value(value: &serde_json::Value) -> Option<Self>{
+                match value.as_str(){
+                    Some("NaN") => Some(Self::NAN),
+                    Some("Infinity") => Some(Self::INFINITY),
+                    Some("-Infinity") => Some(Self::NEG_INFINITY),
+                    _ => value.as_f64().map(|v| v as Self),
+                }
+            }
+        }
+    )+
+}}
+
+impl_ZarrElement_for_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+impl_ZarrElement_for_float!(f32, f64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BloscCodec{
+    BloscLz,
+    Lz4,
+    Snappy,
+    Zlib,
+    Zstd,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Compressor{
+    Raw,
+    Zlib,
+    Gzip,
+    Blosc,
+}
+
+fn decode_lz4_block(input: &[u8], expected_len: usize) -> Result<Vec<u8>, String>{
+    let mut output: Vec<u8> = Vec::with_capacity(expected_len);
+    let mut pos = 0;
+    let read_byte = |pos: &mut usize| -> Result<u8, String>{
+        let byte = *input.get(*pos).ok_or("Truncated lz4 block")?;
+        *pos += 1;
+        Ok(byte)
+    };
+    loop{
+        let token = read_byte(&mut pos)?;
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15{
+            loop{
+                let extra = read_byte(&mut pos)?;
+                literal_len += extra as usize;
+                if extra != 255 { break }
+            }
+        }
+        let literals = input.get(pos..pos + literal_len).ok_or("Truncated lz4 literals")?;
+        output.extend_from_slice(literals);
+        pos += literal_len;
+        if pos == input.len(){
+            break
+        }
+        let offset = u16::from_le_bytes([read_byte(&mut pos)?, read_byte(&mut pos)?]) as usize;
+        if offset == 0 || offset > output.len(){
+            return Err(format!("Bad lz4 match offset {offset}"))
+        }
+        let mut match_len = (token & 0xF) as usize;
+        if match_len == 15{
+            loop{
+                let extra = read_byte(&mut pos)?;
+                match_len += extra as usize;
+                if extra != 255 { break }
+            }
+        }
+        match_len += 4;
+        // matches may overlap with the bytes they produce, so they are copied one at a time
+        let match_start = output.len() - offset;
+        for idx in match_start..match_start + match_len{
+            output.push(output[idx]);
+        }
+        if output.len() > expected_len{
+            return Err("lz4 block decodes into too many bytes".to_owned())
+        }
+    }
+    if output.len() != expected_len{
+        return Err(format!("Expected {expected_len} bytes from lz4 block, found {}", output.len()))
+    }
+    Ok(output)
+}
+
+fn inflate(data: &[u8], gzip: bool) -> Result<Vec<u8>, String>{
+    let mut out = vec![];
+    let result = if gzip {
+        flate2::read::GzDecoder::new(data).read_to_end(&mut out)
+    } else {
+        flate2::read::ZlibDecoder::new(data).read_to_end(&mut out)
+    };
+    result.map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// Decodes a buffer produced by c-blosc 1.x. Only the lz4 and zlib internal codecs and byte shuffling are supported
+fn decode_blosc(data: &[u8]) -> Result<Vec<u8>, ZarrError>{
+    const BYTE_SHUFFLE: u8 = 0x1;
+    const MEMCPYED: u8 = 0x2;
+    const BIT_SHUFFLE: u8 = 0x4;
+    const DONT_SPLIT: u8 = 0x10;
+    const MAX_SPLITS: usize = 16;
+    const MIN_BUFFERSIZE: usize = 128;
+
+    let bad = |reason: &str| ZarrError::UnsupportedCodec(format!("blosc: {reason}"));
+    let read_u32 = |offset: usize| -> Result<usize, ZarrError>{
+        let bytes = data.get(offset..offset + 4).ok_or_else(|| bad("truncated buffer"))?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    if data.len() < 16{
+        return Err(bad("truncated header"))
+    }
+    let flags = data[2];
+    let typesize = (data[3] as usize).max(1);
+    let nbytes = read_u32(4)?;
+    let blocksize = read_u32(8)?;
+    if flags & MEMCPYED != 0{
+        return data.get(16..16 + nbytes).map(|d| d.to_vec()).ok_or_else(|| bad("truncated buffer"))
+    }
+    if flags & BIT_SHUFFLE != 0 && flags & BYTE_SHUFFLE == 0{
+        return Err(bad("bit shuffle is not supported"))
+    }
+    let codec = match flags >> 5{
+        0 => BloscCodec::BloscLz,
+        1 => BloscCodec::Lz4,
+        2 => BloscCodec::Snappy,
+        3 => BloscCodec::Zlib,
+        4 => BloscCodec::Zstd,
+        other => return Err(bad(&format!("unknown internal codec {other}"))),
+    };
+    if !matches!(codec, BloscCodec::Lz4 | BloscCodec::Zlib){
+        return Err(bad(&format!("internal codec {codec:?} is not supported")))
+    }
+    if blocksize == 0{
+        return Err(bad("block size is 0"))
+    }
+
+    let mut out = Vec::with_capacity(nbytes);
+    let num_blocks = nbytes.div_ceil(blocksize);
+    for block_idx in 0..num_blocks{
+        let block_len = blocksize.min(nbytes - block_idx * blocksize);
+        let is_leftover = block_len != blocksize;
+        let num_splits = if flags & DONT_SPLIT == 0 && typesize <= MAX_SPLITS && blocksize / typesize >= MIN_BUFFERSIZE && !is_leftover {
+            typesize
+        } else {
+            1
+        };
+        let split_len = block_len / num_splits;
+        let mut block = Vec::with_capacity(block_len);
+        let mut pos = read_u32(16 + 4 * block_idx)?;
+        for _ in 0..num_splits{
+            let compressed_len = read_u32(pos)?;
+            pos += 4;
+            let compressed = data.get(pos..pos + compressed_len).ok_or_else(|| bad("truncated block"))?;
+            pos += compressed_len;
+            if compressed_len == split_len{
+                block.extend_from_slice(compressed);
+                continue
+            }
+            let decoded = match codec{
+                BloscCodec::Lz4 => decode_lz4_block(compressed, split_len),
+                _ => inflate(compressed, false),
+            }.map_err(|e| bad(&e))?;
+            block.extend(decoded);
+        }
+        if block.len() != block_len{
+            return Err(bad("block decodes into the wrong number of bytes"))
+        }
+        if flags & BYTE_SHUFFLE != 0 && typesize > 1{
+            let num_elements = block_len / typesize;
+            let mut unshuffled = block.clone();
+            for element in 0..num_elements{
+                for byte in 0..typesize{
+                    unshuffled[element * typesize + byte] = block[byte * num_elements + element];
+                }
+            }
+            block = unshuffled;
+        }
+        out.extend(block);
+    }
+    Ok(out)
+}
+
+/// A single array in a local zarr v2 store
+#[derive(Clone, Debug)]
+pub struct ZarrArray{
+    path: PathBuf,
+    shape: Vec<usize>,
+    chunks: Vec<usize>,
+    dtype: DataType,
+    big_endian: bool,
+    fortran_order: bool,
+    fill_value: serde_json::Value,
+    compressor: Compressor,
+    dimension_separator: String,
+}
+
+impl ZarrArray{
+    /// Reads the `.zarray` metadata in directory `path`. No chunk data is read until [Self::read_region] is called
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ZarrError>{
+        let path = path.as_ref().to_owned();
+        let metadata_path = path.join(".zarray");
+        let metadata = read_json(&metadata_path)?;
+        let bad_metadata = |reason: &str| ZarrError::BadMetadata{path: metadata_path.clone(), reason: reason.to_owned()};
+        let usize_list = |key: &str| -> Result<Vec<usize>, ZarrError>{
+            metadata[key].as_array()
+                .ok_or_else(|| bad_metadata(&format!("missing '{key}'")))?
+                .iter()
+                .map(|v| v.as_u64().map(|v| v as usize).ok_or_else(|| bad_metadata(&format!("bad '{key}'"))))
+                .collect()
+        };
+        let shape = usize_list("shape")?;
+        let chunks = usize_list("chunks")?;
+        if chunks.len() != shape.len() || chunks.contains(&0){
+            return Err(bad_metadata("bad chunk shape"))
+        }
+
+        let dtype_str = metadata["dtype"].as_str().ok_or_else(|| bad_metadata("missing 'dtype'"))?;
+        let unsupported_dtype = || ZarrError::UnsupportedDataType(dtype_str.to_owned());
+        let (byte_order, kind_and_size) = dtype_str.split_at(dtype_str.len().min(1));
+        let big_endian = match byte_order{
+            "<" | "|" => false,
+            ">" => true,
+            _ => return Err(unsupported_dtype()),
+        };
+        let dtype = match kind_and_size{
+            "b1" => DataType::Bool,
+            "u1" => DataType::Uint8,
+            "i1" => DataType::Int8,
+            "u2" => DataType::Uint16,
+            "i2" => DataType::Int16,
+            "u4" => DataType::Uint32,
+            "i4" => DataType::Int32,
+            "u8" => DataType::Uint64,
+            "i8" => DataType::Int64,
+            "f4" => DataType::Float32,
+            "f8" => DataType::Float64,
+            _ => return Err(unsupported_dtype()),
+        };
+
+        let fortran_order = match metadata["order"].as_str(){
+            Some("C") | None => false,
+            Some("F") => true,
+            Some(other) => return Err(bad_metadata(&format!("bad order '{other}'"))),
+        };
+        if metadata["filters"].as_array().is_some_and(|filters| !filters.is_empty()){
+            return Err(ZarrError::UnsupportedCodec(format!("filters {}", metadata["filters"])))
+        }
+        let compressor = match &metadata["compressor"]{
+            serde_json::Value::Null => Compressor::Raw,
+            compressor => match compressor["id"].as_str(){
+                Some("zlib") => Compressor::Zlib,
+                Some("gzip") => Compressor::Gzip,
+                Some("blosc") => Compressor::Blosc,
+                _ => return Err(ZarrError::UnsupportedCodec(compressor.to_string())),
+            },
+        };
+        let dimension_separator = metadata["dimension_separator"].as_str().unwrap_or(".").to_owned();
+        Ok(Self{
+            path,
+            shape,
+            chunks,
+            dtype,
+            big_endian,
+            fortran_order,
+            fill_value: metadata["fill_value"].clone(),
+            compressor,
+            dimension_separator,
+        })
+    }
+
+    pub fn shape(&self) -> &[usize]{
+        &self.shape
+    }
+
+    pub fn chunks(&self) -> &[usize]{
+        &self.chunks
+    }
+
+    pub fn dtype(&self) -> DataType{
+        self.dtype
+    }
+
+    fn chunk_path(&self, chunk_index: &[usize]) -> PathBuf{
+        if chunk_index.is_empty(){
+            return self.path.join("0")
+        }
+        let key: Vec<String> = chunk_index.iter().map(|idx| idx.to_string()).collect();
+        self.path.join(key.join(&self.dimension_separator))
+    }
+
+    /// Decoded contents of a chunk, or `None` if it was never written
+    fn read_chunk<T: ZarrElement>(&self, chunk_index: &[usize]) -> Result<Option<ArrayD<T>>, ZarrError>{
+        let path = self.chunk_path(chunk_index);
+        let raw = match std::fs::read(&path){
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(ZarrError::IoError{path, source}),
+        };
+        let bad_chunk = |reason: String| ZarrError::BadChunk{path: path.clone(), reason};
+        let bytes = match self.compressor{
+            Compressor::Raw => raw,
+            Compressor::Zlib => inflate(&raw, false).map_err(bad_chunk)?,
+            Compressor::Gzip => inflate(&raw, true).map_err(bad_chunk)?,
+            Compressor::Blosc => decode_blosc(&raw)?,
+        };
+        let num_elements: usize = self.chunks.iter().product();
+        if bytes.len() != num_elements * T::SIZE{
+            return Err(bad_chunk(format!("expected {} bytes, found {}", num_elements * T::SIZE, bytes.len())))
+        }
+        let values: Vec<T> = bytes.chunks_exact(T::SIZE).map(|b| T::from_bytes(b, self.big_endian)).collect();
+        let shape = IxDyn(&self.chunks).set_f(self.fortran_order);
+        ArrayD::from_shape_vec(shape, values).map(Some).map_err(|e| bad_chunk(e.to_string()))
+    }
+
+    fn read_region_as<T: ZarrElement + Default>(&self, region: &[Range<usize>]) -> Result<ArrayD<T>, ZarrError>{
+        let fill_value = T::from_fill_value(&self.fill_value).unwrap_or_default();
+        let out_shape: Vec<usize> = region.iter().map(|r| r.len()).collect();
+        let mut out = ArrayD::from_elem(IxDyn(&out_shape), fill_value);
+        if out.is_empty(){
+            return Ok(out)
+        }
+        let first_chunk: Vec<usize> = region.iter().zip(&self.chunks).map(|(r, chunk)| r.start / chunk).collect();
+        let num_chunks: Vec<usize> = region.iter().zip(&self.chunks).zip(&first_chunk)
+            .map(|((r, chunk), first)| r.end.div_ceil(*chunk) - first)
+            .collect();
+        for grid_offset in ndarray::indices(IxDyn(&num_chunks)){
+            let chunk_index: Vec<usize> = first_chunk.iter().enumerate().map(|(dim, first)| first + grid_offset[dim]).collect();
+            let Some(chunk) = self.read_chunk::<T>(&chunk_index)? else {
+                continue
+            };
+            // intersection of the chunk and the region, in array coordinates
+            let overlap: Vec<Range<usize>> = chunk_index.iter().zip(&self.chunks).zip(region)
+                .map(|((idx, chunk), r)| (idx * chunk).max(r.start)..((idx + 1) * chunk).min(r.end))
+                .collect();
+            let source = chunk.slice_each_axis(|ax|{
+                let (dim, chunk_start) = (ax.axis.index(), chunk_index[ax.axis.index()] * self.chunks[ax.axis.index()]);
+                Slice::from(overlap[dim].start - chunk_start..overlap[dim].end - chunk_start)
+            });
+            out.slice_each_axis_mut(|ax|{
+                let dim = ax.axis.index();
+                Slice::from(overlap[dim].start - region[dim].start..overlap[dim].end - region[dim].start)
+            }).assign(&source);
+        }
+        Ok(out)
+    }
+
+    /// Reads the elements within `region`, loading only the chunks that intersect it. Chunks missing from
+    /// the store are filled with the array's fill value.
+    pub fn read_region(&self, region: &[Range<usize>]) -> Result<NpyArray, ZarrError>{
+        let out_of_bounds = region.len() != self.shape.len() ||
+            region.iter().zip(&self.shape).any(|(r, extent)| r.start > r.end || r.end > *extent);
+        if out_of_bounds{
+            return Err(ZarrError::BadRegion{region: region.to_owned(), shape: self.shape.clone()})
+        }
+        Ok(match self.dtype{
+            DataType::Bool => self.read_region_as::<bool>(region)?.into(),
+            DataType::Uint8 => self.read_region_as::<u8>(region)?.into(),
+            DataType::Int8 => self.read_region_as::<i8>(region)?.into(),
+            DataType::Uint16 => self.read_region_as::<u16>(region)?.into(),
+            DataType::Int16 => self.read_region_as::<i16>(region)?.into(),
+            DataType::Uint32 => self.read_region_as::<u32>(region)?.into(),
+            DataType::Int32 => self.read_region_as::<i32>(region)?.into(),
+            DataType::Uint64 => self.read_region_as::<u64>(region)?.into(),
+            DataType::Int64 => self.read_region_as::<i64>(region)?.into(),
+            DataType::Float32 => self.read_region_as::<f32>(region)?.into(),
+            DataType::Float64 => self.read_region_as::<f64>(region)?.into(),
+        })
+    }
+
+    /// Reads the whole array into memory
+    pub fn read_all(&self) -> Result<NpyArray, ZarrError>{
+        let region: Vec<Range<usize>> = self.shape.iter().map(|extent| 0..*extent).collect();
+        self.read_region(&region)
+    }
+}
+
+/// An axis of an OME-NGFF multiscale image
+#[derive(Clone, Debug, PartialEq)]
+pub struct NgffAxis{
+    pub name: String,
+    /// Axes with no or a custom type in the metadata are reported as index axes
+    pub axis_type: AxisType,
+    pub unit: Option<PhysicalUnit>,
+}
+
+impl NgffAxis{
+    fn from_json(value: &serde_json::Value) -> Option<Self>{
+        // versions up to 0.3 list axes by name only
+        if let Some(name) = value.as_str(){
+            let axis_type = match name{
+                "t" => AxisType::Time,
+                "c" => AxisType::Channel,
+                _ => AxisType::Space,
+            };
+            return Some(Self{name: name.to_owned(), axis_type, unit: None})
+        }
+        let name = value["name"].as_str()?.to_owned();
+        let unit = value["unit"].clone();
+        let (axis_type, unit) = match value["type"].as_str(){
+            Some("space") => (AxisType::Space, serde_json::from_value::<SpaceUnit>(unit).ok().map(PhysicalUnit::Space)),
+            Some("time") => (AxisType::Time, serde_json::from_value::<TimeUnit>(unit).ok().map(PhysicalUnit::Time)),
+            Some("channel") => (AxisType::Channel, None),
+            _ => (AxisType::Index, None),
+        };
+        Some(Self{name, axis_type, unit})
+    }
+}
+
+/// One resolution level of an OME-NGFF multiscale image
+#[derive(Clone, Debug)]
+pub struct MultiscaleLevel{
+    /// Path of the level's array, relative to the image root
+    pub path: String,
+    /// Physical size of a pixel along each axis, in the axis unit
+    pub scale: Vec<f64>,
+}
+
+/// A multiscale image in a local OME-Zarr (OME-NGFF) store
+#[derive(Clone, Debug)]
+pub struct OmeZarr{
+    root: PathBuf,
+    axes: Vec<NgffAxis>,
+    levels: Vec<MultiscaleLevel>,
+}
+
+impl OmeZarr{
+    /// Reads the first multiscale image described in the `.zattrs` of `root`. Translations are ignored.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, ZarrError>{
+        let root = root.as_ref().to_owned();
+        let attrs_path = root.join(".zattrs");
+        let attrs = read_json(&attrs_path)?;
+        let bad_metadata = |reason: &str| ZarrError::BadMetadata{path: attrs_path.clone(), reason: reason.to_owned()};
+        let multiscale = &attrs["multiscales"][0];
+        if multiscale.is_null(){
+            return Err(bad_metadata("no multiscales"))
+        }
+        let scale_of = |transforms: &serde_json::Value| -> Option<Vec<f64>>{
+            transforms.as_array()?.iter()
+                .find(|transform| transform["type"] == "scale")?["scale"]
+                .as_array()?
+                .iter()
+                .map(|v| v.as_f64())
+                .collect()
+        };
+        let global_scale = scale_of(&multiscale["coordinateTransformations"]);
+
+        let datasets = multiscale["datasets"].as_array().ok_or_else(|| bad_metadata("no datasets"))?;
+        let mut levels = vec![];
+        for dataset in datasets{
+            let path = dataset["path"].as_str().ok_or_else(|| bad_metadata("dataset has no path"))?.to_owned();
+            let mut scale = scale_of(&dataset["coordinateTransformations"]).unwrap_or_default();
+            if let Some(global_scale) = &global_scale{
+                scale = scale.iter().zip(global_scale).map(|(a, b)| a * b).collect();
+            }
+            levels.push(MultiscaleLevel{path, scale});
+        }
+
+        let axes = match multiscale["axes"].as_array(){
+            Some(axes) => axes.iter()
+                .map(|axis| NgffAxis::from_json(axis).ok_or_else(|| bad_metadata(&format!("bad axis {axis}"))))
+                .collect::<Result<Vec<_>, _>>()?,
+            // versions before 0.3 are always 5D
+            None => ["t", "c", "z", "y", "x"].iter().filter_map(|name| NgffAxis::from_json(&serde_json::json!(name))).collect(),
+        };
+        for level in &mut levels{
+            if level.scale.is_empty(){
+                level.scale = vec![1.0; axes.len()];
+            }
+            if level.scale.len() != axes.len(){
+                return Err(bad_metadata(&format!("scale of level '{}' doesn't match the number of axes", level.path)))
+            }
+        }
+        Ok(Self{root, axes, levels})
+    }
+
+    pub fn axes(&self) -> &[NgffAxis]{
+        &self.axes
+    }
+
+    /// Resolution levels, from highest to lowest resolution
+    pub fn levels(&self) -> &[MultiscaleLevel]{
+        &self.levels
+    }
+
+    pub fn open_level(&self, level: usize) -> Result<ZarrArray, ZarrError>{
+        let level = self.levels.get(level).ok_or(ZarrError::NoSuchLevel(level))?;
+        ZarrArray::open(self.root.join(&level.path))
+    }
+
+    /// Pixel spacing of each space and time axis at resolution `level`
+    pub fn pixel_spacing(&self, level: usize) -> Result<Vec<Option<PixelSpacing>>, ZarrError>{
+        let level = self.levels.get(level).ok_or(ZarrError::NoSuchLevel(level))?;
+        Ok(self.axes.iter().zip(&level.scale)
+            .map(|(axis, size)| match axis.axis_type{
+                AxisType::Space | AxisType::Time => Some(PixelSpacing{size: *size, unit: axis.unit}),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn test_read_ome_zarr_regions(){
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, contents: &[u8]|{
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    write(".zattrs", serde_json::json!({"multiscales": [{
+        "version": "0.4",
+        "axes": [{"name": "y", "type": "space", "unit": "micrometer"}, {"name": "x", "type": "space", "unit": "micrometer"}],
+        "datasets": [
+            {"path": "0", "coordinateTransformations": [{"type": "scale", "scale": [0.5, 0.5]}]},
+            {"path": "1", "coordinateTransformations": [{"type": "scale", "scale": [1.0, 1.0]}]},
+        ],
+    }]}).to_string().as_bytes());
+
+    // level 0: 3x5 u16 in 2x3 zlib chunks, with chunk (1, 1) never written
+    write("0/.zarray", serde_json::json!({
+        "zarr_format": 2, "shape": [3, 5], "chunks": [2, 3], "dtype": "<u2", "order": "C",
+        "compressor": {"id": "zlib", "level": 1}, "fill_value": 7, "filters": null,
+    }).to_string().as_bytes());
+    for (chunk_row, chunk_col) in [(0, 0), (0, 1), (1, 0)]{
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::fast());
+        for row in chunk_row * 2..chunk_row * 2 + 2{
+            for col in chunk_col * 3..chunk_col * 3 + 3{
+                encoder.write_all(&((row * 10 + col) as u16).to_le_bytes()).unwrap();
+            }
+        }
+        write(&format!("0/{chunk_row}.{chunk_col}"), &encoder.finish().unwrap());
+    }
+
+    // level 1: a single 2x3 blosc chunk, lz4-compressed and byte-shuffled
+    write("1/.zarray", serde_json::json!({
+        "zarr_format": 2, "shape": [2, 3], "chunks": [2, 3], "dtype": "<u2", "order": "C",
+        "compressor": {"id": "blosc", "cname": "lz4", "clevel": 5, "shuffle": 1}, "fill_value": 0, "filters": null,
+    }).to_string().as_bytes());
+    let shuffled: Vec<u8> = [0u8, 2, 4, 20, 22, 24, 1, 1, 1, 1, 1, 1].to_vec();
+    let mut lz4_block = vec![(shuffled.len() as u8) << 4];
+    lz4_block.extend(&shuffled);
+    let mut blosc = vec![2u8, 1, 0x1 | (1 << 5), 2];
+    for value in [12u32, 12, (16 + 4 + 4 + lz4_block.len()) as u32, 16 + 4]{
+        blosc.extend(value.to_le_bytes());
+    }
+    blosc.extend((lz4_block.len() as u32).to_le_bytes());
+    blosc.extend(&lz4_block);
+    write("1/0.0", &blosc);
+
+    let image = OmeZarr::open(dir.path()).unwrap();
+    assert_eq!(image.axes()[1].unit, Some(PhysicalUnit::Space(SpaceUnit::Micrometer)));
+    assert_eq!(image.pixel_spacing(0).unwrap()[0].unwrap().size, 0.5);
+
+    let level0 = image.open_level(0).unwrap();
+    let NpyArray::ArrayU16(region) = level0.read_region(&[1..3, 2..5]).unwrap() else { panic!("wrong dtype") };
+    assert_eq!(region, ndarray::array![[12u16, 13, 14], [22, 7, 7]].into_dyn());
+
+    let NpyArray::ArrayU16(level1) = image.open_level(1).unwrap().read_all().unwrap() else { panic!("wrong dtype") };
+    assert_eq!(level1, ndarray::array![[256u16, 258, 260], [276, 278, 280]].into_dyn());
+
+    assert!(matches!(level0.read_region(&[0..4, 0..1]), Err(ZarrError::BadRegion{..})));
+}
@@ -1,6 +1,8 @@
 use std::{fmt::{Debug, Display}, io::{Read, Seek}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
 
 use bioimg_spec::rdf;
+
+use crate::download_cache::{DownloadCache, DownloadError};
 // use zip::{read::ZipFile, ZipArchive};
 
 
@@ -9,6 +11,13 @@ impl<T: Seek + Read + Send> SeekReadSend for T{}
 
 type AnyZipArchive = zip::ZipArchive<Box<dyn SeekReadSend + 'static>>;
 
+/// Where the entries of a model package actually live
+enum PackageBacking{
+    Zip(Mutex<AnyZipArchive>),
+    /// An unpacked package, with entry names relative to this directory
+    Dir(PathBuf),
+}
+
 #[derive(Clone, Debug)]
 pub enum ZipArchiveIdentifier{
     Path(PathBuf),
@@ -27,7 +36,9 @@ impl Display for ZipArchiveIdentifier{
 #[derive(Clone)]
 pub struct SharedZipArchive{
     identif: ZipArchiveIdentifier,
-    archive: Arc<Mutex<AnyZipArchive>>,
+    archive: Arc<PackageBacking>,
+    /// Used to resolve url references found in this archive's rdf
+    download_cache: Option<Arc<DownloadCache>>,
 }
 
 impl Debug for SharedZipArchive{
@@ -60,38 +71,129 @@ impl SharedZipArchive{
     pub fn identifier(&self) -> &ZipArchiveIdentifier{
         &self.identif
     }
+    /// Opens a zip file or, if `p` is a directory, an unpacked package
     pub fn open<P: AsRef<Path>>(p: P) -> Result<Self, ZipArchiveOpenError>{
+        if p.as_ref().is_dir(){
+            return Ok(Self::open_dir(p))
+        }
         let file: Box<dyn SeekReadSend + 'static> = Box::new(std::fs::File::open(p.as_ref())?);
         let archive: AnyZipArchive = zip::ZipArchive::new(file)?;
         Ok(Self{
             identif: ZipArchiveIdentifier::Path(p.as_ref().to_owned()),
-            archive: Arc::new(Mutex::new(archive))
+            archive: Arc::new(PackageBacking::Zip(Mutex::new(archive))),
+            download_cache: None,
         })
     }
+    /// Treats the contents of directory `dir` as the entries of a package
+    pub fn open_dir<P: AsRef<Path>>(dir: P) -> Self{
+        Self{
+            identif: ZipArchiveIdentifier::Path(dir.as_ref().to_owned()),
+            archive: Arc::new(PackageBacking::Dir(dir.as_ref().to_owned())),
+            download_cache: None,
+        }
+    }
     pub fn new(identif: ZipArchiveIdentifier, archive: AnyZipArchive) -> Self{
-        Self{identif, archive: Arc::new(Mutex::new(archive))}
+        Self{identif, archive: Arc::new(PackageBacking::Zip(Mutex::new(archive))), download_cache: None}
+    }
+    /// The directory backing this package, if it is not a zip file
+    pub fn directory(&self) -> Option<&Path>{
+        match self.archive.as_ref(){
+            PackageBacking::Zip(_) => None,
+            PackageBacking::Dir(dir) => Some(dir),
+        }
+    }
+    /// The path on disk of entry `name` of a directory-backed package
+    pub fn entry_path(&self, name: &str) -> Option<PathBuf>{
+        let dir = self.directory()?;
+        let mut path = dir.to_owned();
+        for component in name.split('/'){
+            if component.is_empty() || component == "." || component == ".." || component.contains('\\'){
+                return None
+            }
+            path.push(component);
+        }
+        Some(path)
+    }
+    pub fn with_download_cache(self, download_cache: Arc<DownloadCache>) -> Self{
+        Self{download_cache: Some(download_cache), ..self}
+    }
+    pub fn download_cache(&self) -> Option<&Arc<DownloadCache>>{
+        self.download_cache.as_ref()
+    }
+    /// Path to a local copy of `url`, fetched through this archive's download cache
+    pub fn fetch_url(&self, url: &rdf::HttpUrl, sha256: Option<&rdf::Sha256>) -> Result<PathBuf, RdfFileReferenceReadError>{
+        let Some(download_cache) = &self.download_cache else {
+            return Err(RdfFileReferenceReadError::UrlFileReferenceNotSupportedYet)
+        };
+        Ok(download_cache.resolve(url, sha256)?)
     }
     pub fn with_entry<F, Out>(&self, name: &str, entry_reader: F) -> Result<Out, zip::result::ZipError>
     where
-        F: FnOnce(&mut zip::read::ZipFile<'_>) -> Out,
+        F: FnOnce(&mut dyn Read) -> Out,
         Out: 'static,
     {
-        let mut archive_guard = self.archive.lock().unwrap();
-        let mut f = archive_guard.by_name(name)?;
-        let out = entry_reader(&mut f);
-        Ok(out)
+        match self.archive.as_ref(){
+            PackageBacking::Zip(archive) => {
+                let mut archive_guard = archive.lock().unwrap();
+                let mut f = archive_guard.by_name(name)?;
+                Ok(entry_reader(&mut f))
+            },
+            PackageBacking::Dir(_) => {
+                let path = self.entry_path(name).ok_or(zip::result::ZipError::FileNotFound)?;
+                if !path.is_file(){
+                    return Err(zip::result::ZipError::FileNotFound)
+                }
+                let mut f = std::fs::File::open(path)?;
+                Ok(entry_reader(&mut f))
+            },
+        }
+    }
+    /// Uncompressed size of entry `name`
+    pub fn entry_size(&self, name: &str) -> Option<u64>{
+        match self.archive.as_ref(){
+            PackageBacking::Zip(archive) => archive.lock().unwrap().by_name(name).ok().map(|entry| entry.size()),
+            PackageBacking::Dir(_) => std::fs::metadata(self.entry_path(name)?).ok().map(|meta| meta.len()),
+        }
     }
     pub fn has_entry(&self, name: &str) -> bool{
-        self.archive.lock().unwrap().by_name(name).is_ok()
+        match self.archive.as_ref(){
+            PackageBacking::Zip(archive) => archive.lock().unwrap().by_name(name).is_ok(),
+            PackageBacking::Dir(_) => self.entry_path(name).is_some_and(|path| path.is_file()),
+        }
     }
     pub fn with_file_names<F, Out>(&self, f: F) -> Out
     where
         F: for<'a> FnOnce(Box<dyn Iterator<Item=&'a str> + 'a>) -> Out,
         Out: 'static,
     {
-        let archive_guard = self.archive.lock().unwrap();
-        let file_names = Box::new(archive_guard.file_names());
-        f(file_names)
+        match self.archive.as_ref(){
+            PackageBacking::Zip(archive) => {
+                let archive_guard = archive.lock().unwrap();
+                let file_names = Box::new(archive_guard.file_names());
+                f(file_names)
+            },
+            PackageBacking::Dir(dir) => {
+                let mut file_names = vec![];
+                collect_file_names(dir, "", &mut file_names);
+                f(Box::new(file_names.iter().map(|name| name.as_str())))
+            },
+        }
+    }
+}
+
+/// Lists every file under `dir` recursively as a '/'-separated path prefixed with `prefix`.
+/// Unreadable directories are skipped.
+fn collect_file_names(dir: &Path, prefix: &str, file_names: &mut Vec<String>){
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return
+    };
+    for dir_entry in read_dir.flatten(){
+        let name = format!("{prefix}{}", dir_entry.file_name().to_string_lossy());
+        match dir_entry.file_type(){
+            Ok(file_type) if file_type.is_dir() => collect_file_names(&dir_entry.path(), &format!("{name}/"), file_names),
+            Ok(_) => file_names.push(name),
+            Err(_) => continue,
+        }
     }
 }
 
@@ -99,8 +201,12 @@ impl SharedZipArchive{
 pub enum RdfFileReferenceReadError{
     #[error("{0}")]
     ZipError(#[from] zip::result::ZipError),
-    #[error("Url file reference not supported yet")]
+    #[error("Url file reference can't be resolved without a download cache")]
     UrlFileReferenceNotSupportedYet,
+    #[error(transparent)]
+    DownloadError(#[from] DownloadError),
+    #[error("Could not read downloaded file: {0}")]
+    IoError(#[from] std::io::Error),
 }
 
 pub trait RdfFileReferenceExt{
@@ -108,19 +214,22 @@ pub trait RdfFileReferenceExt{
         &self, archive: &SharedZipArchive, reader: F
     ) -> Result<Out, RdfFileReferenceReadError>
     where
-        F: FnOnce(&mut zip::read::ZipFile<'_>) -> Out,
+        F: FnOnce(&mut dyn Read) -> Out,
         Out: 'static;
 }
 impl RdfFileReferenceExt for rdf::FileReference{
     fn try_read<F, Out>(&self, archive: &SharedZipArchive, reader: F) -> Result<Out, RdfFileReferenceReadError>
     where
-        F: FnOnce(&mut zip::read::ZipFile<'_>) -> Out,
+        F: FnOnce(&mut dyn Read) -> Out,
         Out: 'static,
     {
         let inner_path: String = match self{
-            rdf::FileReference::Url(_) => return Err(RdfFileReferenceReadError::UrlFileReferenceNotSupportedYet),
+            rdf::FileReference::Url(url) => {
+                let mut file = std::fs::File::open(archive.fetch_url(url, None)?)?;
+                return Ok(reader(&mut file))
+            },
             rdf::FileReference::Path(path) => path.into(),
         };
-        Ok(archive.with_entry(&inner_path, reader)?)
+        Ok(archive.with_entry(&inner_path, |entry| reader(entry))?)
     }
 }
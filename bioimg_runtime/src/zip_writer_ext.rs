@@ -1,32 +1,482 @@
+use std::collections::HashSet;
 use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
-use bioimg_spec::rdf::FsPath;
+use bioimg_spec::rdf::{FsPath, Sha256};
+use sha2::Digest;
 
 use crate::zoo_model::ModelPackingError;
 
+#[derive(Default)]
+struct PackingProgressState{
+    cancelled: AtomicBool,
+    total_bytes_written: AtomicU64,
+    expected_total_bytes: Mutex<Option<u64>>,
+    /// Name of the entry being written and how many bytes of it have been written so far
+    current_file: Mutex<Option<(String, u64)>>,
+}
+
+/// A handle to follow (and cancel) the packing of a model from another thread
+#[derive(Clone, Default)]
+pub struct PackingProgress(Arc<PackingProgressState>);
+
+impl PackingProgress{
+    /// Makes the packing fail with [ModelPackingError::Cancelled] at the next write
+    pub fn cancel(&self){
+        self.0.cancelled.store(true, Ordering::Relaxed)
+    }
+    pub fn is_cancelled(&self) -> bool{
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+    pub fn total_bytes_written(&self) -> u64{
+        self.0.total_bytes_written.load(Ordering::Relaxed)
+    }
+    /// A rough estimate of how many bytes the whole package will take, if known
+    pub fn expected_total_bytes(&self) -> Option<u64>{
+        *self.0.expected_total_bytes.lock().unwrap()
+    }
+    pub fn set_expected_total_bytes(&self, expected: Option<u64>){
+        *self.0.expected_total_bytes.lock().unwrap() = expected
+    }
+    /// The entry currently being written and the number of bytes written into it so far
+    pub fn current_file(&self) -> Option<(String, u64)>{
+        self.0.current_file.lock().unwrap().clone()
+    }
+    /// How far along the packing is, between 0 and 1, if the expected size is known
+    pub fn fraction(&self) -> Option<f32>{
+        let expected = self.expected_total_bytes().filter(|expected| *expected > 0)?;
+        Some((self.total_bytes_written() as f64 / expected as f64).min(1.0) as f32)
+    }
+    fn start_file(&self, name: String){
+        *self.0.current_file.lock().unwrap() = Some((name, 0))
+    }
+    fn add_bytes(&self, num_bytes: u64){
+        self.0.total_bytes_written.fetch_add(num_bytes, Ordering::Relaxed);
+        if let Some((_, file_bytes)) = self.0.current_file.lock().unwrap().as_mut(){
+            *file_bytes += num_bytes;
+        }
+    }
+}
+
+/// Computes the sha256 of everything written through it, optionally reporting progress and bailing out on cancellation
+pub(crate) struct HashingWriter<'w>{
+    inner: &'w mut dyn Write,
+    hasher: sha2::Sha256,
+    progress: Option<&'w PackingProgress>,
+    report_bytes: bool,
+}
+
+impl<'w> HashingWriter<'w>{
+    pub(crate) fn new(inner: &'w mut dyn Write, progress: Option<&'w PackingProgress>) -> Self{
+        Self{inner, hasher: sha2::Sha256::new(), progress, report_bytes: true}
+    }
+
+    /// Still bails out on cancellation, but doesn't count the written bytes towards the progress
+    fn without_reporting_bytes(self) -> Self{
+        Self{report_bytes: false, ..self}
+    }
+
+    pub(crate) fn finalize(self) -> Sha256{
+        Sha256::from_digest(self.hasher.finalize().into())
+    }
+}
+
+impl Write for HashingWriter<'_>{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.progress.is_some_and(|progress| progress.is_cancelled()){
+            return Err(std::io::Error::other("packing was cancelled"))
+        }
+        let num_written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..num_written]);
+        if let Some(progress) = self.progress.filter(|_| self.report_bytes){
+            progress.add_bytes(num_written as u64);
+        }
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// How entries get named when a model is packed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntryNaming{
+    /// Prefixes every name with a random uuid, so entries never clash
+    #[default]
+    Unique,
+    /// Uses the names as given (e.g. `README.md`, `onnx_weights.onnx`), adding a numeric suffix on clashes
+    Readable,
+    /// Names entries after the sha256 of their contents (keeping the extension), so that identical inputs
+    /// produce identical packages. Entries with the same contents are only written once.
+    ContentAddressed,
+}
+
+/// How entries get compressed when written into a zip
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionPolicy{
+    /// Never compress
+    StoreAll,
+    /// Deflates entries that compress well (text, yaml, npy, scripts) at `level` (or the deflate default
+    /// if `None`), and stores everything else, since weights and images are usually compressed already
+    ByFileKind{level: Option<i64>},
+}
+
+impl Default for CompressionPolicy{
+    fn default() -> Self {
+        Self::ByFileKind{level: None}
+    }
+}
+
+impl CompressionPolicy{
+    fn is_compressible(path: &str) -> bool{
+        let Some((_, extension)) = path.rsplit_once('.') else {
+            return false
+        };
+        matches!(
+            extension.to_ascii_lowercase().as_str(),
+            "yaml" | "yml" | "md" | "txt" | "json" | "npy" | "py" | "csv" | "xml" | "html" | "toml" | "ijm"
+        )
+    }
+
+    fn apply(&self, path: &str, file_options: zip::write::SimpleFileOptions) -> zip::write::SimpleFileOptions{
+        match self{
+            Self::ByFileKind{level} if Self::is_compressible(path) => {
+                file_options.compression_method(zip::CompressionMethod::Deflated).compression_level(*level)
+            },
+            _ => file_options.compression_method(zip::CompressionMethod::Stored),
+        }
+    }
+}
+
+enum PackageSink<W: Write + Seek>{
+    Zip(Box<zip::ZipWriter<W>>),
+    /// Writes every entry as a file under this directory
+    Dir(PathBuf),
+}
+
 // Hides the ZipWriter to enforce correct usage
-pub struct ModelZipWriter<W: Write + Seek>(zip::ZipWriter<W>);
+pub struct ModelZipWriter<W: Write + Seek>{
+    sink: PackageSink<W>,
+    naming: EntryNaming,
+    used_names: HashSet<String>,
+    file_options: zip::write::SimpleFileOptions,
+    compression: CompressionPolicy,
+    progress: PackingProgress,
+}
+
+impl ModelZipWriter<std::fs::File>{
+    /// Writes the package unpacked into directory `dir`, which is created if needed
+    pub fn new_dir(dir: &Path) -> Result<Self, ModelPackingError> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self::from_sink(PackageSink::Dir(dir.to_owned())))
+    }
+}
 
 impl<W: Write + Seek> ModelZipWriter<W> {
     pub fn new(zip_sink: W) -> Self {
-        Self(zip::ZipWriter::new(zip_sink))
+        Self::from_sink(PackageSink::Zip(Box::new(zip::ZipWriter::new(zip_sink))))
+    }
+
+    fn from_sink(sink: PackageSink<W>) -> Self{
+        Self{
+            sink,
+            naming: EntryNaming::default(),
+            used_names: HashSet::from(["rdf.yaml".to_owned()]),
+            file_options: zip::write::SimpleFileOptions::default(),
+            compression: CompressionPolicy::default(),
+            progress: PackingProgress::default(),
+        }
+    }
+
+    pub fn with_compression(self, compression: CompressionPolicy) -> Self{
+        Self{compression, ..self}
+    }
+
+    /// Reports every write to `progress`, and stops writing once it gets cancelled
+    pub fn with_progress(self, progress: PackingProgress) -> Self{
+        Self{progress, ..self}
     }
 
-    pub fn write_file<F, Out, E>(&mut self, path: &FsPath, f: F) -> Result<Out, ModelPackingError>
+    pub fn progress(&self) -> &PackingProgress{
+        &self.progress
+    }
+
+    pub fn with_entry_naming(self, naming: EntryNaming) -> Self{
+        Self{naming, ..self}
+    }
+
+    /// Makes the output depend only on what is written: entries are content-addressed and
+    /// get a fixed modification time (1980-01-01) and permissions.
+    pub fn reproducible(self) -> Self{
+        let file_options = self.file_options
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(0o644);
+        Self{naming: EntryNaming::ContentAddressed, file_options, ..self}
+    }
+
+    fn sanitize_entry_name(name: &str) -> String{
+        let name = name.replace(['/', '\\'], "_");
+        if name.is_empty() || name == "." || name == ".." { "file".to_owned() } else { name }
+    }
+
+    /// Splits `name` into stem and extension (with its leading dot, if any)
+    fn split_extension(name: &str) -> (&str, &str){
+        match name.rfind('.'){
+            Some(dot_idx) if dot_idx > 0 => name.split_at(dot_idx),
+            _ => (name, ""),
+        }
+    }
+
+    /// Picks the path for a new entry that is to be recognizable as `name` (e.g. `README.md`)
+    fn entry_path(&mut self, name: &str) -> FsPath{
+        let name = Self::sanitize_entry_name(name);
+        match self.naming{
+            EntryNaming::Unique | EntryNaming::ContentAddressed => FsPath::unique_suffixed(&format!("_{name}")),
+            EntryNaming::Readable => {
+                let (stem, extension) = Self::split_extension(&name);
+                let mut candidate = name.clone();
+                let mut counter = 2;
+                while self.used_names.contains(&candidate){
+                    candidate = format!("{stem}_{counter}{extension}");
+                    counter += 1;
+                }
+                self.used_names.insert(candidate.clone());
+                FsPath::try_from(candidate).expect("separators were replaced")
+            },
+        }
+    }
+
+    /// Writes a new file recognizable as `name` via `f`, picking its path according to the entry naming.
+    ///
+    /// Returns the output of `f`, the path of the new entry and the sha256 of the written bytes.
+    /// With [EntryNaming::ContentAddressed], the contents are spooled into a temporary file until their sha256 is known.
+    pub fn write_new_file<F, Out, E>(&mut self, name: &str, f: F) -> Result<(Out, FsPath, Sha256), ModelPackingError>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<Out, E>,
+        E: Into<ModelPackingError>,
+    {
+        self.write_new_file_of_size(name, Some(0), f)
+    }
+
+    /// Like [Self::write_new_file], for entries that might be big (e.g. weights).
+    ///
+    /// Entries of `size_hint` bytes over 4GiB (or of unknown size) are written with zip64 extensions.
+    pub fn write_new_file_of_size<F, Out, E>(
+        &mut self, name: &str, size_hint: Option<u64>, f: F
+    ) -> Result<(Out, FsPath, Sha256), ModelPackingError>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<Out, E>,
+        E: Into<ModelPackingError>,
+    {
+        let large_file = !matches!(size_hint, Some(size) if size < u32::MAX as u64);
+        if self.naming != EntryNaming::ContentAddressed{
+            let path = self.entry_path(name);
+            let (out, sha256) = self.write_entry(&path, large_file, true, f)?;
+            return Ok((out, path, sha256))
+        }
+        if self.progress.is_cancelled(){
+            return Err(ModelPackingError::Cancelled)
+        }
+        let name = Self::sanitize_entry_name(name);
+        self.progress.start_file(name.clone());
+        let mut spool = std::io::BufWriter::new(tempfile::tempfile()?);
+        let mut spool_writer = HashingWriter::new(&mut spool, Some(&self.progress));
+        let out = match f(&mut spool_writer).map_err(|e| e.into()){
+            Err(_) if self.progress.is_cancelled() => return Err(ModelPackingError::Cancelled),
+            res => res?,
+        };
+        spool_writer.flush()?;
+        let sha256 = spool_writer.finalize();
+        let mut spool = spool.into_inner().map_err(|e| e.into_error())?;
+
+        let (_, extension) = Self::split_extension(&name);
+        let path_string = format!("{sha256}{extension}");
+        let path = FsPath::try_from(path_string.clone()).expect("hex digits and a sanitized extension");
+        if self.used_names.insert(path_string){
+            let size = spool.stream_position()?;
+            spool.rewind()?;
+            // the bytes were already reported while spooling
+            self.write_entry(&path, size >= u32::MAX as u64, false, |writer| std::io::copy(&mut spool, writer))?;
+        }
+        Ok((out, path, sha256))
+    }
+
+    /// Writes a new file at `path` via `f`, returning the output of `f` and the sha256 of the written bytes
+    pub fn write_file<F, Out, E>(&mut self, path: &FsPath, f: F) -> Result<(Out, Sha256), ModelPackingError>
     where
         //FIXME: using W as a param keeps Seek, so using dyn to remove it
         F: FnOnce(&mut dyn Write) -> Result<Out, E>,
         E: Into<ModelPackingError>,
     {
-        let file_options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        self.write_entry(path, false, true, f)
+    }
+
+    fn write_entry<F, Out, E>(
+        &mut self, path: &FsPath, large_file: bool, report_bytes: bool, f: F
+    ) -> Result<(Out, Sha256), ModelPackingError>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<Out, E>,
+        E: Into<ModelPackingError>,
+    {
+        if self.progress.is_cancelled(){
+            return Err(ModelPackingError::Cancelled)
+        }
         let path: String = path.clone().into();
-        self.0.start_file(path, file_options)?;
-        f(&mut self.0).map_err(|e| e.into())
+        if report_bytes{
+            self.progress.start_file(path.clone());
+        }
+        let mut file;
+        let inner: &mut dyn Write = match &mut self.sink{
+            PackageSink::Zip(zip_writer) => {
+                let file_options = self.compression.apply(&path, self.file_options).large_file(large_file);
+                zip_writer.start_file(path, file_options)?;
+                zip_writer
+            },
+            PackageSink::Dir(dir) => {
+                let file_path = dir.join(&path);
+                if let Some(parent) = file_path.parent(){
+                    std::fs::create_dir_all(parent)?;
+                }
+                file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+                &mut file
+            },
+        };
+        let mut writer = HashingWriter::new(inner, Some(&self.progress));
+        if !report_bytes{
+            writer = writer.without_reporting_bytes();
+        }
+        let out = match f(&mut writer).map_err(|e| e.into()){
+            Err(_) if self.progress.is_cancelled() => return Err(ModelPackingError::Cancelled),
+            res => res?,
+        };
+        writer.flush()?;
+        Ok((out, writer.finalize()))
     }
 
     //FIXME: can we enforce the calling of this function with something like must_use ?
     pub fn finish(self) -> Result<(), ModelPackingError> {
-        self.0.finish()?;
+        match self.sink{
+            PackageSink::Zip(zip_writer) => { (*zip_writer).finish()?; },
+            PackageSink::Dir(_) => (),
+        }
         Ok(())
     }
 }
+
+#[test]
+fn test_write_file_returns_sha256(){
+    let mut writer = ModelZipWriter::new(std::io::Cursor::new(Vec::<u8>::new()));
+    let path = FsPath::unique_suffixed(".txt");
+    let (num_bytes, sha256) = writer.write_file(&path, |writer| -> Result<usize, std::io::Error>{
+        writer.write_all(b"ab")?;
+        writer.write_all(b"c")?;
+        Ok(3)
+    }).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(num_bytes, 3);
+    assert_eq!(sha256.as_str(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+}
+
+#[test]
+fn test_unpacked_package_round_trip(){
+    use crate::zip_archive_ext::SharedZipArchive;
+    use crate::FileSource;
+
+    let dir = tempfile::tempdir().unwrap();
+    let package_dir = dir.path().join("model");
+    let path = FsPath::unique_suffixed(".txt");
+    let mut writer = ModelZipWriter::new_dir(&package_dir).unwrap();
+    writer.write_file(&path, |writer| writer.write_all(b"contents")).unwrap();
+    writer.finish().unwrap();
+
+    let archive = SharedZipArchive::open(&package_dir).unwrap();
+    let path_string: String = path.clone().into();
+    let file_names: Vec<String> = archive.with_file_names(|names| names.map(|n| n.to_owned()).collect());
+    assert_eq!(file_names, vec![path_string.clone()]);
+    let contents = archive.with_entry(&path_string, |entry|{
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).map(|_| contents)
+    }).unwrap().unwrap();
+    assert_eq!(contents, "contents");
+    assert!(!archive.has_entry("../model"));
+
+    let file_source = FileSource::from_rdf_file_reference(archive, &bioimg_spec::rdf::FileReference::Path(path)).unwrap();
+    assert!(matches!(file_source, FileSource::LocalFile{..}));
+}
+
+#[test]
+fn test_readable_entry_names(){
+    let mut writer = ModelZipWriter::new(std::io::Cursor::new(Vec::<u8>::new())).with_entry_naming(EntryNaming::Readable);
+    let names: Vec<String> = ["README.md", "README.md", "rdf.yaml", "../weights", "LICENSE"].into_iter()
+        .map(|name| writer.entry_path(name).into())
+        .collect();
+    assert_eq!(names, vec!["README.md", "README_2.md", "rdf_2.yaml", ".._weights", "LICENSE"]);
+}
+
+#[test]
+fn test_reproducible_packing(){
+    let pack = || -> Vec<u8> {
+        let mut writer = ModelZipWriter::new(std::io::Cursor::new(Vec::<u8>::new())).reproducible();
+        let (_, first_path, sha256) = writer.write_new_file("README.md", |writer| writer.write_all(b"docs")).unwrap();
+        let (_, second_path, _) = writer.write_new_file("copy.md", |writer| writer.write_all(b"docs")).unwrap();
+        assert_eq!(String::from(first_path.clone()), format!("{sha256}.md"));
+        assert_eq!(first_path, second_path);
+        writer.write_file(&FsPath::try_from("rdf.yaml".to_owned()).unwrap(), |writer| writer.write_all(b"rdf")).unwrap();
+        let PackageSink::Zip(zip_writer) = writer.sink else { unreachable!() };
+        zip_writer.finish().unwrap().into_inner()
+    };
+    let first = pack();
+    assert_eq!(first, pack());
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(first)).unwrap();
+    assert_eq!(archive.len(), 2);
+    for idx in 0..archive.len(){
+        let entry = archive.by_index(idx).unwrap();
+        assert_eq!(entry.last_modified(), Some(zip::DateTime::default()));
+        assert_eq!(entry.unix_mode().map(|mode| mode & 0o777), Some(0o644));
+    }
+}
+
+#[test]
+fn test_packing_progress_and_cancellation(){
+    for naming in [EntryNaming::Unique, EntryNaming::ContentAddressed]{
+        let progress = PackingProgress::default();
+        progress.set_expected_total_bytes(Some(8));
+        let mut writer = ModelZipWriter::new(std::io::Cursor::new(Vec::<u8>::new()))
+            .with_entry_naming(naming)
+            .with_progress(progress.clone());
+        writer.write_new_file("a.txt", |writer| writer.write_all(b"abcd")).unwrap();
+        assert_eq!(progress.total_bytes_written(), 4);
+        assert_eq!(progress.fraction(), Some(0.5));
+        assert_eq!(progress.current_file().unwrap().1, 4);
+
+        let result = writer.write_new_file("b.txt", |writer| {
+            writer.write_all(b"ab")?;
+            progress.cancel();
+            writer.write_all(b"cd")
+        });
+        assert!(matches!(result, Err(ModelPackingError::Cancelled)));
+        assert_eq!(progress.total_bytes_written(), 6);
+    }
+}
+
+#[test]
+fn test_compression_by_file_kind(){
+    let mut writer = ModelZipWriter::new(std::io::Cursor::new(Vec::<u8>::new()))
+        .with_compression(CompressionPolicy::ByFileKind{level: Some(9)});
+    let text = "a very repetitive readme ".repeat(100);
+    let (_, readme_path, _) = writer.write_new_file("README.md", |writer| writer.write_all(text.as_bytes())).unwrap();
+    let (_, weights_path, _) = writer.write_new_file_of_size("weights.onnx", None, |writer| writer.write_all(text.as_bytes())).unwrap();
+    let PackageSink::Zip(zip_writer) = writer.sink else { unreachable!() };
+    let mut archive = zip::ZipArchive::new(zip_writer.finish().unwrap()).unwrap();
+
+    let readme = archive.by_name(&String::from(readme_path)).unwrap();
+    assert_eq!(readme.compression(), zip::CompressionMethod::Deflated);
+    assert!(readme.compressed_size() < readme.size() / 10);
+    drop(readme);
+    let weights = archive.by_name(&String::from(weights_path)).unwrap();
+    assert_eq!(weights.compression(), zip::CompressionMethod::Stored);
+}
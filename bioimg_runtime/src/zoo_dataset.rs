@@ -0,0 +1,202 @@
+use std::io::{Seek, Write};
+
+use bioimg_spec::rdf::{self, FileReference, HttpUrl, LicenseId, ResourceId, Version};
+use bioimg_spec::rdf::author::Author2;
+use bioimg_spec::rdf::maintainer::Maintainer;
+use bioimg_spec::rdf::model::dataset_descr::{DatasetDescr, DatasetDescrEnum, DatasetDescrMarker, DatasetName, LinkedDatasetDescr};
+
+use crate::cover_image::CoverImageLoadingError;
+use crate::file_source::FileSourceError;
+use crate::icon::IconLoadingError;
+use crate::zip_archive_ext::{RdfFileReferenceExt, RdfFileReferenceReadError, SharedZipArchive};
+use crate::zip_writer_ext::ModelZipWriter;
+use crate::zoo_model::ModelPackingError;
+use crate::{CoverImage, FileSource, Icon};
+
+#[derive(thiserror::Error, Debug)]
+pub enum DatasetLoadingError{
+    #[error("Error reading file: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not load a cover image: {0}")]
+    CoverImageLoadingError(#[from] CoverImageLoadingError),
+    #[error("Could not load an icon: {0}")]
+    IconLoadingError(#[from] IconLoadingError),
+    #[error("Could not read file from the rdf: {0}")]
+    RdfFileReferenceReadError(#[from] RdfFileReferenceReadError),
+    #[error("Could not load an attachment: {0}")]
+    FileSourceError(#[from] FileSourceError),
+}
+
+/// A dataset described inline in a model's `training_data`, with all of its files loaded
+#[derive(Clone)]
+pub struct ZooDataset{
+    pub format_version: Version,
+    pub name: DatasetName,
+    pub description: rdf::ResourceTextDescription,
+    pub covers: Vec<CoverImage>,
+    pub id: Option<ResourceId>,
+    pub authors: Vec<Author2>,
+    pub attachments: Vec<FileSource>,
+    pub cite: Vec<rdf::CiteEntry2>,
+    pub license: Option<LicenseId>,
+    pub config: serde_json::Map<String, serde_json::Value>,
+    pub git_repo: Option<HttpUrl>,
+    pub icon: Option<Icon>,
+    pub links: Vec<String>,
+    pub maintainers: Vec<Maintainer>,
+    pub tags: Vec<rdf::Tag>,
+    pub version: Option<Version>,
+    pub documentation: Option<String>,
+    pub source: Option<HttpUrl>,
+}
+
+impl ZooDataset{
+    pub fn new(name: DatasetName, description: rdf::ResourceTextDescription) -> Self{
+        Self{
+            format_version: Version::version_0_3_0(),
+            name,
+            description,
+            covers: vec![],
+            id: None,
+            authors: vec![],
+            attachments: vec![],
+            cite: vec![],
+            license: None,
+            config: Default::default(),
+            git_repo: None,
+            icon: None,
+            links: vec![],
+            maintainers: vec![],
+            tags: vec![],
+            version: None,
+            documentation: None,
+            source: None,
+        }
+    }
+}
+
+/// The dataset a model was trained on
+#[derive(Clone)]
+pub enum TrainingData{
+    /// A dataset in the bioimage.io collection
+    Linked(ResourceId),
+    Inline(Box<ZooDataset>),
+}
+
+impl TrainingData{
+    pub fn try_load(rdf: DatasetDescrEnum, archive: &SharedZipArchive) -> Result<Self, DatasetLoadingError>{
+        let rdf = match rdf{
+            DatasetDescrEnum::LinkedDatasetDescr(linked) => return Ok(Self::Linked(linked.id)),
+            DatasetDescrEnum::DatasetDescr(rdf) => *rdf,
+        };
+        let covers = rdf.covers.into_iter()
+            .map(|rdf_cover| CoverImage::try_load(rdf_cover, archive))
+            .collect::<Result<_, _>>()?;
+        let attachments = rdf.attachments.iter()
+            .map(|att| FileSource::from_rdf_file_descr(archive.clone(), att))
+            .collect::<Result<_, _>>()?;
+        let icon = rdf.icon.map(|icon| Icon::try_load(icon, archive)).transpose()?;
+        let documentation = match rdf.documentation{
+            Some(documentation) => Some(documentation.try_read(archive, |reader| {
+                let mut documentation = String::new();
+                reader.read_to_string(&mut documentation).map(|_| documentation)
+            })??),
+            None => None,
+        };
+        Ok(Self::Inline(Box::new(ZooDataset{
+            format_version: rdf.format_version,
+            name: rdf.name,
+            description: rdf.description,
+            covers,
+            id: rdf.id,
+            authors: rdf.authors,
+            attachments,
+            cite: rdf.cite,
+            license: rdf.license,
+            config: rdf.config,
+            git_repo: rdf.git_repo,
+            icon,
+            links: rdf.links,
+            maintainers: rdf.maintainers,
+            tags: rdf.tags,
+            version: rdf.version,
+            documentation,
+            source: rdf.source,
+        })))
+    }
+
+    /// Files that get written when packing this dataset
+    pub fn file_sources(&self) -> &[FileSource]{
+        match self{
+            Self::Linked(_) => &[],
+            Self::Inline(dataset) => &dataset.attachments,
+        }
+    }
+
+    pub fn rdf_dump(&self, writer: &mut ModelZipWriter<impl Write + Seek>) -> Result<DatasetDescrEnum, ModelPackingError>{
+        let dataset = match self{
+            Self::Linked(id) => return Ok(LinkedDatasetDescr{id: id.clone()}.into()),
+            Self::Inline(dataset) => dataset,
+        };
+        let covers = dataset.covers.iter()
+            .map(|cover| cover.dump(writer))
+            .collect::<Result<Vec<_>, _>>()?;
+        let attachments = dataset.attachments.iter()
+            .map(|file| file.dump_as_file_description(writer))
+            .collect::<Result<Vec<_>, _>>()?;
+        let icon = dataset.icon.as_ref().map(|icon| icon.dump(writer)).transpose()?;
+        let documentation = match &dataset.documentation{
+            Some(documentation) => {
+                let (_, documentation_path, _) = writer.write_new_file("dataset_README.md", |writer| {
+                    writer.write_all(documentation.as_bytes())
+                })?;
+                Some(FileReference::Path(documentation_path))
+            },
+            None => None,
+        };
+        Ok(DatasetDescr{
+            format_version: dataset.format_version.clone(),
+            marker: DatasetDescrMarker,
+            name: dataset.name.clone(),
+            description: dataset.description.clone(),
+            covers,
+            id: dataset.id.clone(),
+            authors: dataset.authors.clone(),
+            attachments,
+            cite: dataset.cite.clone(),
+            license: dataset.license,
+            config: dataset.config.clone(),
+            git_repo: dataset.git_repo.clone(),
+            icon,
+            links: dataset.links.clone(),
+            maintainers: dataset.maintainers.clone(),
+            tags: dataset.tags.clone(),
+            version: dataset.version.clone(),
+            documentation,
+            source: dataset.source.clone(),
+        }.into())
+    }
+}
+
+#[test]
+fn test_inline_dataset_round_trip(){
+    let mut dataset = ZooDataset::new(
+        DatasetName::try_from("Nuclei".to_owned()).unwrap(),
+        rdf::ResourceTextDescription::try_from("Fluorescence images of nuclei".to_owned()).unwrap(),
+    );
+    dataset.documentation = Some("# Nuclei".to_owned());
+    let training_data = TrainingData::Inline(Box::new(dataset));
+
+    let dir = tempfile::tempdir().unwrap();
+    let package_dir = dir.path().join("model");
+    let mut writer = ModelZipWriter::new_dir(&package_dir).unwrap();
+    let rdf = training_data.rdf_dump(&mut writer).unwrap();
+    writer.finish().unwrap();
+
+    let archive = SharedZipArchive::open(&package_dir).unwrap();
+    let TrainingData::Inline(loaded) = TrainingData::try_load(rdf, &archive).unwrap() else {
+        panic!("Expected an inline dataset");
+    };
+    assert_eq!(loaded.name.to_string(), "Nuclei");
+    assert_eq!(loaded.documentation.as_deref(), Some("# Nuclei"));
+}
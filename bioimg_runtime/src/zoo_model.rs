@@ -1,17 +1,18 @@
 use std::{
-    io::{Read, Seek, Write}, path::{Path, PathBuf}, sync::Arc
+    io::{Seek, Write}, path::{Path, PathBuf}, sync::Arc
 };
 
 use bioimg_spec::rdf::{model::ModelRdfName, FileReference, FsPath, HttpUrl, LicenseId, ResourceId, Version};
-use bioimg_spec::rdf::model::unsupported::Version_0_5_4_OrLater;
+use bioimg_spec::rdf::model::unsupported::FutureVersion;
 use bioimg_spec::rdf::model::unsupported::UnsupportedLegacyModel;
 use bioimg_spec::rdf::model::unsupported::UnsupportedFutureModel;
 use bioimg_spec::rdf;
-use bioimg_spec::rdf::version::Version_0_5_x;
+use bioimg_spec::rdf::version::{Version_0_4_x, Version_0_5_x};
 use bioimg_spec::rdf::non_empty_list::NonEmptyList;
 use bioimg_spec::rdf::model::RdfTypeModel;
 use bioimg_spec::rdf::model::unsupported::Version_0_4_X_OrEarlier;
 use bioimg_spec::rdf::model::ModelRdfV0_5;
+use bioimg_spec::rdf::model::ModelRdfV0_4;
 use bioimg_spec::rdf::maintainer::Maintainer;
 use bioimg_spec::rdf::file_reference::FsPathComponent;
 use bioimg_spec::rdf::author::Author2;
@@ -20,14 +21,21 @@ use image::ImageError;
 use serde::Deserialize;
 
 use crate::{FileSource, Icon, ModelInterface, NpyArray, TensorValidationError};
-use crate::zip_archive_ext::{SharedZipArchive, ZipArchiveOpenError};
+use crate::zip_archive_ext::{RdfFileReferenceExt, RdfFileReferenceReadError, SharedZipArchive, ZipArchiveOpenError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::download_cache::DownloadCache;
+use crate::download_cache::DownloadError;
+use crate::file_source::FileSourceError;
 use crate::cover_image::CoverImageLoadingError;
 use crate::CoverImage;
-use crate::zip_writer_ext::ModelZipWriter;
+use crate::zip_writer_ext::{CompressionPolicy, EntryNaming, ModelZipWriter, PackingProgress};
 use crate::npy_array::ArcNpyArray;
 use crate::model_weights::{ModelWeights, ModelWeightsLoadingError};
 use crate::model_interface::{InputSlot, ModelInterfaceLoadingError, OutputSlot};
 use crate::icon::IconLoadingError;
+use crate::self_test::{self, InferenceBackend, SelfTestError, SelfTestReport, Tolerance};
+use crate::hash_verification::{self, HashMismatches, HashVerification};
+use crate::zoo_dataset::{DatasetLoadingError, TrainingData};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ModelPackingError {
@@ -49,6 +57,10 @@ pub enum ModelPackingError {
     HttpErro{reason: String},
     #[error("Unexpected status ({status})when requesting {url}")]
     UnexpectedHttpStatus{status: u16, url: HttpUrl},
+    #[error("Could not download file: {0}")]
+    DownloadError(#[from] DownloadError),
+    #[error("Packing was cancelled")]
+    Cancelled,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -67,10 +79,14 @@ pub enum ModelLoadingError{
     CoverImageLoadingError(#[from] CoverImageLoadingError),
     #[error("Could not load an icon: {0}")]
     IconLoadingError(#[from] IconLoadingError),
-    #[error("Url file reference not supported yet")]
-    UrlFileReferenceNotSupportedYet,
+    #[error("Could not read file from the rdf: {0}")]
+    RdfFileReferenceReadError(#[from] RdfFileReferenceReadError),
+    #[error("Could not load an attachment: {0}")]
+    FileSourceError(#[from] FileSourceError),
     #[error("Error loading models from rdf: {0}")]
     ModelWeightsLoadingError(#[from] ModelWeightsLoadingError),
+    #[error("Could not load training data: {0}")]
+    DatasetLoadingError(#[from] DatasetLoadingError),
     #[error("Could not load model interface: {0}")]
     ModelInterfaceLoadingError(#[from] ModelInterfaceLoadingError),
     #[error("Could not produce a valid Input tensor description: {0}")]
@@ -79,12 +95,16 @@ pub enum ModelLoadingError{
     TensorValidationError(#[from] TensorValidationError),
     #[error("Unsupported legacy model version: {version}. The earliest supported version is {earliest_supported}")]
     UnsupportedLegacyModel{version: Version_0_4_X_OrEarlier, earliest_supported: Version},
+    #[error("Could not convert legacy model: {0}")]
+    LegacyModelConversionError(#[from] modelrdf::model_rdf_0_4::LegacyModelConversionError),
     #[error("Rdf version is too new for this application: {format_version}. The latest supported version is {latest_supported}")]
-    FutureModel{format_version: Version_0_5_4_OrLater, latest_supported: Version},
+    FutureModel{format_version: FutureVersion, latest_supported: Version},
     #[error("Bad rdf: {inner}")]
     BadModel{inner: serde_yaml::Error},
     #[error("Unrecognized rdf data (found version {format_version:?})")]
     UnrecognizedRdf{format_version: Option<String>},
+    #[error("Files don't match their declared sha256: {0}")]
+    HashMismatch(HashMismatches),
 }
 
 pub struct ZooModel {
@@ -104,7 +124,7 @@ pub struct ZooModel {
     pub license: LicenseId,
     pub name: ModelRdfName,
     pub id: Option<ResourceId>,
-    // training_data: DatasetDescrEnum, //FIXME
+    pub training_data: Option<TrainingData>,
     pub weights: ModelWeights,
     pub interface: ModelInterface<ArcNpyArray>,
 }
@@ -116,6 +136,24 @@ impl ZooModel{
     }
 
     pub fn try_load_archive(archive: SharedZipArchive) -> Result<Self, ModelLoadingError>{
+        let (model, _) = Self::try_load_archive_with(archive, HashVerification::Strict)?;
+        Ok(model)
+    }
+
+    /// Loads a model from `archive`, resolving url references through the archive's download cache
+    /// (or a default one in the system temp dir if the archive has none). Large files like weights and
+    /// attachments are only downloaded once their contents are read.
+    ///
+    /// Files that don't match their declared sha256 are returned alongside the model when using
+    /// [HashVerification::WarnOnly], so they can be shown to the user; with [HashVerification::Strict] they are an error.
+    pub fn try_load_archive_with(
+        archive: SharedZipArchive, hash_verification: HashVerification
+    ) -> Result<(Self, HashMismatches), ModelLoadingError>{
+        #[cfg(not(target_arch = "wasm32"))]
+        let archive = match archive.download_cache(){
+            Some(_) => archive,
+            None => archive.with_download_cache(Arc::new(DownloadCache::default())),
+        };
         let model_rdf_yaml: serde_yaml::Value = 'model_rdf: {
             for file_name in ["rdf.yaml", "bioimageio.yaml"]{
                 let zip_res = archive.with_entry(file_name, |entry|{
@@ -135,11 +173,18 @@ impl ZooModel{
         };
         let model_rdf = match ModelRdfV0_5::deserialize(&model_rdf_yaml){
             Ok(model_rdf) => model_rdf,
-            Err(v5_err) => {
+            Err(v5_err) => 'legacy: {
+                let v4_err = match ModelRdfV0_4::deserialize(&model_rdf_yaml){
+                    Ok(legacy_model) => break 'legacy ModelRdfV0_5::try_from(legacy_model)?,
+                    Err(v4_err) => v4_err,
+                };
                 if let Ok(legacy_model) = UnsupportedLegacyModel::deserialize(&model_rdf_yaml){
+                    if Version_0_4_x::deserialize(&model_rdf_yaml["format_version"]).is_ok(){
+                        return Err(ModelLoadingError::BadModel { inner: v4_err })
+                    }
                     return Err(ModelLoadingError::UnsupportedLegacyModel {
                         version: legacy_model.format_version,
-                        earliest_supported: Version_0_5_x::earliest_supported_version(),
+                        earliest_supported: Version::version_0_4_0(),
                     })
                 }
                 if let Ok(future_model) = UnsupportedFutureModel::deserialize(&model_rdf_yaml){
@@ -152,30 +197,29 @@ impl ZooModel{
             }
         };
 
+        let mismatches = HashMismatches(
+            hash_verification::find_mismatches(&archive, hash_verification::declared_hashes(&model_rdf))?
+        );
+        if !mismatches.0.is_empty() && hash_verification == HashVerification::Strict{
+            return Err(ModelLoadingError::HashMismatch(mismatches))
+        }
+
         let covers: Vec<CoverImage> = model_rdf.covers.into_iter()
             .map(|rdf_cover| CoverImage::try_load(rdf_cover, &archive))
             .collect::<Result<_, _>>()?;
 
-        let attachments: Vec<FileSource> = model_rdf.attachments.into_iter()
-            .map(|att| match att.source{
-                rdf::FileReference::Url(_) => return Err(ModelLoadingError::UrlFileReferenceNotSupportedYet),
-                rdf::FileReference::Path(fs_path) => {
-                    Ok(FileSource::FileInZipArchive { archive: archive.clone(), inner_path: Arc::from(String::from(fs_path).as_str()) })
-                }
-            })
+        let attachments: Vec<FileSource> = model_rdf.attachments.iter()
+            .map(|att| FileSource::from_rdf_file_descr(archive.clone(), att))
             .collect::<Result<_, _>>()?;
         let icon = model_rdf.icon.map(|icon| Icon::try_load(icon, &archive)).transpose()?;
 
-        let mut documentation = String::new();
-        match model_rdf.documentation{
-            rdf::FileReference::Url(_) => return Err(ModelLoadingError::UrlFileReferenceNotSupportedYet),
-            FileReference::Path(path) => {
-                let path_string: String = path.into();
-                archive.with_entry(&path_string, |entry| {
-                    entry.read_to_string(&mut documentation)
-                })??;
-            },
-        }
+        let documentation = model_rdf.documentation.try_read(&archive, |reader| {
+            let mut documentation = String::new();
+            reader.read_to_string(&mut documentation).map(|_| documentation)
+        })??;
+        let training_data = model_rdf.training_data
+            .map(|training_data| TrainingData::try_load(training_data, &archive))
+            .transpose()?;
         let weights = ModelWeights::try_from_rdf(model_rdf.weights, archive.clone())?;
 
         let input_slots: Vec<_> = model_rdf.inputs.into_inner().into_iter()
@@ -187,7 +231,7 @@ impl ZooModel{
 
         let model_interface = ModelInterface::try_build(input_slots, output_slots)?;
 
-        Ok(Self{
+        let model = Self{
             description: model_rdf.description,
             covers,
             attachments,
@@ -204,21 +248,111 @@ impl ZooModel{
             license: model_rdf.license,
             name: model_rdf.name,
             id: model_rdf.id,
+            training_data,
             weights,
             interface: model_interface,
-        })
+        };
+        Ok((model, mismatches))
+    }
+}
+
+impl ZooModel{
+    /// Inference backend for the first weights format that can be run by this build
+    pub fn inference_backend(&self) -> Result<Option<Box<dyn InferenceBackend>>, SelfTestError>{
+        #[cfg(feature = "onnx")]
+        if let Some(weights) = self.weights.onnx(){
+            let model = crate::onnx_backend::OnnxModel::try_load(weights, &self.interface)
+                .map_err(|err| SelfTestError::InferenceError(Box::new(err)))?;
+            return Ok(Some(Box::new(model)))
+        }
+        Ok(None)
+    }
+
+    /// Runs the model on its input test tensors with [Self::inference_backend],
+    /// comparing the results against the output test tensors
+    pub fn run_self_test(&self, tolerance: Tolerance) -> Result<SelfTestReport, SelfTestError>{
+        let backend = self.inference_backend()?.ok_or(SelfTestError::NoAvailableBackend)?;
+        self.run_self_test_with(backend.as_ref(), tolerance)
+    }
+
+    pub fn run_self_test_with(&self, backend: &(impl InferenceBackend + ?Sized), tolerance: Tolerance) -> Result<SelfTestReport, SelfTestError>{
+        self_test::run_self_test(&self.interface, backend, tolerance)
     }
 }
 
 impl ZooModel {
     pub fn pack_into_tmp(self) -> Result<std::fs::File, ModelPackingError>{
         let mut tmp_file = tempfile::tempfile()?;
-        self.pack_into(&mut tmp_file)?;
+        self.pack_into(&mut tmp_file, CompressionPolicy::default())?;
         tmp_file.rewind()?;
         Ok(tmp_file)
     }
-    pub fn pack_into<Sink: Write + Seek>(self, sink: Sink) -> Result<(), ModelPackingError> {
-        let mut writer = ModelZipWriter::new(sink);
+    pub fn pack_into<Sink: Write + Seek>(self, sink: Sink, compression: CompressionPolicy) -> Result<(), ModelPackingError> {
+        self.pack_into_with(sink, EntryNaming::default(), compression)
+    }
+    /// Packs the model into a zip file at `path`, reporting to (and obeying cancellation from) `progress`.
+    ///
+    /// The zip is written to a temporary file next to `path` and only moved over it once packing succeeds,
+    /// so a failed or cancelled packing leaves any previous file at `path` untouched.
+    pub fn pack_into_path(
+        self, path: &Path, compression: CompressionPolicy, progress: PackingProgress
+    ) -> Result<(), ModelPackingError> {
+        let parent_dir = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut tmp_file = tempfile::NamedTempFile::new_in(parent_dir)?;
+        let timestamp = iso8601_timestamp::Timestamp::now_utc();
+        let writer = ModelZipWriter::new(tmp_file.as_file_mut()).with_compression(compression).with_progress(progress);
+        self.pack_with(writer, timestamp)?;
+        tmp_file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+    pub fn pack_into_with<Sink: Write + Seek>(
+        self, sink: Sink, naming: EntryNaming, compression: CompressionPolicy
+    ) -> Result<(), ModelPackingError> {
+        let timestamp = iso8601_timestamp::Timestamp::now_utc();
+        self.pack_with(ModelZipWriter::new(sink).with_entry_naming(naming).with_compression(compression), timestamp)
+    }
+    /// Packs the model so that the same model and `timestamp` always produce a byte-identical zip
+    pub fn pack_reproducibly<Sink: Write + Seek>(
+        self, sink: Sink, timestamp: iso8601_timestamp::Timestamp
+    ) -> Result<(), ModelPackingError> {
+        self.pack_with(ModelZipWriter::new(sink).reproducible(), timestamp)
+    }
+    /// Writes the model unpacked into `dir`, which must either not exist or be empty.
+    ///
+    /// Files get human-readable names like `README.md` or `onnx_weights.onnx`, so that the contents
+    /// of the package can be inspected (or diffed) without having to map uuids back to their roles.
+    /// They are written as plain files, so there is no compression to configure.
+    pub fn pack_into_dir(self, dir: &Path) -> Result<(), ModelPackingError> {
+        let dir_existed = dir.exists();
+        if dir_existed && std::fs::read_dir(dir)?.next().is_some(){
+            return Err(ModelPackingError::AlreadyExists(dir.to_owned()))
+        }
+        let timestamp = iso8601_timestamp::Timestamp::now_utc();
+        let result = ModelZipWriter::new_dir(dir)
+            .and_then(|writer| self.pack_with(writer.with_entry_naming(EntryNaming::Readable), timestamp));
+        if result.is_err(){
+            if dir_existed{
+                // the directory was empty, so everything in it was written here. It is kept itself, since the
+                // caller might have set it up with specific permissions or mounted something on it
+                for entry in std::fs::read_dir(dir).into_iter().flatten().flatten(){
+                    let path = entry.path();
+                    _ = if path.is_dir(){ std::fs::remove_dir_all(path) } else { std::fs::remove_file(path) };
+                }
+            } else {
+                _ = std::fs::remove_dir_all(dir);
+            }
+        }
+        result
+    }
+    fn pack_with<Sink: Write + Seek>(
+        self, mut writer: ModelZipWriter<Sink>, timestamp: iso8601_timestamp::Timestamp
+    ) -> Result<(), ModelPackingError> {
+        let expected_total_bytes = self.attachments.iter()
+            .chain(self.weights.file_sources())
+            .chain(self.training_data.iter().flat_map(|training_data| training_data.file_sources()))
+            .map(|source| source.size_hint())
+            .sum::<Option<u64>>();
+        writer.progress().set_expected_total_bytes(expected_total_bytes);
 
         let (inputs, outputs) = self.interface.dump(&mut writer)?;
         let covers = self.covers.iter().map(|cov| {
@@ -232,16 +366,17 @@ impl ZooModel {
             None => None,
         };
         let documentation: FileReference = {
-            let documentation_path = FsPath::unique_suffixed("_README.md");
-            writer.write_file(&documentation_path, |writer| -> Result<FileReference, std::io::Error> {
-                writer.write_all(self.documentation.as_bytes())?;
-                Ok(FileReference::Path(documentation_path.clone()))
-            })?
+            let (_, documentation_path, _) = writer.write_new_file("README.md", |writer| {
+                writer.write_all(self.documentation.as_bytes())
+            })?;
+            FileReference::Path(documentation_path)
         };
-        let timestamp = iso8601_timestamp::Timestamp::now_utc();
+        let training_data = self.training_data.as_ref()
+            .map(|training_data| training_data.rdf_dump(&mut writer))
+            .transpose()?;
         let weights = self.weights.rdf_dump(&mut writer)?;
 
-        let model_rdf = ModelRdfV0_5 {
+        let mut model_rdf = ModelRdfV0_5 {
             description: self.description,
             covers,
             id: None,
@@ -264,9 +399,10 @@ impl ZooModel {
             outputs,
             run_mode: None,
             timestamp,
-            training_data: None, //FIXME
+            training_data,
             weights,
         };
+        model_rdf.format_version = model_rdf.minimum_format_version();
         let model_json_val = serde_json::to_value(&model_rdf).unwrap();
 
         let rdf_file_name = FsPathComponent::try_from("rdf.yaml".to_owned()).unwrap();
@@ -277,3 +413,177 @@ impl ZooModel {
         Ok(())
     }
 }
+
+/// A minimal model whose onnx weights are read from `weights_path`
+#[cfg(test)]
+pub(crate) fn test_model(weights_path: &Path) -> ZooModel{
+    use crate::model_weights::{OnnxWeights, WeightsBase};
+
+    let x_axis = serde_json::json!([{"type": "space", "id": "x", "size": 4}]);
+    let interface = ModelInterface::try_build(
+        vec![InputSlot{
+            tensor_meta: serde_json::from_value(serde_json::json!({"id": "raw", "axes": x_axis})).unwrap(),
+            test_tensor: Arc::new(NpyArray::from(ndarray::array![1.0f32, 2.0, 3.0, 4.0].into_dyn())),
+        }],
+        vec![OutputSlot{
+            tensor_meta: serde_json::from_value(serde_json::json!({"id": "out", "axes": x_axis})).unwrap(),
+            test_tensor: Arc::new(NpyArray::from(ndarray::array![1.0f32, 2.0, 3.0, 4.0].into_dyn())),
+        }],
+    ).unwrap();
+    let weights = OnnxWeights{
+        weights: WeightsBase{source: FileSource::LocalFile{path: Arc::from(weights_path)}, authors: None, comment: None},
+        opset_version: 13.try_into().unwrap(),
+    };
+    ZooModel{
+        description: rdf::ResourceTextDescription::try_from("A model for testing".to_owned()).unwrap(),
+        covers: vec![],
+        attachments: vec![],
+        cite: serde_json::from_value(serde_json::json!([{"text": "Someone et al.", "doi": "10.1000/182"}])).unwrap(),
+        config: Default::default(),
+        git_repo: None,
+        icon: None,
+        links: vec![],
+        maintainers: vec![],
+        tags: vec![],
+        version: None,
+        authors: serde_json::from_value(serde_json::json!([{"name": "Someone"}])).unwrap(),
+        documentation: "# Test model".to_owned(),
+        license: serde_json::from_value(serde_json::json!("MIT")).unwrap(),
+        name: ModelRdfName::try_from("Test Model".to_owned()).unwrap(),
+        id: None,
+        training_data: None,
+        weights: ModelWeights::new(None, Some(weights), None, None, None, None).unwrap(),
+        interface,
+    }
+}
+
+#[test]
+fn test_hash_mismatches_are_returned(){
+    let dir = tempfile::tempdir().unwrap();
+    let weights_path = dir.path().join("weights.onnx");
+    std::fs::write(&weights_path, b"weights").unwrap();
+    let package_dir = dir.path().join("model");
+    test_model(&weights_path).pack_into_dir(&package_dir).unwrap();
+
+    let packed_weights = std::fs::read_dir(&package_dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "onnx"))
+        .unwrap();
+    std::fs::write(packed_weights, b"tampered").unwrap();
+
+    let (_, mismatches) = ZooModel::try_load_archive_with(
+        SharedZipArchive::open(&package_dir).unwrap(), HashVerification::WarnOnly
+    ).unwrap();
+    assert_eq!(mismatches.0.len(), 1);
+    assert!(matches!(
+        ZooModel::try_load(&package_dir),
+        Err(ModelLoadingError::HashMismatch(HashMismatches(mismatches))) if mismatches.len() == 1
+    ));
+}
+
+#[test]
+fn test_pack_reproducibly(){
+    let dir = tempfile::tempdir().unwrap();
+    let weights_path = dir.path().join("weights.onnx");
+    std::fs::write(&weights_path, b"weights").unwrap();
+    let timestamp = iso8601_timestamp::Timestamp::UNIX_EPOCH;
+
+    let pack = || -> Vec<u8> {
+        let mut packed = std::io::Cursor::new(Vec::<u8>::new());
+        test_model(&weights_path).pack_reproducibly(&mut packed, timestamp).unwrap();
+        packed.into_inner()
+    };
+    let first = pack();
+    assert_eq!(first, pack());
+
+    let packed_path = dir.path().join("model.zip");
+    std::fs::write(&packed_path, &first).unwrap();
+    ZooModel::try_load(&packed_path).unwrap();
+}
+
+#[test]
+fn test_pack_into_path_keeps_previous_file_on_failure(){
+    let dir = tempfile::tempdir().unwrap();
+    let weights_path = dir.path().join("weights.onnx");
+    std::fs::write(&weights_path, b"weights").unwrap();
+    let packed_path = dir.path().join("model.zip");
+    std::fs::write(&packed_path, b"previous").unwrap();
+
+    let progress = PackingProgress::default();
+    progress.cancel();
+    let result = test_model(&weights_path).pack_into_path(&packed_path, CompressionPolicy::default(), progress);
+    assert!(matches!(result, Err(ModelPackingError::Cancelled)));
+    assert_eq!(std::fs::read(&packed_path).unwrap(), b"previous");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    test_model(&weights_path).pack_into_path(&packed_path, CompressionPolicy::StoreAll, PackingProgress::default()).unwrap();
+    ZooModel::try_load(&packed_path).unwrap();
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&packed_path).unwrap()).unwrap();
+    for idx in 0..archive.len(){
+        assert_eq!(archive.by_index(idx).unwrap().compression(), zip::CompressionMethod::Stored);
+    }
+}
+
+#[test]
+fn test_pack_into_dir_cleans_up_on_failure(){
+    let dir = tempfile::tempdir().unwrap();
+    let missing_weights = dir.path().join("missing.onnx");
+
+    let new_dir = dir.path().join("new");
+    assert!(test_model(&missing_weights).pack_into_dir(&new_dir).is_err());
+    assert!(!new_dir.exists());
+
+    let existing_dir = dir.path().join("existing");
+    std::fs::create_dir(&existing_dir).unwrap();
+    assert!(test_model(&missing_weights).pack_into_dir(&existing_dir).is_err());
+    assert!(existing_dir.is_dir());
+    assert_eq!(std::fs::read_dir(&existing_dir).unwrap().count(), 0);
+}
+
+#[test]
+fn test_load_legacy_package(){
+    let dir = tempfile::tempdir().unwrap();
+    let rdf = |mode: &str| serde_json::json!({
+        "format_version": "0.4.10",
+        "type": "model",
+        "name": "Legacy UNet 2D",
+        "description": "A legacy model",
+        "authors": [{"name": "Someone"}],
+        "cite": [{"text": "Some paper", "doi": "10.1000/182"}],
+        "license": "MIT",
+        "documentation": "README.md",
+        "test_inputs": ["test_input.npy"],
+        "test_outputs": ["test_output.npy"],
+        "inputs": [{
+            "name": "raw",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": {"min": [1, 1, 8, 8], "step": [0, 0, 4, 4]},
+            "preprocessing": [{"name": "zero_mean_unit_variance", "kwargs": {"mode": mode, "axes": "yx"}}],
+        }],
+        "outputs": [{
+            "name": "mask",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": {"reference_tensor": "raw", "scale": [1.0, 1.0, 0.5, 0.5], "offset": [0, 0, 2, 2]},
+            "halo": [0, 0, 2, 2],
+        }],
+        "weights": {"onnx": {"source": "weights.onnx"}},
+    });
+    std::fs::write(dir.path().join("README.md"), "# Legacy model").unwrap();
+    std::fs::write(dir.path().join("weights.onnx"), b"weights").unwrap();
+    let test_tensor = ndarray::Array4::<f32>::zeros((1, 1, 8, 8));
+    ndarray_npy::write_npy(dir.path().join("test_input.npy"), &test_tensor).unwrap();
+    ndarray_npy::write_npy(dir.path().join("test_output.npy"), &test_tensor).unwrap();
+
+    std::fs::write(dir.path().join("rdf.yaml"), serde_yaml::to_string(&rdf("per_sample")).unwrap()).unwrap();
+    let model = ZooModel::try_load(dir.path()).unwrap();
+    assert_eq!(model.interface.inputs()[0].tensor_meta.id.to_string(), "raw");
+    assert_eq!(model.interface.outputs()[0].tensor_meta.id.to_string(), "mask");
+
+    std::fs::write(dir.path().join("rdf.yaml"), serde_yaml::to_string(&rdf("per_dataset")).unwrap()).unwrap();
+    assert!(matches!(
+        ZooModel::try_load(dir.path()),
+        Err(ModelLoadingError::LegacyModelConversionError(modelrdf::model_rdf_0_4::LegacyModelConversionError::UnsupportedProcessingMode{..}))
+    ));
+}
@@ -17,5 +17,22 @@ impl<R: Borrow<FileReference>> Display for FileDescription<R>{
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub struct Sha256(Lowercase<BoundedString<64, 64>>);
 
+impl Sha256{
+    pub fn from_digest(digest: [u8; 32]) -> Self{
+        let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        Self(Lowercase::try_from(hex).expect("64 lowercase hex digits should be a valid sha256"))
+    }
+
+    pub fn as_str(&self) -> &str{
+        &self.0
+    }
+}
+
+impl Display for Sha256{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 
 pub type EnvironmentFileDescr = FileDescription<EnvironmentFile>;
\ No newline at end of file
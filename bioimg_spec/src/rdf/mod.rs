@@ -36,7 +36,7 @@ pub use literal::{LiteralInt, LitStr};
 pub use version::Version;
 pub use file_reference::{HttpUrl, FsPath, FileReference, CoverImageSource, EnvironmentFile};
 pub use author::Author2;
-pub use file_description::{FileDescription, EnvironmentFileDescr};
+pub use file_description::{FileDescription, EnvironmentFileDescr, Sha256};
 pub use maintainer::{Maintainer, MaintainerName};
 pub use orcid::Orcid;
 pub use cite_entry::CiteEntry2;
@@ -130,6 +130,15 @@ impl InputAxis{
             Self::Space(axis) => Some(axis.size.clone()),
         }
     }
+
+    /// Physical size of one pixel along this axis. Axes without a physical scale behave as if it were 1
+    pub fn scale(&self) -> AxisScale{
+        match self {
+            Self::Time(axis) => axis.scale,
+            Self::Space(axis) => axis.scale,
+            _ => AxisScale::default(),
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -160,6 +160,27 @@ impl OutputAxis{
             Self::Space(axis) => Some(axis.size.size()),
         }
     }
+
+    /// Physical size of one pixel along this axis. Axes without a physical scale behave as if it were 1
+    pub fn scale(&self) -> AxisScale{
+        match self {
+            Self::Time(axis) => axis.scale,
+            Self::Space(axis) => axis.scale,
+            _ => AxisScale::default(),
+        }
+    }
+
+    pub fn halo(&self) -> Option<Halo>{
+        let size = match self {
+            Self::Time(axis) => &axis.size,
+            Self::Space(axis) => &axis.size,
+            _ => return None,
+        };
+        match size{
+            OutputSpacetimeSize::Haloed { halo, .. } => Some(*halo),
+            OutputSpacetimeSize::Standard { .. } => None,
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -1,4 +1,7 @@
-use crate::rdf::{HttpUrl, ResourceId};
+use crate::rdf::{
+    self, Author2, BoundedString, CiteEntry2, CoverImageSource, FileDescription, FileReference, HttpUrl, Icon, LicenseId,
+    Maintainer, ResourceId, ResourceTextDescription, Version,
+};
 
 // A bioimage.io dataset resource description file (dataset RDF) describes a dataset relevant to bioimage
 // processing.
@@ -6,10 +9,22 @@ use crate::rdf::{HttpUrl, ResourceId};
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum DatasetDescrEnum{
-    DatasetDescr(DatasetDescr),
+    DatasetDescr(Box<DatasetDescr>),
     LinkedDatasetDescr(LinkedDatasetDescr),
 }
 
+impl From<DatasetDescr> for DatasetDescrEnum{
+    fn from(value: DatasetDescr) -> Self {
+        Self::DatasetDescr(Box::new(value))
+    }
+}
+
+impl From<LinkedDatasetDescr> for DatasetDescrEnum{
+    fn from(value: LinkedDatasetDescr) -> Self {
+        Self::LinkedDatasetDescr(value)
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(into = "String")]
 #[serde(try_from = "String")]
@@ -32,20 +47,117 @@ impl TryFrom<String> for DatasetDescrMarker{
     }
 }
 
+pub type DatasetName = BoundedString<1, 128>;
+
+fn _default_dataset_format_version() -> Version{
+    Version::version_0_3_0()
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct DatasetDescr{
+    /// Version of the bioimage.io dataset description specification used
+    #[serde(default = "_default_dataset_format_version")]
+    pub format_version: Version,
+
     #[serde(rename = "type")]
-    marker: DatasetDescrMarker,
-    /// URL to the source of the dataset
+    pub marker: DatasetDescrMarker,
+
+    /// A human-friendly name of the dataset
+    pub name: DatasetName,
+
+    /// A string containing a brief description.
+    pub description: ResourceTextDescription,
+
+    /// Cover images.
     #[serde(default)]
-    source: Option<HttpUrl>
-}
+    pub covers: Vec<CoverImageSource>,
+
+    /// bioimage.io wide, unique identifier assigned by the
+    /// [bioimage.io collection](https://github.com/bioimage-io/collection-bioimage-io)
+    #[serde(default)]
+    pub id: Option<ResourceId>,
+
+    /// The authors are the creators of the dataset description and the primary points of contact.
+    #[serde(default)]
+    pub authors: Vec<Author2>,
+
+    /// file attachments
+    #[serde(default)]
+    pub attachments: Vec<FileDescription>,
 
+    /// citations
+    #[serde(default)]
+    pub cite: Vec<CiteEntry2>,
 
+    /// A [SPDX license identifier](https://spdx.org/licenses/).
+    #[serde(default)]
+    pub license: Option<LicenseId>,
+
+    /// A field for custom configuration that can contain any keys not present in the RDF spec.
+    #[serde(default)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+
+    /// A URL to the Git repository where the resource is being developed
+    #[serde(default)]
+    pub git_repo: Option<HttpUrl>,
+
+    /// An icon for illustration, e.g. on bioimage.io
+    #[serde(default)]
+    pub icon: Option<Icon>,
+
+    /// IDs of other bioimage.io resources
+    #[serde(default)]
+    pub links: Vec<String>,
+
+    /// Maintainers of this resource.
+    #[serde(default)]
+    pub maintainers: Vec<Maintainer>,
+
+    /// Associated tags
+    #[serde(default)]
+    pub tags: Vec<rdf::Tag>,
+
+    /// The version number of the resource, in `MAJOR.MINOR.PATCH` format
+    #[serde(default)]
+    pub version: Option<Version>,
+
+    /// URL or relative path to a markdown file with additional documentation.
+    #[serde(default)]
+    pub documentation: Option<FileReference>,
+
+    /// URL to the source of the dataset
+    #[serde(default)]
+    pub source: Option<HttpUrl>,
+}
 
 /// Reference to a bioimage.io dataset.
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct LinkedDatasetDescr{
     /// A valid dataset `id` from the bioimage.io collection.
-    id: ResourceId
+    pub id: ResourceId
+}
+
+#[test]
+fn test_training_data_variants(){
+    let linked: DatasetDescrEnum = serde_json::from_value(serde_json::json!({"id": "some-dataset"})).unwrap();
+    assert!(matches!(linked, DatasetDescrEnum::LinkedDatasetDescr(_)));
+
+    let inline: DatasetDescrEnum = serde_json::from_value(serde_json::json!({
+        "type": "dataset",
+        "name": "Nuclei",
+        "description": "Fluorescence images of nuclei",
+        "authors": [{"name": "Someone"}],
+        "license": "CC-BY-4.0",
+        "documentation": "dataset_README.md",
+        "source": "https://example.com/nuclei.zip",
+    })).unwrap();
+    let DatasetDescrEnum::DatasetDescr(dataset) = &inline else {
+        panic!("Expected an inline dataset description");
+    };
+    assert_eq!(dataset.format_version, Version::version_0_3_0());
+    assert_eq!(dataset.authors.len(), 1);
+
+    let serialized = serde_json::to_value(&inline).unwrap();
+    assert_eq!(serialized["type"], "dataset");
+    assert_eq!(serialized["format_version"], "0.3.0");
 }
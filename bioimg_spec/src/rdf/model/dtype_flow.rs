@@ -0,0 +1,148 @@
+//! Tracking of data types as they flow through pre- and postprocessing steps
+//!
+//! A processing chain is always run with implicit `ensure_dtype` steps at its ends (see
+//! [super::input_tensor::InputTensorMetadata::normalized_preprocessing] and
+//! [super::output_tensor::OutputTensorMetadata::normalized_postprocessing]), so the data type
+//! at every step of the chain is known in advance and can be checked before running anything.
+
+use std::fmt::Display;
+
+use super::{
+    postprocessing::PostprocessingDescr,
+    preprocessing::EnsureDtype,
+    DataType, PreprocessingDescr,
+};
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum DtypeFlowError{
+    #[error("Processing step #{position} ({step}) can't operate on {dtype} values")]
+    UnsupportedInputDtype{position: usize, step: String, dtype: DataType},
+    #[error("Processing produces {produced} values but the tensor data is declared as {declared}")]
+    FinalDtypeMismatch{produced: DataType, declared: DataType},
+}
+
+/// A pre- or postprocessing step, as seen by the dtype-flow analysis
+pub trait ProcessingStep: Clone + Display{
+    fn ensure_dtype(dtype: DataType) -> Self;
+    fn is_ensure_dtype(&self) -> bool;
+    fn is_binarize(&self) -> bool;
+    /// The data type this step produces when fed `input`, or `None` if it can't handle `input`
+    fn output_dtype(&self, input: DataType) -> Option<DataType>;
+}
+
+/// Arithmetic steps compute in float32 and make no sense over booleans
+fn arithmetic_output_dtype(input: DataType) -> Option<DataType>{
+    match input{
+        DataType::Bool => None,
+        _ => Some(DataType::Float32),
+    }
+}
+
+impl ProcessingStep for PreprocessingDescr{
+    fn ensure_dtype(dtype: DataType) -> Self{
+        Self::EnsureDtype(EnsureDtype{dtype})
+    }
+    fn is_ensure_dtype(&self) -> bool{
+        matches!(self, Self::EnsureDtype(_))
+    }
+    fn is_binarize(&self) -> bool{
+        matches!(self, Self::Binarize(_))
+    }
+    fn output_dtype(&self, input: DataType) -> Option<DataType>{
+        match self{
+            Self::EnsureDtype(descr) => Some(descr.dtype),
+            Self::Binarize(_) => Some(DataType::Bool),
+            Self::Clip(_) |
+            Self::ScaleLinear(_) |
+            Self::Sigmoid(_) |
+            Self::FixedZeroMeanUnitVariance(_) |
+            Self::ZeroMeanUnitVariance(_) |
+            Self::ScaleRange(_) => arithmetic_output_dtype(input),
+        }
+    }
+}
+
+impl ProcessingStep for PostprocessingDescr{
+    fn ensure_dtype(dtype: DataType) -> Self{
+        Self::EnsureDtype(EnsureDtype{dtype})
+    }
+    fn is_ensure_dtype(&self) -> bool{
+        matches!(self, Self::EnsureDtype(_))
+    }
+    fn is_binarize(&self) -> bool{
+        matches!(self, Self::Binarize(_))
+    }
+    fn output_dtype(&self, input: DataType) -> Option<DataType>{
+        match self{
+            Self::EnsureDtype(descr) => Some(descr.dtype),
+            Self::Binarize(_) => Some(DataType::Bool),
+            Self::Clip(_) |
+            Self::ScaleLinear(_) |
+            Self::Sigmoid(_) |
+            Self::FixedZeroMeanUnitVariance(_) |
+            Self::ZeroMeanUnitVariance(_) |
+            Self::ScaleRange(_) |
+            Self::ScaleMeanVarianceDescr(_) => arithmetic_output_dtype(input),
+        }
+    }
+}
+
+/// Returns `steps` with an `ensure_dtype(leading)` step prepended unless `steps` already starts with a step that
+/// sets the data type, and with an `ensure_dtype(trailing)` appended unless it already ends with one.
+///
+/// `binarize` only counts as setting the data type at the start of the chain if `binarize_leads` is true.
+pub fn with_implicit_ensure_dtype<P: ProcessingStep>(
+    steps: &[P], leading: DataType, trailing: DataType, binarize_leads: bool
+) -> Vec<P>{
+    let mut normalized = Vec::with_capacity(steps.len() + 2);
+    let starts_with_dtype = match steps.first(){
+        Some(step) => step.is_ensure_dtype() || (binarize_leads && step.is_binarize()),
+        None => false,
+    };
+    if !starts_with_dtype{
+        normalized.push(P::ensure_dtype(leading));
+    }
+    normalized.extend_from_slice(steps);
+    if !matches!(normalized.last(), Some(step) if step.is_ensure_dtype() || step.is_binarize()){
+        normalized.push(P::ensure_dtype(trailing));
+    }
+    normalized
+}
+
+/// Follows the data type of a tensor of type `input` through `steps`, returning the resulting data type
+pub fn check_dtype_flow<P: ProcessingStep>(input: DataType, steps: &[P]) -> Result<DataType, DtypeFlowError>{
+    steps.iter().enumerate().try_fold(input, |dtype, (position, step)|{
+        step.output_dtype(dtype).ok_or_else(|| DtypeFlowError::UnsupportedInputDtype {
+            position, step: step.to_string(), dtype
+        })
+    })
+}
+
+#[test]
+fn test_dtype_flow(){
+    use super::preprocessing::{BinarizeDescr, SimpleBinarizeDescr, Zmuv};
+
+    let binarize = PreprocessingDescr::Binarize(BinarizeDescr::Simple(SimpleBinarizeDescr{threshold: 0.5}));
+    let zmuv = PreprocessingDescr::ZeroMeanUnitVariance(Zmuv{axes: None, eps: Default::default()});
+
+    let normalized = with_implicit_ensure_dtype(std::slice::from_ref(&zmuv), DataType::Uint8, DataType::Float32, false);
+    assert!(matches!(
+        normalized.as_slice(),
+        [
+            PreprocessingDescr::EnsureDtype(EnsureDtype{dtype: DataType::Uint8}),
+            PreprocessingDescr::ZeroMeanUnitVariance(_),
+            PreprocessingDescr::EnsureDtype(EnsureDtype{dtype: DataType::Float32}),
+        ]
+    ));
+    assert!(matches!(check_dtype_flow(DataType::Uint8, &normalized), Ok(DataType::Float32)));
+
+    let normalized = with_implicit_ensure_dtype(std::slice::from_ref(&binarize), DataType::Float32, DataType::Float32, false);
+    assert_eq!(normalized.len(), 2);
+    assert!(matches!(check_dtype_flow(DataType::Float32, &normalized), Ok(DataType::Bool)));
+
+    let normalized = with_implicit_ensure_dtype(&[binarize, zmuv], DataType::Float32, DataType::Float32, false);
+    assert!(matches!(
+        check_dtype_flow(DataType::Float32, &normalized),
+        Err(DtypeFlowError::UnsupportedInputDtype{position: 2, dtype: DataType::Bool, ..})
+    ));
+}
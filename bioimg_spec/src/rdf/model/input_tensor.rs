@@ -4,12 +4,18 @@ use serde::{Deserialize, Serialize};
 
 use crate::rdf::FileDescription;
 
-use super::{axes::input_axes::InputAxisGroup, preprocessing::{BinarizeDescr, PreprocessingDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId, TensorId, TensorTextDescription};
+use super::{dtype_flow::{check_dtype_flow, with_implicit_ensure_dtype, DtypeFlowError}, DataType, axes::input_axes::{InputAxis, InputAxisGroup}, tensor_data_descr::TensorDataDescrs, preprocessing::{BinarizeDescr, PreprocessingDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId, TensorId, TensorTextDescription};
 
 #[derive(thiserror::Error, Debug)]
 pub enum InputTensorParsingError{
     #[error("Axis reference to non-existing axis")]
     PreprocessingReferencesNonExistingAxis,
+    #[error("Found {num_descrs} per-channel data descriptions but tensor has {num_channels} channels")]
+    DataDoesNotMatchChannels{num_descrs: usize, num_channels: usize},
+    #[error("Per-channel data descriptions have mixed data types ({first} and {other})")]
+    MixedChannelDataTypes{first: DataType, other: DataType},
+    #[error("Bad preprocessing: {0}")]
+    DtypeFlowError(#[from] DtypeFlowError),
 }
 
 
@@ -45,11 +51,27 @@ pub struct InputTensorMetadata {
     ///   changing the data type.
     preprocessing: Vec<PreprocessingDescr>,
     axes: InputAxisGroup,
+    /// Description of the tensor's data values, optionally per channel (float32 if not declared)
+    data: TensorDataDescrs,
+    /// Whether `data` was given explicitly rather than defaulted
+    data_is_declared: bool,
 }
 
 impl InputTensorMetadata{
     pub fn axes(&self) -> &InputAxisGroup{ &self.axes }
     pub fn preprocessing(&self) -> &Vec<PreprocessingDescr>{ &self.preprocessing }
+    pub fn data(&self) -> &TensorDataDescrs{ &self.data }
+    /// The `data` as written in the rdf, if it was given at all
+    pub fn declared_data(&self) -> Option<&TensorDataDescrs>{ self.data_is_declared.then_some(&self.data) }
+
+    /// The preprocessing steps including the implicit `ensure_dtype` steps described in [Self::preprocessing]
+    pub fn normalized_preprocessing(&self) -> Vec<PreprocessingDescr>{
+        normalize_preprocessing(&self.preprocessing, &self.data)
+    }
+}
+
+fn normalize_preprocessing(preprocessing: &[PreprocessingDescr], data: &TensorDataDescrs) -> Vec<PreprocessingDescr>{
+    with_implicit_ensure_dtype(preprocessing, data.data_type(), DataType::Float32, false)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,6 +84,8 @@ pub struct InputTensorMetadataMsg {
     #[serde(default)]
     pub description: TensorTextDescription,
     pub axes: InputAxisGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<TensorDataDescrs>,
 }
 
 impl TryFrom<InputTensorMetadataMsg> for InputTensorMetadata{
@@ -100,12 +124,34 @@ impl TryFrom<InputTensorMetadataMsg> for InputTensorMetadata{
             }
         }
 
+        let num_channels = message.axes.iter()
+            .find_map(|axis| match axis{
+                InputAxis::Channel(channel_axis) => Some(usize::from(channel_axis.size())),
+                _ => None,
+            });
+        let data_is_declared = message.data.is_some();
+        let data = message.data.unwrap_or_default();
+        if let TensorDataDescrs::PerChannel(descrs) = &data{
+            if Some(descrs.len().get()) != num_channels{
+                return Err(InputTensorParsingError::DataDoesNotMatchChannels{
+                    num_descrs: descrs.len().get(), num_channels: num_channels.unwrap_or(0),
+                })
+            }
+            let first = data.data_type();
+            if let Some(other) = data.as_slice().iter().map(|descr| descr.data_type()).find(|dtype| *dtype != first){
+                return Err(InputTensorParsingError::MixedChannelDataTypes{first, other})
+            }
+        }
+        check_dtype_flow(data.data_type(), &normalize_preprocessing(&message.preprocessing, &data))?;
+
         Ok(Self{
             id: message.id,
             optional: message.optional,
             preprocessing: message.preprocessing,
             description: message.description,
             axes: message.axes,
+            data,
+            data_is_declared,
         })
     }
 }
@@ -118,6 +164,7 @@ impl From<InputTensorMetadata> for InputTensorMetadataMsg{
             preprocessing: value.preprocessing,
             description: value.description,
             axes: value.axes,
+            data: value.data_is_declared.then_some(value.data),
         }
     }
 }
@@ -5,6 +5,7 @@ pub mod axes;
 pub mod axis_size;
 pub mod data_range;
 pub mod data_type;
+pub mod dtype_flow;
 pub mod input_tensor;
 pub mod output_tensor;
 pub mod preprocessing;
@@ -18,7 +19,7 @@ pub mod run_mode;
 pub mod dataset_descr;
 pub mod unsupported;
 pub mod model_rdf_0_5;
-// pub mod model_rdf_0_4;
+pub mod model_rdf_0_4;
 
 pub use axes::{
     AxisType, AxisId, AxisScale,
@@ -49,7 +50,10 @@ pub use weights::{
 };
 pub use preprocessing::PreprocessingDescr;
 pub use data_type::DataType;
+pub use tensor_data_descr::{TensorDataDescr, TensorDataDescrs};
 pub use model_rdf_0_5::ModelRdfV0_5;
+pub use model_rdf_0_4::ModelRdfV0_4;
+pub use dataset_descr::{DatasetDescr, DatasetDescrEnum, LinkedDatasetDescr};
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(try_from = "String")]
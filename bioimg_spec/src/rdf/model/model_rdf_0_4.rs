@@ -0,0 +1,837 @@
+//! Model descriptions in the legacy 0.4.x format and their conversion to 0.5
+
+use serde_json::{json, Map, Value};
+
+use crate::rdf::version::{Version_0_4_x, Version_0_5_x};
+use crate::rdf::Version;
+use super::ModelRdfV0_5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LegacyModelConversionError{
+    #[error("Tensor '{tensor}' has axes '{axes}' but a shape of length {shape_len}")]
+    AxesShapeMismatch{tensor: String, axes: String, shape_len: usize},
+    #[error("Tensor '{tensor}' has an unknown axis '{axis}'")]
+    UnknownAxis{tensor: String, axis: char},
+    #[error("Channel axis of tensor '{tensor}' must have a fixed size")]
+    NonFixedChannelAxis{tensor: String},
+    #[error("Shape of tensor '{tensor}' can't be expressed in the 0.5 format")]
+    UnsupportedShape{tensor: String},
+    #[error("Tensor '{tensor}' references unknown tensor '{reference}'")]
+    UnknownReferenceTensor{tensor: String, reference: String},
+    #[error("Axis '{axis}' of tensor '{tensor}' can't be expressed in terms of tensor '{reference}'")]
+    BadReferenceAxis{tensor: String, axis: char, reference: String},
+    #[error("Axis '{axis}' of tensor '{tensor}' has an unsupported size (scale {scale}, offset {offset})")]
+    BadImplicitSize{tensor: String, axis: char, scale: f32, offset: f32},
+    #[error("Unknown processing step '{name}' in tensor '{tensor}'")]
+    UnknownProcessing{tensor: String, name: String},
+    #[error("'{name}' of tensor '{tensor}' uses mode '{mode}', which can't be expressed in the 0.5 format")]
+    UnsupportedProcessingMode{tensor: String, name: String, mode: String},
+    #[error("'{name}' of tensor '{tensor}' is missing its '{kwarg}' argument")]
+    MissingProcessingKwarg{tensor: String, name: String, kwarg: String},
+    #[error("Could not determine along which axis '{name}' of tensor '{tensor}' applies")]
+    AmbiguousProcessingAxis{tensor: String, name: String},
+    #[error("Expected {expected} test tensors, found {found}")]
+    TestTensorCountMismatch{expected: usize, found: usize},
+    #[error("Bad pytorch architecture: '{0}'. Expected 'path/to/file.py:Callable' or 'module.path:Callable'")]
+    BadPytorchArchitecture(String),
+    #[error("Unsupported weights dependencies '{0}'. Only conda environments are supported")]
+    UnsupportedDependencies(String),
+    #[error("Training dataset has unsupported format version '{0}'. Only 0.2.x and 0.3.x are supported")]
+    UnsupportedDatasetVersion(String),
+    #[error("Training dataset attachments have unsupported entries besides 'files': {0:?}")]
+    UnsupportedDatasetAttachments(Vec<String>),
+    #[error("Converted model is not valid: {0}")]
+    InvalidConversion(#[from] serde_json::Error),
+}
+
+type ConversionResult<T> = Result<T, LegacyModelConversionError>;
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TensorShapeV0_4{
+    Explicit(Vec<usize>),
+    Parameterized{min: Vec<usize>, step: Vec<usize>},
+    Implicit{reference_tensor: String, scale: Vec<f32>, offset: Vec<f32>},
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ProcessingV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub kwargs: Map<String, Value>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct InputTensorV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// One letter per axis, e.g. "bcyx"
+    pub axes: String,
+    pub data_type: String,
+    /// `None` means unbounded (e.g. `-inf`/`inf`)
+    #[serde(default)]
+    pub data_range: Option<(Option<f32>, Option<f32>)>,
+    pub shape: TensorShapeV0_4,
+    #[serde(default)]
+    pub preprocessing: Vec<ProcessingV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OutputTensorV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// One letter per axis, e.g. "bcyx"
+    pub axes: String,
+    pub data_type: String,
+    /// `None` means unbounded (e.g. `-inf`/`inf`)
+    #[serde(default)]
+    pub data_range: Option<(Option<f32>, Option<f32>)>,
+    pub shape: TensorShapeV0_4,
+    #[serde(default)]
+    pub halo: Option<Vec<u64>>,
+    #[serde(default)]
+    pub postprocessing: Vec<ProcessingV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WeightsEntryV0_4{
+    pub source: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub authors: Option<Value>,
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// e.g. "conda:environment.yaml"
+    #[serde(default)]
+    pub dependencies: Option<String>,
+    /// e.g. "unet.py:UNet2d" or "my_package.models:UNet2d"
+    #[serde(default)]
+    pub architecture: Option<String>,
+    #[serde(default)]
+    pub architecture_sha256: Option<String>,
+    #[serde(default)]
+    pub kwargs: Map<String, Value>,
+    #[serde(default)]
+    pub pytorch_version: Option<Version>,
+    #[serde(default)]
+    pub tensorflow_version: Option<Version>,
+    #[serde(default)]
+    pub opset_version: Option<u32>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WeightsV0_4{
+    #[serde(default)]
+    pub keras_hdf5: Option<WeightsEntryV0_4>,
+    #[serde(default)]
+    pub onnx: Option<WeightsEntryV0_4>,
+    #[serde(default)]
+    pub pytorch_state_dict: Option<WeightsEntryV0_4>,
+    #[serde(default)]
+    pub tensorflow_js: Option<WeightsEntryV0_4>,
+    #[serde(default)]
+    pub tensorflow_saved_model_bundle: Option<WeightsEntryV0_4>,
+    #[serde(default)]
+    pub torchscript: Option<WeightsEntryV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct AttachmentsV0_4{
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct RunModeV0_4{
+    pub name: String,
+}
+
+/// A model description in the 0.4.x format.
+///
+/// Fields whose shape didn't change in 0.5 are kept as raw values and only validated on conversion.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ModelRdfV0_4{
+    pub format_version: Version_0_4_x,
+    pub name: String,
+    pub description: String,
+    pub authors: Value,
+    pub cite: Value,
+    pub license: Value,
+    pub documentation: String,
+    #[serde(default)]
+    pub tags: Vec<Value>,
+    #[serde(default)]
+    pub covers: Vec<String>,
+    #[serde(default)]
+    pub attachments: Option<AttachmentsV0_4>,
+    #[serde(default)]
+    pub icon: Option<Value>,
+    #[serde(default)]
+    pub id: Option<Value>,
+    #[serde(default)]
+    pub config: Map<String, Value>,
+    #[serde(default)]
+    pub git_repo: Option<Value>,
+    #[serde(default)]
+    pub links: Vec<Value>,
+    #[serde(default)]
+    pub maintainers: Vec<Value>,
+    #[serde(default)]
+    pub version: Option<Value>,
+    #[serde(default)]
+    pub timestamp: Option<Value>,
+    #[serde(default)]
+    pub run_mode: Option<RunModeV0_4>,
+    #[serde(default)]
+    pub training_data: Option<Value>,
+    pub inputs: Vec<InputTensorV0_4>,
+    pub outputs: Vec<OutputTensorV0_4>,
+    pub test_inputs: Vec<String>,
+    pub test_outputs: Vec<String>,
+    #[serde(default)]
+    pub sample_inputs: Vec<String>,
+    #[serde(default)]
+    pub sample_outputs: Vec<String>,
+    pub weights: WeightsV0_4,
+}
+
+fn axis_id(tensor: &str, letter: char) -> ConversionResult<&'static str>{
+    Ok(match letter{
+        'b' => "batch",
+        'c' => "channel",
+        'i' => "index",
+        't' => "time",
+        'x' => "x",
+        'y' => "y",
+        'z' => "z",
+        axis => return Err(LegacyModelConversionError::UnknownAxis{tensor: tensor.to_owned(), axis}),
+    })
+}
+
+fn tensor_id(name: &str) -> String{
+    name.to_lowercase()
+}
+
+fn check_axes_len(tensor: &str, axes: &str, shape_len: usize) -> ConversionResult<()>{
+    if axes.chars().count() != shape_len{
+        return Err(LegacyModelConversionError::AxesShapeMismatch{
+            tensor: tensor.to_owned(), axes: axes.to_owned(), shape_len
+        })
+    }
+    Ok(())
+}
+
+fn channel_names(count: usize) -> Value{
+    Value::Array((0..count).map(|idx| Value::String(format!("channel{idx}"))).collect())
+}
+
+/// Builds a 0.5 axis of type matching `letter` with the given (non-channel) `size`
+fn axis_with_size(tensor: &str, letter: char, size: Value) -> ConversionResult<Value>{
+    let id = axis_id(tensor, letter)?;
+    Ok(match letter{
+        'b' => json!({"type": "batch"}),
+        'c' => match size.as_u64(){
+            Some(count) => json!({"type": "channel", "channel_names": channel_names(count as usize)}),
+            None => return Err(LegacyModelConversionError::NonFixedChannelAxis{tensor: tensor.to_owned()}),
+        },
+        'i' => json!({"type": "index", "size": size}),
+        't' => json!({"type": "time", "id": id, "size": size}),
+        _ => json!({"type": "space", "id": id, "size": size}),
+    })
+}
+
+fn input_axes(tensor: &InputTensorV0_4) -> ConversionResult<Vec<Value>>{
+    let letters = tensor.axes.chars();
+    match &tensor.shape{
+        TensorShapeV0_4::Explicit(shape) => {
+            check_axes_len(&tensor.name, &tensor.axes, shape.len())?;
+            letters.zip(shape.iter())
+                .map(|(letter, size)| axis_with_size(&tensor.name, letter, json!(size)))
+                .collect()
+        },
+        TensorShapeV0_4::Parameterized{min, step} => {
+            check_axes_len(&tensor.name, &tensor.axes, min.len())?;
+            check_axes_len(&tensor.name, &tensor.axes, step.len())?;
+            letters.zip(min.iter().zip(step.iter()))
+                .map(|(letter, (min, step))| {
+                    let size = if *step == 0 { json!(min) } else { json!({"min": min, "step": step}) };
+                    axis_with_size(&tensor.name, letter, size)
+                })
+                .collect()
+        },
+        TensorShapeV0_4::Implicit{..} => Err(LegacyModelConversionError::UnsupportedShape{tensor: tensor.name.clone()}),
+    }
+}
+
+/// In 0.4, an implicit output size is `reference_size * scale + 2 * offset`
+fn output_axes(tensor: &OutputTensorV0_4, inputs: &[InputTensorV0_4]) -> ConversionResult<Vec<Value>>{
+    let mut axes: Vec<Value> = match &tensor.shape{
+        TensorShapeV0_4::Explicit(shape) => {
+            check_axes_len(&tensor.name, &tensor.axes, shape.len())?;
+            tensor.axes.chars().zip(shape.iter())
+                .map(|(letter, size)| axis_with_size(&tensor.name, letter, json!(size)))
+                .collect::<Result<_, _>>()?
+        },
+        // outputs can't be parameterized on their own, so this only works if the size is actually fixed
+        TensorShapeV0_4::Parameterized{min, step} => {
+            check_axes_len(&tensor.name, &tensor.axes, min.len())?;
+            if step.iter().any(|step| *step != 0){
+                return Err(LegacyModelConversionError::UnsupportedShape{tensor: tensor.name.clone()})
+            }
+            tensor.axes.chars().zip(min.iter())
+                .map(|(letter, size)| axis_with_size(&tensor.name, letter, json!(size)))
+                .collect::<Result<_, _>>()?
+        },
+        TensorShapeV0_4::Implicit{reference_tensor, scale, offset} => {
+            check_axes_len(&tensor.name, &tensor.axes, scale.len())?;
+            check_axes_len(&tensor.name, &tensor.axes, offset.len())?;
+            let Some(reference) = inputs.iter().find(|inp| inp.name == *reference_tensor) else {
+                return Err(LegacyModelConversionError::UnknownReferenceTensor{
+                    tensor: tensor.name.clone(), reference: reference_tensor.clone()
+                })
+            };
+            let reference_axes = input_axes(reference)?;
+            tensor.axes.chars().zip(scale.iter().zip(offset.iter()))
+                .map(|(letter, (scale, offset))| {
+                    implicit_axis(tensor, letter, *scale, *offset, reference, &reference_axes)
+                })
+                .collect::<Result<_, _>>()?
+        },
+    };
+
+    if let Some(halo) = &tensor.halo{
+        check_axes_len(&tensor.name, &tensor.axes, halo.len())?;
+        for (axis, halo) in axes.iter_mut().zip(halo.iter()){
+            let is_spacetime = matches!(axis["type"].as_str(), Some("space" | "time"));
+            if *halo > 0 && is_spacetime{
+                axis["halo"] = json!(halo);
+            }
+        }
+    }
+    Ok(axes)
+}
+
+fn implicit_axis(
+    tensor: &OutputTensorV0_4,
+    letter: char,
+    scale: f32,
+    offset: f32,
+    reference: &InputTensorV0_4,
+    reference_axes: &[Value],
+) -> ConversionResult<Value>{
+    let bad_size = || LegacyModelConversionError::BadImplicitSize{tensor: tensor.name.clone(), axis: letter, scale, offset};
+    let total_offset = 2.0 * offset;
+    if total_offset < 0.0 || total_offset.fract() != 0.0{
+        return Err(bad_size())
+    }
+    let total_offset = total_offset as usize;
+    if letter == 'b'{
+        return axis_with_size(&tensor.name, letter, Value::Null)
+    }
+    if scale == 0.0{
+        return axis_with_size(&tensor.name, letter, json!(total_offset))
+    }
+
+    let Some(reference_axis) = reference.axes.chars().position(|ref_letter| ref_letter == letter)
+        .map(|idx| &reference_axes[idx])
+    else {
+        return Err(LegacyModelConversionError::BadReferenceAxis{
+            tensor: tensor.name.clone(), axis: letter, reference: reference.name.clone()
+        })
+    };
+    if letter == 'c'{
+        let num_ref_channels = reference_axis["channel_names"].as_array().map(|names| names.len()).unwrap_or(0);
+        let num_channels = num_ref_channels as f32 * scale + total_offset as f32;
+        if num_channels < 1.0 || num_channels.fract() != 0.0{
+            return Err(bad_size())
+        }
+        return axis_with_size(&tensor.name, letter, json!(num_channels as usize))
+    }
+    if scale < 0.0{
+        return Err(bad_size())
+    }
+    let size = json!({
+        "tensor_id": tensor_id(&reference.name),
+        "axis_id": axis_id(&tensor.name, letter)?,
+        "offset": total_offset,
+    });
+    let mut axis = axis_with_size(&tensor.name, letter, size)?;
+    if matches!(letter, 't' | 'x' | 'y' | 'z'){
+        // 0.5 sizes scale with the ratio between the axes' scales. Reference axes are converted with a scale of 1.0
+        axis["scale"] = json!(1.0 / scale);
+    }
+    Ok(axis)
+}
+
+/// The axis along which per-entry `kwargs` (e.g. a list of gains) vary: the only non-batch axis not reduced over
+fn varying_axis(tensor: &str, tensor_axes: &str, step: &ProcessingV0_4) -> ConversionResult<Value>{
+    let reduced_axes = step.kwargs.get("axes").and_then(|axes| axes.as_str()).unwrap_or("");
+    let mut candidates = tensor_axes.chars().filter(|letter| *letter != 'b' && !reduced_axes.contains(*letter));
+    match (candidates.next(), candidates.next()){
+        (Some(letter), None) => Ok(json!(axis_id(tensor, letter)?)),
+        _ => Err(LegacyModelConversionError::AmbiguousProcessingAxis{tensor: tensor.to_owned(), name: step.name.clone()}),
+    }
+}
+
+fn axis_ids(tensor: &str, kwargs: &Map<String, Value>) -> ConversionResult<Option<Value>>{
+    let Some(axes) = kwargs.get("axes").and_then(|axes| axes.as_str()) else {
+        return Ok(None)
+    };
+    let ids = axes.chars()
+        .map(|letter| axis_id(tensor, letter).map(|id| json!(id)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(Value::Array(ids)))
+}
+
+/// Copies the entries in `keys` that are present in `kwargs`
+fn pick(kwargs: &Map<String, Value>, keys: &[&str]) -> Map<String, Value>{
+    keys.iter()
+        .filter_map(|key| kwargs.get(*key).map(|val| (key.to_string(), val.clone())))
+        .collect()
+}
+
+fn convert_processing(tensor: &str, tensor_axes: &str, step: &ProcessingV0_4) -> ConversionResult<Value>{
+    let kwargs = &step.kwargs;
+    // 0.5 has no per-dataset statistics, and silently computing them per sample would change the model's behavior
+    if let Some(mode @ "per_dataset") = kwargs.get("mode").and_then(|mode| mode.as_str()){
+        return Err(LegacyModelConversionError::UnsupportedProcessingMode{
+            tensor: tensor.to_owned(), name: step.name.clone(), mode: mode.to_owned()
+        })
+    }
+    let (id, mut new_kwargs) = match step.name.as_str(){
+        "binarize" => ("binarize", pick(kwargs, &["threshold"])),
+        "clip" => ("clip", pick(kwargs, &["min", "max"])),
+        "sigmoid" => return Ok(json!({"id": "sigmoid", "kwargs": null})),
+        "scale_linear" => {
+            let mut new_kwargs = pick(kwargs, &["gain", "offset"]);
+            if new_kwargs.values().any(|val| val.is_array()){
+                new_kwargs.insert("axis".into(), varying_axis(tensor, tensor_axes, step)?);
+            }
+            ("scale_linear", new_kwargs)
+        },
+        // in 0.4, mode defaults to "fixed"
+        "zero_mean_unit_variance" if kwargs.get("mode").and_then(|mode| mode.as_str()).unwrap_or("fixed") == "fixed" => {
+            let mut new_kwargs = pick(kwargs, &["mean", "std"]);
+            if let Some(kwarg) = ["mean", "std"].into_iter().find(|kwarg| !new_kwargs.contains_key(*kwarg)){
+                return Err(LegacyModelConversionError::MissingProcessingKwarg{
+                    tensor: tensor.to_owned(), name: step.name.clone(), kwarg: kwarg.to_owned()
+                })
+            }
+            if new_kwargs.values().any(|val| val.is_array()){
+                new_kwargs.insert("axis".into(), varying_axis(tensor, tensor_axes, step)?);
+            }
+            ("fixed_zero_mean_unit_variance", new_kwargs)
+        },
+        "zero_mean_unit_variance" => ("zero_mean_unit_variance", pick(kwargs, &["eps"])),
+        "scale_range" => {
+            let mut new_kwargs = pick(kwargs, &["min_percentile", "max_percentile", "eps"]);
+            if let Some(reference_tensor) = kwargs.get("reference_tensor").and_then(|r| r.as_str()){
+                new_kwargs.insert("reference_tensor".into(), json!(tensor_id(reference_tensor)));
+            }
+            ("scale_range", new_kwargs)
+        },
+        "scale_mean_variance" => {
+            let mut new_kwargs = pick(kwargs, &["eps"]);
+            if let Some(reference_tensor) = kwargs.get("reference_tensor").and_then(|r| r.as_str()){
+                new_kwargs.insert("reference_tensor".into(), json!(tensor_id(reference_tensor)));
+            }
+            ("scale_mean_variance", new_kwargs)
+        },
+        name => return Err(LegacyModelConversionError::UnknownProcessing{tensor: tensor.to_owned(), name: name.to_owned()}),
+    };
+    if matches!(id, "zero_mean_unit_variance" | "scale_range" | "scale_mean_variance"){
+        if let Some(axes) = axis_ids(tensor, kwargs)?{
+            new_kwargs.insert("axes".into(), axes);
+        }
+    }
+    Ok(json!({"id": id, "kwargs": new_kwargs}))
+}
+
+fn tensor_data(data_type: &str, data_range: Option<(Option<f32>, Option<f32>)>) -> Value{
+    let (min, max) = data_range.unwrap_or((None, None));
+    let finite = |val: Option<f32>| val.filter(|v| v.is_finite());
+    json!({"type": data_type, "range": [finite(min), finite(max)]})
+}
+
+fn file_descr(path: &str) -> Value{
+    json!({"source": path})
+}
+
+/// Fields of a 0.3 dataset description
+const DATASET_FIELDS_V0_3: &[&str] = &[
+    "format_version", "type", "name", "description", "covers", "id", "authors", "attachments", "cite",
+    "license", "config", "git_repo", "icon", "links", "maintainers", "tags", "version", "documentation", "source",
+];
+
+/// Converts an inline 0.2 dataset description (the kind 0.4 models embed) to 0.3.
+///
+/// 0.2 lists attachments like 0.4 models do and has a `download_url` which becomes the `source` if there
+/// is none. Any other field that 0.3 doesn't know about (e.g. `rdf_source` or `badges`) is moved into
+/// `config` so that it isn't lost. Linked datasets and 0.3 descriptions are left untouched.
+fn convert_training_data(mut training_data: Value) -> ConversionResult<Value>{
+    let Some(dataset) = training_data.as_object_mut() else {
+        return Ok(training_data)
+    };
+    if dataset.keys().all(|key| key == "id"){
+        return Ok(training_data)
+    }
+    let format_version = match dataset.get("format_version"){
+        Some(Value::String(format_version)) => format_version.clone(),
+        Some(other) => other.to_string(),
+        None => return Err(LegacyModelConversionError::UnsupportedDatasetVersion("<missing>".into())),
+    };
+    let version = Version::try_from(format_version.clone())
+        .map_err(|_| LegacyModelConversionError::UnsupportedDatasetVersion(format_version.clone()))?;
+    if version >= Version::version_0_3_0() && version < Version::major_minor_patch(0, 4, 0){
+        return Ok(training_data)
+    }
+    if version < Version::major_minor_patch(0, 2, 0) || version >= Version::version_0_3_0(){
+        return Err(LegacyModelConversionError::UnsupportedDatasetVersion(format_version))
+    }
+
+    if let Some(mut attachments) = dataset.remove("attachments"){
+        let files: Vec<Value> = attachments["files"].as_array().into_iter().flatten()
+            .filter_map(|path| path.as_str())
+            .map(file_descr)
+            .collect();
+        if let Some(attachments) = attachments.as_object_mut(){
+            attachments.remove("files");
+            if !attachments.is_empty(){
+                return Err(LegacyModelConversionError::UnsupportedDatasetAttachments(attachments.keys().cloned().collect()))
+            }
+        }
+        dataset.insert("attachments".into(), json!(files));
+    }
+    if !dataset.contains_key("source"){
+        if let Some(download_url) = dataset.remove("download_url"){
+            dataset.insert("source".into(), download_url);
+        }
+    }
+    let unknown_keys: Vec<String> = dataset.keys()
+        .filter(|key| !DATASET_FIELDS_V0_3.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    let mut config = match dataset.remove("config"){
+        Some(Value::Object(config)) => config,
+        _ => Map::new(),
+    };
+    for key in unknown_keys{
+        if let Some(value) = dataset.remove(&key){
+            config.insert(key, value);
+        }
+    }
+    dataset.insert("config".into(), Value::Object(config));
+    dataset.insert("format_version".into(), json!(Version::version_0_3_0()));
+    Ok(training_data)
+}
+
+fn convert_weights_entry(entry: &WeightsEntryV0_4, format: &str) -> ConversionResult<Value>{
+    // 0.5 requires framework versions which were optional in 0.4; these are the oldest ones the spec assumes
+    let pytorch_version = entry.pytorch_version.clone().unwrap_or_else(|| Version::major_minor_patch(1, 10, 0));
+    let tensorflow_version = entry.tensorflow_version.clone().unwrap_or_else(|| Version::major_minor_patch(1, 15, 0));
+
+    let mut out = Map::new();
+    out.insert("source".into(), json!(entry.source));
+    if let Some(sha256) = &entry.sha256{
+        out.insert("sha256".into(), json!(sha256));
+    }
+    if let Some(authors) = &entry.authors{
+        out.insert("authors".into(), authors.clone());
+    }
+    out.insert("parent".into(), json!(entry.parent));
+    if let Some(dependencies) = &entry.dependencies{
+        let Some(env_file) = dependencies.strip_prefix("conda:") else {
+            return Err(LegacyModelConversionError::UnsupportedDependencies(dependencies.clone()))
+        };
+        out.insert("dependencies".into(), file_descr(env_file));
+    }
+    match format{
+        "pytorch_state_dict" => {
+            let architecture = entry.architecture.as_deref().unwrap_or("");
+            let Some((location, callable)) = architecture.rsplit_once(':') else {
+                return Err(LegacyModelConversionError::BadPytorchArchitecture(architecture.to_owned()))
+            };
+            let architecture = if location.ends_with(".py") || location.contains('/'){
+                let mut arch = Map::new();
+                arch.insert("source".into(), json!(location));
+                if let Some(sha256) = &entry.architecture_sha256{
+                    arch.insert("sha256".into(), json!(sha256));
+                }
+                arch.insert("callable".into(), json!(callable));
+                arch.insert("kwargs".into(), Value::Object(entry.kwargs.clone()));
+                Value::Object(arch)
+            }else{
+                json!({"import_from": location, "callable": callable, "kwargs": entry.kwargs})
+            };
+            out.insert("architecture".into(), architecture);
+            out.insert("pytorch_version".into(), json!(pytorch_version));
+        },
+        "torchscript" => { out.insert("pytorch_version".into(), json!(pytorch_version)); },
+        "onnx" => { out.insert("opset_version".into(), json!(entry.opset_version.unwrap_or(15))); },
+        _ => { out.insert("tensorflow_version".into(), json!(tensorflow_version)); },
+    }
+    Ok(Value::Object(out))
+}
+
+impl TryFrom<ModelRdfV0_4> for ModelRdfV0_5{
+    type Error = LegacyModelConversionError;
+    fn try_from(legacy: ModelRdfV0_4) -> Result<Self, Self::Error> {
+        if legacy.test_inputs.len() != legacy.inputs.len(){
+            return Err(LegacyModelConversionError::TestTensorCountMismatch{
+                expected: legacy.inputs.len(), found: legacy.test_inputs.len()
+            })
+        }
+        if legacy.test_outputs.len() != legacy.outputs.len(){
+            return Err(LegacyModelConversionError::TestTensorCountMismatch{
+                expected: legacy.outputs.len(), found: legacy.test_outputs.len()
+            })
+        }
+
+        let inputs = legacy.inputs.iter().enumerate()
+            .map(|(idx, inp)| -> ConversionResult<Value>{
+                let preprocessing = inp.preprocessing.iter()
+                    .map(|step| convert_processing(&inp.name, &inp.axes, step))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(json!({
+                    "id": tensor_id(&inp.name),
+                    "description": inp.description,
+                    "axes": input_axes(inp)?,
+                    "data": tensor_data(&inp.data_type, inp.data_range),
+                    "preprocessing": preprocessing,
+                    "test_tensor": file_descr(&legacy.test_inputs[idx]),
+                    "sample_tensor": legacy.sample_inputs.get(idx).map(|path| file_descr(path)),
+                }))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = legacy.outputs.iter().enumerate()
+            .map(|(idx, out)| -> ConversionResult<Value>{
+                let postprocessing = out.postprocessing.iter()
+                    .map(|step| convert_processing(&out.name, &out.axes, step))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(json!({
+                    "id": tensor_id(&out.name),
+                    "description": out.description,
+                    "axes": output_axes(out, &legacy.inputs)?,
+                    "data": tensor_data(&out.data_type, out.data_range),
+                    "postprocessing": postprocessing,
+                    "test_tensor": file_descr(&legacy.test_outputs[idx]),
+                    "sample_tensor": legacy.sample_outputs.get(idx).map(|path| file_descr(path)),
+                }))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut weights = Map::new();
+        let weights_entries = [
+            ("keras_hdf5", &legacy.weights.keras_hdf5),
+            ("onnx", &legacy.weights.onnx),
+            ("pytorch_state_dict", &legacy.weights.pytorch_state_dict),
+            ("tensorflow_js", &legacy.weights.tensorflow_js),
+            ("tensorflow_saved_model_bundle", &legacy.weights.tensorflow_saved_model_bundle),
+            ("torchscript", &legacy.weights.torchscript),
+        ];
+        for (format, entry) in weights_entries{
+            if let Some(entry) = entry{
+                weights.insert(format.into(), convert_weights_entry(entry, format)?);
+            }
+        }
+
+        let mut rdf = json!({
+            "format_version": Version_0_5_x::new(),
+            "type": "model",
+            "name": legacy.name,
+            "description": legacy.description,
+            "authors": legacy.authors,
+            "cite": legacy.cite,
+            "license": legacy.license,
+            "documentation": legacy.documentation,
+            "tags": legacy.tags,
+            "covers": legacy.covers,
+            "attachments": legacy.attachments.unwrap_or_default().files.iter().map(|path| file_descr(path)).collect::<Vec<_>>(),
+            "config": legacy.config,
+            "links": legacy.links,
+            "maintainers": legacy.maintainers,
+            "inputs": inputs,
+            "outputs": outputs,
+            "weights": weights,
+        });
+        let optional_fields = [
+            ("icon", legacy.icon),
+            ("id", legacy.id),
+            ("git_repo", legacy.git_repo),
+            ("version", legacy.version),
+            ("timestamp", legacy.timestamp),
+            ("training_data", legacy.training_data.map(convert_training_data).transpose()?),
+            ("run_mode", legacy.run_mode.map(|run_mode| json!(run_mode.name))),
+        ];
+        for (key, value) in optional_fields{
+            if let Some(value) = value{
+                rdf[key] = value;
+            }
+        }
+        Ok(serde_json::from_value(rdf)?)
+    }
+}
+
+#[test]
+fn test_legacy_model_conversion(){
+    use super::{InputAxis, OutputAxis, PreprocessingDescr};
+
+    let raw = serde_json::json!({
+        "format_version": "0.4.10",
+        "type": "model",
+        "name": "Legacy UNet 2D",
+        "description": "A legacy model",
+        "authors": [{"name": "Someone"}],
+        "cite": [{"text": "Some paper", "doi": "10.1000/182"}],
+        "license": "MIT",
+        "documentation": "README.md",
+        "test_inputs": ["test_input.npy"],
+        "test_outputs": ["test_output.npy"],
+        "inputs": [{
+            "name": "raw",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": {"min": [1, 1, 64, 64], "step": [0, 0, 16, 16]},
+            "preprocessing": [
+                {"name": "zero_mean_unit_variance", "kwargs": {"mode": "per_sample", "axes": "yx"}},
+                {"name": "scale_linear", "kwargs": {"gain": [2.0], "offset": [1.0], "axes": "yx"}},
+            ],
+        }],
+        "outputs": [{
+            "name": "mask",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": {"reference_tensor": "raw", "scale": [1.0, 2.0, 1.0, 1.0], "offset": [0, 0, 0, 0]},
+            "halo": [0, 0, 8, 8],
+            "postprocessing": [{"name": "sigmoid"}],
+        }],
+        "weights": {
+            "pytorch_state_dict": {
+                "source": "weights.pt",
+                "architecture": "unet.py:UNet2d",
+                "kwargs": {"depth": 3},
+                "dependencies": "conda:environment.yaml",
+            },
+            "onnx": {"source": "weights.onnx"},
+        },
+    });
+    let legacy: ModelRdfV0_4 = serde_json::from_value(raw).unwrap();
+    let model = ModelRdfV0_5::try_from(legacy).unwrap();
+
+    let input = &model.inputs[0];
+    assert_eq!(input.meta.id.to_string(), "raw");
+    let input_axes: Vec<_> = input.meta.axes().iter().collect();
+    assert!(matches!(input_axes[0], InputAxis::Batch(_)));
+    assert!(matches!(input_axes[1], InputAxis::Channel(_)));
+    assert!(matches!(input_axes[3], InputAxis::Space(_)));
+    assert!(matches!(input.meta.preprocessing()[0], PreprocessingDescr::ZeroMeanUnitVariance(_)));
+    assert!(matches!(input.meta.preprocessing()[1], PreprocessingDescr::ScaleLinear(_)));
+
+    let output_axes: Vec<_> = model.outputs[0].metadata.axes().iter().collect();
+    let OutputAxis::Channel(channel) = output_axes[1] else { panic!("expected a channel axis") };
+    assert_eq!(channel.channel_names.len().get(), 2);
+    assert!(matches!(output_axes[2], OutputAxis::Space(_)));
+
+    assert!(model.weights.pytorch_state_dict.is_some());
+    assert!(model.weights.onnx.is_some());
+
+    let too_old = serde_json::json!({"format_version": "0.3.6", "name": "whatever"});
+    assert!(serde_json::from_value::<ModelRdfV0_4>(too_old).is_err());
+}
+
+#[test]
+fn test_legacy_implicit_shapes_and_processing_modes(){
+    let raw = |output_shape: Value, mode: &str| serde_json::json!({
+        "format_version": "0.4.10",
+        "type": "model",
+        "name": "Legacy UNet 2D",
+        "description": "A legacy model",
+        "authors": [{"name": "Someone"}],
+        "cite": [{"text": "Some paper", "doi": "10.1000/182"}],
+        "license": "MIT",
+        "documentation": "README.md",
+        "test_inputs": ["test_input.npy"],
+        "test_outputs": ["test_output.npy"],
+        "inputs": [{
+            "name": "raw",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": {"min": [1, 1, 64, 64], "step": [0, 0, 16, 16]},
+            "preprocessing": [{"name": "scale_range", "kwargs": {"mode": mode, "axes": "yx"}}],
+        }],
+        "outputs": [{
+            "name": "mask",
+            "axes": "bcyx",
+            "data_type": "float32",
+            "shape": output_shape,
+            "halo": [0, 0, 8, 8],
+        }],
+        "weights": {"onnx": {"source": "weights.onnx"}},
+    });
+    let shape = json!({"reference_tensor": "raw", "scale": [1.0, 1.0, 0.5, 0.5], "offset": [0, 0, 4, 4]});
+    let legacy: ModelRdfV0_4 = serde_json::from_value(raw(shape, "per_sample")).unwrap();
+    let axes = output_axes(&legacy.outputs[0], &legacy.inputs).unwrap();
+    assert_eq!(axes[2]["size"], json!({"tensor_id": "raw", "axis_id": "y", "offset": 8}));
+    assert_eq!(axes[2]["scale"], json!(2.0));
+    assert_eq!(axes[3]["halo"], json!(8));
+    assert!(axes[1].get("halo").is_none());
+    ModelRdfV0_5::try_from(legacy).unwrap();
+
+    let shape = json!({"reference_tensor": "raw", "scale": [1.0, 1.0, 1.0, 1.0], "offset": [0, 0, -1, 0]});
+    let legacy: ModelRdfV0_4 = serde_json::from_value(raw(shape, "per_sample")).unwrap();
+    assert!(matches!(
+        ModelRdfV0_5::try_from(legacy),
+        Err(LegacyModelConversionError::BadImplicitSize{axis: 'y', ..})
+    ));
+
+    let shape = json!({"reference_tensor": "raw", "scale": [1.0, 1.0, 1.0, 1.0], "offset": [0, 0, 0, 0]});
+    let legacy: ModelRdfV0_4 = serde_json::from_value(raw(shape, "per_dataset")).unwrap();
+    assert!(matches!(
+        ModelRdfV0_5::try_from(legacy),
+        Err(LegacyModelConversionError::UnsupportedProcessingMode{..})
+    ));
+
+    // zero_mean_unit_variance defaults to fixed statistics, which must then be given
+    let step = |kwargs: Value| ProcessingV0_4{name: "zero_mean_unit_variance".into(), kwargs: serde_json::from_value(kwargs).unwrap()};
+    let converted = convert_processing("raw", "bcyx", &step(json!({"mean": 3.0, "std": 2.0}))).unwrap();
+    assert_eq!(converted, json!({"id": "fixed_zero_mean_unit_variance", "kwargs": {"mean": 3.0, "std": 2.0}}));
+    assert!(matches!(
+        convert_processing("raw", "bcyx", &step(json!({"mean": 3.0}))),
+        Err(LegacyModelConversionError::MissingProcessingKwarg{kwarg, ..}) if kwarg == "std"
+    ));
+    let converted = convert_processing("raw", "bcyx", &step(json!({"mode": "per_sample", "axes": "yx"}))).unwrap();
+    assert_eq!(converted["id"], json!("zero_mean_unit_variance"));
+}
+
+#[test]
+fn test_legacy_training_data_conversion(){
+    let converted = convert_training_data(json!({
+        "format_version": "0.2.3",
+        "type": "dataset",
+        "name": "Cells",
+        "description": "Some cells",
+        "attachments": {"files": ["notes.txt"]},
+        "download_url": "https://example.com/cells.zip",
+        "rdf_source": "https://example.com/rdf.yaml",
+        "config": {"custom": 1},
+    })).unwrap();
+    assert_eq!(converted["format_version"], json!("0.3.0"));
+    assert_eq!(converted["attachments"], json!([{"source": "notes.txt"}]));
+    assert_eq!(converted["source"], json!("https://example.com/cells.zip"));
+    assert_eq!(converted["config"], json!({"custom": 1, "rdf_source": "https://example.com/rdf.yaml"}));
+    serde_json::from_value::<super::dataset_descr::DatasetDescr>(converted).unwrap();
+
+    let linked = json!({"id": "some-dataset"});
+    assert_eq!(convert_training_data(linked.clone()).unwrap(), linked);
+    assert!(matches!(
+        convert_training_data(json!({"format_version": "0.1.0", "name": "Cells"})),
+        Err(LegacyModelConversionError::UnsupportedDatasetVersion(_))
+    ));
+    assert!(matches!(
+        convert_training_data(json!({"format_version": "0.2.0", "attachments": {"files": [], "samples": ["a.npy"]}})),
+        Err(LegacyModelConversionError::UnsupportedDatasetAttachments(_))
+    ));
+}
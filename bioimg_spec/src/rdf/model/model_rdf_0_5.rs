@@ -147,3 +147,52 @@ pub struct ModelRdfV0_5 {
     /// The available weight formats determine which consumers can use this model
     pub weights: WeightsDescr,
 }
+
+impl ModelRdfV0_5{
+    /// The earliest format version that can represent all fields used by this model.
+    ///
+    /// Of the 0.5.4 additions, only the `comment` of weights entries is parsed into a field, so it is
+    /// the only one that can require 0.5.4 here. Other newer fields only survive inside the free-form
+    /// `config`, which is written back as-is and doesn't affect the chosen version.
+    pub fn minimum_format_version(&self) -> Version_0_5_x{
+        if self.weights.bases().any(|base| base.comment.is_some()){
+            return Version_0_5_x::version_0_5_4()
+        }
+        Version_0_5_x::new()
+    }
+}
+
+#[test]
+fn test_format_version_follows_used_features(){
+    let mut raw = serde_json::json!({
+        "format_version": "0.5.4",
+        "type": "model",
+        "name": "Some model",
+        "description": "A model",
+        "authors": [{"name": "Someone"}],
+        "cite": [{"text": "Some paper", "doi": "10.1000/182"}],
+        "license": "MIT",
+        "documentation": "README.md",
+        "inputs": [{
+            "id": "raw",
+            "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": 64}],
+            "test_tensor": {"source": "raw.npy"},
+        }],
+        "outputs": [{
+            "id": "mask",
+            "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": 64}],
+            "test_tensor": {"source": "mask.npy"},
+        }],
+        "weights": {"onnx": {"source": "weights.onnx", "opset_version": 15, "comment": "exported from pytorch"}},
+    });
+    let model: ModelRdfV0_5 = serde_json::from_value(raw.clone()).unwrap();
+    assert_eq!(model.minimum_format_version(), Version_0_5_x::version_0_5_4());
+
+    raw["weights"]["onnx"].as_object_mut().unwrap().remove("comment");
+    let model: ModelRdfV0_5 = serde_json::from_value(raw.clone()).unwrap();
+    assert_eq!(model.minimum_format_version(), Version_0_5_x::new());
+    assert!(serde_json::to_value(&model).unwrap()["weights"]["onnx"].get("comment").is_none());
+
+    raw["format_version"] = serde_json::json!("0.5.5");
+    assert!(serde_json::from_value::<ModelRdfV0_5>(raw).is_err());
+}
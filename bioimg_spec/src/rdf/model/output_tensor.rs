@@ -4,14 +4,20 @@ use serde::{Deserialize, Serialize};
 
 use crate::rdf::{model::{postprocessing::ScaleMeanVarianceDescr, preprocessing::{BinarizeDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId}, FileDescription};
 
-use super::{axes::output_axes::OutputAxisGroup, postprocessing::PostprocessingDescr, TensorId, TensorTextDescription};
+use super::{dtype_flow::{check_dtype_flow, with_implicit_ensure_dtype, DtypeFlowError}, DataType, axes::output_axes::{OutputAxis, OutputAxisGroup}, tensor_data_descr::TensorDataDescrs, postprocessing::PostprocessingDescr, TensorId, TensorTextDescription};
 
 #[derive(thiserror::Error, Debug)]
 pub enum OutputTensorParsingError{
     #[error("Axis reference to non-existing axis")]
     PreprocessingReferencesNonExistingAxis,
+    #[error("Found {num_descrs} per-channel data descriptions but tensor has {num_channels} channels")]
+    DataDoesNotMatchChannels{num_descrs: usize, num_channels: usize},
+    #[error("Per-channel data descriptions have mixed data types ({first} and {other})")]
+    MixedChannelDataTypes{first: DataType, other: DataType},
     #[error("Found a self-reference from/to {tensor_id}")]
-    SelfReference{tensor_id: TensorId}
+    SelfReference{tensor_id: TensorId},
+    #[error("Bad postprocessing: {0}")]
+    DtypeFlowError(#[from] DtypeFlowError),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -36,11 +42,30 @@ pub struct OutputTensorMetadata{
     pub description: TensorTextDescription,
     axes: OutputAxisGroup,
     postprocessing: Vec<PostprocessingDescr>,
+    /// Description of the tensor's data values, optionally per channel. If not declared, this
+    /// is whatever data type the postprocessing produces
+    data: TensorDataDescrs,
+    /// Whether `data` was given explicitly rather than derived from the postprocessing
+    data_is_declared: bool,
 }
 
 impl OutputTensorMetadata{
     pub fn axes(&self) -> &OutputAxisGroup{ &self.axes }
     pub fn postprocessing(&self) -> &Vec<PostprocessingDescr>{ &self.postprocessing }
+    pub fn data(&self) -> &TensorDataDescrs{ &self.data }
+    /// The `data` as written in the rdf, if it was given at all
+    pub fn declared_data(&self) -> Option<&TensorDataDescrs>{ self.data_is_declared.then_some(&self.data) }
+
+    /// The postprocessing steps including implicit `ensure_dtype` steps: one converting the raw model output to
+    /// float32 unless postprocessing starts with `ensure_dtype` or `binarize`, and one converting the result to
+    /// the tensor's data type unless postprocessing ends with `ensure_dtype` or `binarize`
+    pub fn normalized_postprocessing(&self) -> Vec<PostprocessingDescr>{
+        normalize_postprocessing(&self.postprocessing, &self.data)
+    }
+}
+
+fn normalize_postprocessing(postprocessing: &[PostprocessingDescr], data: &TensorDataDescrs) -> Vec<PostprocessingDescr>{
+    with_implicit_ensure_dtype(postprocessing, DataType::Float32, data.data_type(), true)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -52,6 +77,8 @@ pub struct OutputTensorMetadataMsg{
     #[serde(default)]
     pub description: TensorTextDescription,
     pub axes: OutputAxisGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<TensorDataDescrs>,
 }
 
 impl TryFrom<OutputTensorMetadataMsg> for OutputTensorMetadata{
@@ -94,11 +121,40 @@ impl TryFrom<OutputTensorMetadataMsg> for OutputTensorMetadata{
                 _ => (),
             }
         }
+        let num_channels = message.axes.iter()
+            .find_map(|axis| match axis{
+                OutputAxis::Channel(channel_axis) => Some(usize::from(channel_axis.size())),
+                _ => None,
+            });
+        let data_is_declared = message.data.is_some();
+        let data = message.data.unwrap_or_default();
+        if let TensorDataDescrs::PerChannel(descrs) = &data{
+            if Some(descrs.len().get()) != num_channels{
+                return Err(OutputTensorParsingError::DataDoesNotMatchChannels{
+                    num_descrs: descrs.len().get(), num_channels: num_channels.unwrap_or(0),
+                })
+            }
+            let first = data.data_type();
+            if let Some(other) = data.as_slice().iter().map(|descr| descr.data_type()).find(|dtype| *dtype != first){
+                return Err(OutputTensorParsingError::MixedChannelDataTypes{first, other})
+            }
+        }
+        // without a declared `data`, the float32 default only applies if postprocessing doesn't pick a type itself
+        let produced = check_dtype_flow(DataType::Float32, &normalize_postprocessing(&message.postprocessing, &data))?;
+        let data = match data_is_declared{
+            true if produced != data.data_type() => {
+                return Err(DtypeFlowError::FinalDtypeMismatch{produced, declared: data.data_type()}.into())
+            },
+            true => data,
+            false => TensorDataDescrs::from(produced),
+        };
         Ok(Self{
             id: message.id,
             postprocessing: message.postprocessing,
             description: message.description,
             axes: message.axes,
+            data,
+            data_is_declared,
         })
     }
 }
@@ -110,6 +166,46 @@ impl From<OutputTensorMetadata> for OutputTensorMetadataMsg{
             description: value.description,
             postprocessing: value.postprocessing,
             axes: value.axes,
+            data: value.data_is_declared.then_some(value.data),
         }
     }
 }
+
+#[test]
+fn test_output_data_type_from_postprocessing(){
+    use super::DataType;
+
+    let meta = |data: Option<serde_json::Value>| {
+        let mut raw = serde_json::json!({
+            "id": "mask",
+            "axes": [{"type": "space", "id": "x", "size": 4}],
+            "postprocessing": [{"id": "binarize", "kwargs": {"threshold": 0.5}}],
+        });
+        if let Some(data) = data{
+            raw["data"] = data;
+        }
+        serde_json::from_value::<OutputTensorMetadata>(raw)
+    };
+
+    let implicit = meta(None).unwrap();
+    assert_eq!(implicit.data().data_type(), DataType::Bool);
+    assert!(implicit.declared_data().is_none());
+    assert!(serde_json::to_value(&implicit).unwrap().get("data").is_none());
+
+    let declared = meta(Some(serde_json::json!({"type": "bool"}))).unwrap();
+    assert!(declared.declared_data().is_some());
+    assert!(meta(Some(serde_json::json!({"type": "float32"}))).is_err());
+
+    let unprocessed: OutputTensorMetadata = serde_json::from_value(serde_json::json!({
+        "id": "out", "axes": [{"type": "space", "id": "x", "size": 4}],
+    })).unwrap();
+    assert_eq!(unprocessed.data().data_type(), DataType::Float32);
+
+    let per_channel = |types: [&str; 2]| serde_json::from_value::<OutputTensorMetadata>(serde_json::json!({
+        "id": "out",
+        "axes": [{"type": "channel", "channel_names": ["a", "b"]}, {"type": "space", "id": "x", "size": 4}],
+        "data": [{"type": types[0]}, {"type": types[1]}],
+    }));
+    assert!(per_channel(["float32", "float32"]).is_ok());
+    assert!(per_channel(["float32", "uint8"]).unwrap_err().to_string().contains("mixed data types"));
+}
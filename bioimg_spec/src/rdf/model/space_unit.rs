@@ -84,3 +84,42 @@ pub enum SpaceUnit{
     #[strum(to_string = "zettameter")]
     Zettameter,
 }
+
+impl SpaceUnit{
+    /// Length of one of this unit, in meters
+    pub fn in_meters(&self) -> f64{
+        match self{
+            Self::Attometer => 1e-18,
+            Self::Angstrom => 1e-10,
+            Self::Centimeter => 1e-2,
+            Self::Decimeter => 1e-1,
+            Self::Exameter => 1e18,
+            Self::Femtometer => 1e-15,
+            Self::Foot => 0.3048,
+            Self::Gigameter => 1e9,
+            Self::Hectometer => 1e2,
+            Self::Inch => 0.0254,
+            Self::Kilometer => 1e3,
+            Self::Megameter => 1e6,
+            Self::Meter => 1.0,
+            Self::Micrometer => 1e-6,
+            Self::Mile => 1609.344,
+            Self::Millimeter => 1e-3,
+            Self::Nanometer => 1e-9,
+            Self::Parsec => 3.085_677_581_491_367e16,
+            Self::Petameter => 1e15,
+            Self::Picometer => 1e-12,
+            Self::Terameter => 1e12,
+            Self::Yard => 0.9144,
+            Self::Yoctometer => 1e-24,
+            Self::Yottameter => 1e24,
+            Self::Zeptometer => 1e-21,
+            Self::Zettameter => 1e21,
+        }
+    }
+
+    /// Converts `value`, measured in this unit, into `other`
+    pub fn convert(&self, value: f64, other: SpaceUnit) -> f64{
+        value * self.in_meters() / other.in_meters()
+    }
+}
@@ -12,6 +12,7 @@ pub enum TensorDataDescr {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
 pub enum TVs {
     Ints(NonEmptyList<i64>),
     Floats(NonEmptyList<f32>),
@@ -33,6 +34,12 @@ pub enum TensorDataUnit {
     Si(SiUnit),
 }
 
+impl Default for TensorDataUnit{
+    fn default() -> Self {
+        Self::ArbitraryUnit(LitStr::default())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NominalOrOrdinalDataDescr {
     /// A fixed set of nominal or an ascending sequence of ordinal values.
@@ -55,15 +62,80 @@ fn _default_data_type() -> DataType {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IntervalOrRatioDataDescr {
     #[serde(rename = "type")]
-    data_type: DataType,
-    range: (Option<f32>, Option<f32>),
-    unit: TensorDataUnit,
+    #[serde(default)]
+    pub data_type: DataType,
+    /// Tuple `(minimum, maximum)` specifying the allowed range of the data in this tensor.
+    /// `None` corresponds to min/max of what can be expressed by `data_type`.
+    #[serde(default)]
+    pub range: (Option<f32>, Option<f32>),
+    #[serde(default)]
+    pub unit: TensorDataUnit,
     #[serde(default = "_default_scale")]
-    scale: f32,
+    pub scale: f32,
     #[serde(default)]
-    offset: Option<f32>,
+    pub offset: Option<f32>,
+}
+
+impl Default for IntervalOrRatioDataDescr{
+    fn default() -> Self {
+        Self{
+            data_type: DataType::Float32,
+            range: (None, None),
+            unit: TensorDataUnit::default(),
+            scale: _default_scale(),
+            offset: None,
+        }
+    }
 }
 
 fn _default_scale() -> f32 {
     1.0
 }
+
+impl TensorDataDescr{
+    pub fn data_type(&self) -> DataType{
+        match self{
+            Self::NominalOrOrdinal(descr) => descr.data_type,
+            Self::IntervalOrRatio(descr) => descr.data_type,
+        }
+    }
+}
+
+impl From<DataType> for TensorDataDescr{
+    fn from(data_type: DataType) -> Self {
+        Self::IntervalOrRatio(IntervalOrRatioDataDescr{data_type, ..Default::default()})
+    }
+}
+
+/// The `data` of a tensor: either one description for the whole tensor or one per channel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TensorDataDescrs{
+    Single(TensorDataDescr),
+    PerChannel(NonEmptyList<TensorDataDescr>),
+}
+
+impl Default for TensorDataDescrs{
+    fn default() -> Self {
+        Self::Single(TensorDataDescr::IntervalOrRatio(Default::default()))
+    }
+}
+
+impl From<DataType> for TensorDataDescrs{
+    fn from(data_type: DataType) -> Self {
+        Self::Single(data_type.into())
+    }
+}
+
+impl TensorDataDescrs{
+    pub fn as_slice(&self) -> &[TensorDataDescr]{
+        match self{
+            Self::Single(descr) => std::slice::from_ref(descr),
+            Self::PerChannel(descrs) => descrs,
+        }
+    }
+    /// Data type of the tensor. Tensor metadata rejects per-channel descriptions that disagree, so the first one is used
+    pub fn data_type(&self) -> DataType{
+        self.as_slice()[0].data_type()
+    }
+}
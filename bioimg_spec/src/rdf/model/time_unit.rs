@@ -75,3 +75,39 @@ pub enum TimeUnit{
     #[strum(to_string = "zettasecond")]
     Zettasecond,
 }
+
+impl TimeUnit{
+    /// Duration of one of this unit, in seconds
+    pub fn in_seconds(&self) -> f64{
+        match self{
+            Self::Attosecond => 1e-18,
+            Self::Centisecond => 1e-2,
+            Self::Day => 86400.0,
+            Self::Decisecond => 1e-1,
+            Self::Exasecond => 1e18,
+            Self::Femtosecond => 1e-15,
+            Self::Gigasecond => 1e9,
+            Self::Hectosecond => 1e2,
+            Self::Hour => 3600.0,
+            Self::Kilosecond => 1e3,
+            Self::Megasecond => 1e6,
+            Self::Microsecond => 1e-6,
+            Self::Millisecond => 1e-3,
+            Self::Minute => 60.0,
+            Self::Nanosecond => 1e-9,
+            Self::Petasecond => 1e15,
+            Self::Picosecond => 1e-12,
+            Self::Second => 1.0,
+            Self::Terasecond => 1e12,
+            Self::Yoctosecond => 1e-24,
+            Self::Yottasecond => 1e24,
+            Self::Zeptosecond => 1e-21,
+            Self::Zettasecond => 1e21,
+        }
+    }
+
+    /// Converts `value`, measured in this unit, into `other`
+    pub fn convert(&self, value: f64, other: TimeUnit) -> f64{
+        value * self.in_seconds() / other.in_seconds()
+    }
+}
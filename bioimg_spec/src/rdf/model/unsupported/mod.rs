@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::rdf::Version;
+use crate::rdf::{version::Version_0_5_x, Version};
 
 #[derive(thiserror::Error, Debug)]
 pub enum LegacyVersionParsingError{
@@ -45,21 +45,21 @@ pub enum FutureVersionParsingError{
     VersionTooLow{found: Version}
 }
 
+/// A format version newer than the latest one this crate supports
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
-#[allow(non_camel_case_types)]
 #[serde(try_from = "Version")]
-pub struct Version_0_5_4_OrLater(Version);
+pub struct FutureVersion(Version);
 
-impl Display for Version_0_5_4_OrLater{
+impl Display for FutureVersion{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
 }
 
-impl TryFrom<Version> for Version_0_5_4_OrLater{
+impl TryFrom<Version> for FutureVersion{
     type Error = FutureVersionParsingError;
     fn try_from(value: Version) -> Result<Self, Self::Error> {
-        if value > Version::version_0_5_3() {
+        if value > Version_0_5_x::latest_supported_version() {
             return Ok(Self(value))
         }
         return Err(FutureVersionParsingError::VersionTooLow { found: value })
@@ -71,6 +71,6 @@ pub struct UnsupportedFutureModel{
     /// Version of the bioimage.io model description specification used.
     /// When creating a new model always use the latest micro/patch version described here.
     /// The `format_version` is important for any consumer software to understand how to parse the fields.
-    pub format_version: Version_0_5_4_OrLater,
+    pub format_version: FutureVersion,
 }
 
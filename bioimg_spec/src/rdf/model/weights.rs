@@ -45,6 +45,19 @@ impl Deref for WeightsDescr{
     }
 }
 
+impl MaybeSomeWeightsDescr{
+    pub fn bases(&self) -> impl Iterator<Item=&WeightsDescrBase>{
+        [
+            self.keras_hdf5.as_ref().map(|w| &w.base),
+            self.onnx.as_ref().map(|w| &w.base),
+            self.pytorch_state_dict.as_ref().map(|w| &w.base),
+            self.tensorflow_js.as_ref().map(|w| &w.base),
+            self.tensorflow_saved_model_bundle.as_ref().map(|w| &w.base),
+            self.torchscript.as_ref().map(|w| &w.base),
+        ].into_iter().flatten()
+    }
+}
+
 impl TryFrom<MaybeSomeWeightsDescr> for WeightsDescr{
     type Error = ModelWeightsParsingError;
     fn try_from(value: MaybeSomeWeightsDescr) -> Result<Self, Self::Error> {
@@ -101,6 +114,9 @@ pub struct WeightsDescrBase{
     #[serde(default)]
     pub authors: Option<Vec<Author2>>,
     pub parent: Option<WeightsFormat>,
+    /// A comment about this weights entry, for example how these weights were created (since 0.5.4)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
 }
 
 
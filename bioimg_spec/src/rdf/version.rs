@@ -27,12 +27,21 @@ impl Version{
             ..Default::default()
         })
     }
+    pub fn version_0_5_4() -> Version{
+        Self::major_minor_patch(0, 5, 4)
+    }
     pub fn version_0_5_3() -> Version{
         Self::major_minor_patch(0, 5, 3)
     }
     pub fn version_0_5_0() -> Version{
         Self::major_minor_patch(0, 5, 0)
     }
+    pub fn version_0_4_0() -> Version{
+        Self::major_minor_patch(0, 4, 0)
+    }
+    pub fn version_0_3_0() -> Version{
+        Self::major_minor_patch(0, 3, 0)
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -79,8 +88,12 @@ impl Version_0_5_x{
     pub fn new() -> Self{
         Self(Version::version_0_5_3())
     }
+    /// 0.5.4 added `comment`s to weights entries
+    pub fn version_0_5_4() -> Self{
+        Self(Version::version_0_5_4())
+    }
     pub fn latest_supported_version() -> Version{
-        Version::version_0_5_3()
+        Version::version_0_5_4()
     }
     pub fn earliest_supported_version() -> Version{
         Version::version_0_5_0()
@@ -90,14 +103,29 @@ impl Version_0_5_x{
 impl TryFrom<Version> for Version_0_5_x {
     type Error = VersionParsingError;
     fn try_from(version: Version) -> Result<Self, Self::Error> {
-        if  version < Version::version_0_5_0() {
+        if  version < Self::earliest_supported_version() {
             return Err(VersionParsingError { reason: format!("Version is too low: {version}") })
         }
-        if  version > Version::version_0_5_3() {
+        if  version > Self::latest_supported_version() {
             return Err(VersionParsingError {
-                reason: format!("Version is too high: {version}. Max supported rdf version is {}", Version::version_0_5_3())
+                reason: format!("Version is too high: {version}. Max supported rdf version is {}", Self::latest_supported_version())
             })
         }
         Ok(Self(version))
     }
 }
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(try_from="Version")]
+pub struct Version_0_4_x(Version);
+
+impl TryFrom<Version> for Version_0_4_x {
+    type Error = VersionParsingError;
+    fn try_from(version: Version) -> Result<Self, Self::Error> {
+        if version < Version::version_0_4_0() || version >= Version::version_0_5_0() {
+            return Err(VersionParsingError { reason: format!("Expected a 0.4.x version, found {version}") })
+        }
+        Ok(Self(version))
+    }
+}